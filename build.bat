@@ -1 +1 @@
-cargo build --release --target i686-pc-windows-msvc -Zbuild-std
\ No newline at end of file
+cargo build --release --target i686-pc-windows-msvc -Zbuild-std --features hook
@@ -0,0 +1,425 @@
+use std::ffi::CString;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadErrorKind {
+    // Not enough bits left in the buffer
+    EndOfBuffer,
+    // More bits were requested than the returned integer can hold
+    InvalidWidth,
+    // The bits were read but don't describe a valid value
+    InvalidValue,
+    // The packet doesn't match its header checksum
+    ChecksumMismatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadError {
+    pub kind: ReadErrorKind,
+    // Bit position in the buffer where the read started
+    pub pos: usize,
+    // Number of bits requested
+    pub bits: usize,
+}
+
+impl ReadError {
+    pub fn new(kind: ReadErrorKind, pos: usize, bits: usize) -> Self {
+        Self {
+            kind,
+            pos,
+            bits
+        }
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ReadErrorKind::EndOfBuffer => {
+                write!(f, "tried to read {} bits at bit {} past the end of the buffer", self.bits, self.pos)
+            },
+            ReadErrorKind::InvalidWidth => {
+                write!(f, "invalid read width of {} bits at bit {}", self.bits, self.pos)
+            },
+            ReadErrorKind::InvalidValue => {
+                write!(f, "invalid value of {} bits at bit {}", self.bits, self.pos)
+            },
+            ReadErrorKind::ChecksumMismatch => {
+                write!(f, "checksum mismatch over {} bits, corrupted packet or wrong game profile", self.bits)
+            },
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+pub type ReadResult<T> = Result<T, ReadError>;
+
+pub(crate) const COORD_INTEGER_BITS: usize = 14;
+pub(crate) const COORD_FRACTIONAL_BITS: usize = 5;
+pub(crate) const COORD_DENOMINATOR: u32 = 1 << COORD_FRACTIONAL_BITS;
+pub(crate) const COORD_RESOLUTION: f32 = 1. / COORD_DENOMINATOR as f32;
+
+// Multiplayer coords are 11 bits wide while in bounds, low precision ones keep 3 fraction bits
+const COORD_INTEGER_BITS_MP: usize = 11;
+const COORD_FRACTIONAL_BITS_MP_LOWPRECISION: usize = 3;
+const COORD_RESOLUTION_LOWPRECISION: f32 = 1. / (1 << COORD_FRACTIONAL_BITS_MP_LOWPRECISION) as f32;
+
+pub(crate) const NORMAL_FRACTIONAL_BITS: usize = 11;
+pub(crate) const NORMAL_DENOMINATOR: u32 = (1 << NORMAL_FRACTIONAL_BITS) - 1;
+pub(crate) const NORMAL_RESOLUTION: f32 = 1. / NORMAL_DENOMINATOR as f32;
+
+// Sign extends the low `bits` bits of `value`
+fn sign_extend(value: u64, bits: usize) -> i64 {
+    if bits == 0 {
+        return 0;
+    }
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+#[derive(Debug, Clone)]
+pub struct BitReader {
+    pub content: Vec<u8>,
+    // Bit position in the buffer
+    pub pos: usize
+}
+
+impl BitReader {
+    pub fn new(content: Vec<u8>) -> Self {
+        Self {
+            content,
+            pos: 0
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (self.pos as f64 / 8.).ceil() as usize >= self.content.len()
+    }
+
+    pub fn bits_left(&self) -> usize {
+        (self.content.len() * 8).saturating_sub(self.pos)
+    }
+
+    // Returns an error if `bits` can't be read into an integer of `max` bits
+    fn check(&self, bits: usize, max: usize) -> ReadResult<()> {
+        if bits > max {
+            return Err(ReadError::new(ReadErrorKind::InvalidWidth, self.pos, bits));
+        }
+        if bits > self.bits_left() {
+            return Err(ReadError::new(ReadErrorKind::EndOfBuffer, self.pos, bits));
+        }
+        Ok(())
+    }
+
+    // Read at most 8 bits
+    pub fn read_u8(&mut self, bits: usize) -> ReadResult<u8> {
+        self.check(bits, 8)?;
+
+        // Calculate the byte position in the buffer
+        let byte_pos = self.pos / 8;
+        // Bit position in the byte
+        let bit_pos = self.pos % 8;
+
+        let read;
+        // Check if we have to read through 2 different parts
+        if bit_pos + bits > 8 {
+            // Read the first part
+            let p1_len =  8 - bit_pos;
+            let p1 = (self.content[byte_pos] >> bit_pos) & (2u8.pow(p1_len as u32) - 1);
+
+            // Read the second part
+            let p2_len = bits - p1_len;
+            let p2 = self.content[byte_pos + 1] & (2u8.pow(p2_len as u32) - 1);
+
+            // Combine both part
+            read = (p2 << p1_len) | p1;
+        } else if bits == 0 {
+            read = 0;
+        } else {
+            // Read the corresponding bits
+            read = (self.content[byte_pos] >> bit_pos) & ((2u64.pow(bits as u32) - 1) as u8);
+        }
+
+        self.pos += bits;
+        Ok(read)
+    }
+
+    // Read at most 16 bits
+    pub fn read_u16(&mut self, bits: usize) -> ReadResult<u16> {
+        self.check(bits, 16)?;
+
+        if bits <= 8 {
+            return Ok(self.read_u8(bits)? as u16);
+        }
+
+        // Read the first and second part
+        let p1 = self.read_u8(8)? as u16;
+        let p2 = self.read_u8(bits - 8)? as u16;
+
+        // Combine both part and return the result
+        Ok((p2 << 8) | p1)
+    }
+
+    // Read at most 32 bits
+    pub fn read_u32(&mut self, bits: usize) -> ReadResult<u32> {
+        self.check(bits, 32)?;
+
+        if bits <= 16 {
+            return Ok(self.read_u16(bits)? as u32);
+        }
+
+        // Read the first and second part
+        let p1 = self.read_u16(16)? as u32;
+        let p2 = self.read_u16(bits - 16)? as u32;
+
+        // Combine both part and return the result
+        Ok((p2 << 16) | p1)
+    }
+
+    // Read at most 64 bits
+    pub fn read_u64(&mut self, bits: usize) -> ReadResult<u64> {
+        self.check(bits, 64)?;
+
+        if bits <= 32 {
+            return Ok(self.read_u32(bits)? as u64);
+        }
+
+        // Read the first and second part
+        let p1 = self.read_u32(32)? as u64;
+        let p2 = self.read_u32(bits - 32)? as u64;
+
+        // Combine both part and return the result
+        Ok((p2 << 32) | p1)
+    }
+
+    // Read at most 8 bits as a two's complement integer, as written by CBitWrite::WriteSBitLong
+    pub fn read_i8(&mut self, bits: usize) -> ReadResult<i8> {
+        Ok(sign_extend(self.read_u8(bits)? as u64, bits) as i8)
+    }
+
+    // Read at most 16 bits as a two's complement integer
+    pub fn read_i16(&mut self, bits: usize) -> ReadResult<i16> {
+        Ok(sign_extend(self.read_u16(bits)? as u64, bits) as i16)
+    }
+
+    // Read at most 32 bits as a two's complement integer
+    pub fn read_i32(&mut self, bits: usize) -> ReadResult<i32> {
+        Ok(sign_extend(self.read_u32(bits)? as u64, bits) as i32)
+    }
+
+    // Read at most 64 bits as a two's complement integer
+    pub fn read_i64(&mut self, bits: usize) -> ReadResult<i64> {
+        Ok(sign_extend(self.read_u64(bits)?, bits))
+    }
+
+    // Read a float as its 32 raw bits, as written by CBitWrite::WriteBitFloat
+    pub fn read_f32(&mut self) -> ReadResult<f32> {
+        Ok(f32::from_bits(self.read_u32(32)?))
+    }
+
+    // Read `len` whole bytes
+    pub fn read_bytes(&mut self, len: usize) -> ReadResult<Vec<u8>> {
+        if len > self.bits_left() / 8 {
+            return Err(ReadError::new(ReadErrorKind::EndOfBuffer, self.pos, len.saturating_mul(8)));
+        }
+
+        let mut res = Vec::with_capacity(len);
+        for _ in 0..len {
+            res.push(self.read_u8(8)?);
+        }
+        Ok(res)
+    }
+
+    // Read `bits` bits into a new byte buffer, the last byte being zero-padded
+    pub fn read_bits(&mut self, bits: usize) -> ReadResult<Vec<u8>> {
+        self.check(bits, usize::MAX)?;
+
+        let mut res = Vec::with_capacity(bits.div_ceil(8));
+        let mut left = bits;
+        while left > 0 {
+            let len = left.min(8);
+            res.push(self.read_u8(len)?);
+            left -= len;
+        }
+        Ok(res)
+    }
+
+    // Read a variable length integer, 7 bits at a time
+    pub fn read_var_u32(&mut self) -> ReadResult<u32> {
+        let start = self.pos;
+        let mut res: u32 = 0;
+
+        for i in 0..5 {
+            let byte = self.read_u8(8)?;
+            res |= ((byte & 0x7f) as u32) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(res);
+            }
+        }
+
+        Err(ReadError::new(ReadErrorKind::InvalidValue, start, self.pos - start))
+    }
+
+    // Read a variable length integer of up to 64 bits, 7 bits at a time
+    pub fn read_var_u64(&mut self) -> ReadResult<u64> {
+        let start = self.pos;
+        let mut res: u64 = 0;
+
+        for i in 0..10 {
+            let byte = self.read_u8(8)?;
+            res |= ((byte & 0x7f) as u64) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(res);
+            }
+        }
+
+        Err(ReadError::new(ReadErrorKind::InvalidValue, start, self.pos - start))
+    }
+
+    // Read a zigzag encoded variable length integer
+    pub fn read_var_i32(&mut self) -> ReadResult<i32> {
+        let value = self.read_var_u32()?;
+        Ok((value >> 1) as i32 ^ -((value & 1) as i32))
+    }
+
+    // Read a zigzag encoded variable length integer of up to 64 bits
+    pub fn read_var_i64(&mut self) -> ReadResult<i64> {
+        let value = self.read_var_u64()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    // Read an integer stored in 4, 8, 12 or 32 bits, as written by CBitWrite::WriteUBitVar
+    pub fn read_ubit_var(&mut self) -> ReadResult<u32> {
+        let value = self.read_u32(6)?;
+        Ok(match value & (16 | 32) {
+            16 => (value & 15) | (self.read_u32(4)? << 4),
+            32 => (value & 15) | (self.read_u32(8)? << 4),
+            48 => (value & 15) | (self.read_u32(32 - 4)? << 4),
+            _ => value,
+        })
+    }
+
+    // Read a world coordinate, as written by CBitWrite::WriteBitCoord
+    pub fn read_bit_coord(&mut self) -> ReadResult<f32> {
+        let has_int = self.read_u8(1)? != 0;
+        let has_fract = self.read_u8(1)? != 0;
+
+        if !has_int && !has_fract {
+            return Ok(0.);
+        }
+
+        let negative = self.read_u8(1)? != 0;
+        let mut value = 0.;
+        if has_int {
+            value += (self.read_u16(COORD_INTEGER_BITS)? + 1) as f32;
+        }
+        if has_fract {
+            value += self.read_u8(COORD_FRACTIONAL_BITS)? as f32 * COORD_RESOLUTION;
+        }
+
+        Ok(if negative { -value } else { value })
+    }
+
+    // Read a multiplayer world coordinate, as written by CBitWrite::WriteBitCoordMP. Integral ones
+    // have no fraction, low precision ones only 3 bits of it.
+    pub fn read_bit_coord_mp(&mut self, integral: bool, low_precision: bool) -> ReadResult<f32> {
+        let in_bounds = self.read_u8(1)? != 0;
+        let int_bits = if in_bounds { COORD_INTEGER_BITS_MP } else { COORD_INTEGER_BITS };
+
+        let has_int = self.read_u8(1)? != 0;
+        let mut negative = false;
+        let mut value = 0.;
+        if integral {
+            if has_int {
+                negative = self.read_u8(1)? != 0;
+                value = (self.read_u16(int_bits)? + 1) as f32;
+            }
+        } else {
+            negative = self.read_u8(1)? != 0;
+            if has_int {
+                value = (self.read_u16(int_bits)? + 1) as f32;
+            }
+            value += if low_precision {
+                self.read_u8(COORD_FRACTIONAL_BITS_MP_LOWPRECISION)? as f32 * COORD_RESOLUTION_LOWPRECISION
+            } else {
+                self.read_u8(COORD_FRACTIONAL_BITS)? as f32 * COORD_RESOLUTION
+            };
+        }
+
+        Ok(if negative { -value } else { value })
+    }
+
+    // Read a coordinate relative to its cell, as written by CBitWrite::WriteBitCellCoord
+    pub fn read_bit_cell_coord(&mut self, bits: usize, integral: bool, low_precision: bool) -> ReadResult<f32> {
+        let int = self.read_u32(bits)? as f32;
+        if integral {
+            return Ok(int);
+        }
+
+        Ok(int + if low_precision {
+            self.read_u8(COORD_FRACTIONAL_BITS_MP_LOWPRECISION)? as f32 * COORD_RESOLUTION_LOWPRECISION
+        } else {
+            self.read_u8(COORD_FRACTIONAL_BITS)? as f32 * COORD_RESOLUTION
+        })
+    }
+
+    // Read a component of a unit vector, as written by CBitWrite::WriteBitNormal
+    pub fn read_bit_normal(&mut self) -> ReadResult<f32> {
+        let negative = self.read_u8(1)? != 0;
+        let value = self.read_u16(NORMAL_FRACTIONAL_BITS)? as f32 * NORMAL_RESOLUTION;
+
+        Ok(if negative { -value } else { value })
+    }
+
+    // Read a vector of world coordinates, as written by CBitWrite::WriteBitVec3Coord. Components
+    // that aren't sent are 0.
+    pub fn read_bit_vec3_coord(&mut self) -> ReadResult<[f32; 3]> {
+        let has = [self.read_u8(1)? != 0, self.read_u8(1)? != 0, self.read_u8(1)? != 0];
+
+        let mut vector = [0.; 3];
+        for (component, has) in vector.iter_mut().zip(has) {
+            if has {
+                *component = self.read_bit_coord()?;
+            }
+        }
+        Ok(vector)
+    }
+
+    // Read a unit vector, as written by CBitWrite::WriteBitVec3Normal. Only the sign of z is sent,
+    // it's rebuilt from x and y.
+    pub fn read_bit_vec3_normal(&mut self) -> ReadResult<[f32; 3]> {
+        let has_x = self.read_u8(1)? != 0;
+        let has_y = self.read_u8(1)? != 0;
+
+        let x = if has_x { self.read_bit_normal()? } else { 0. };
+        let y = if has_y { self.read_bit_normal()? } else { 0. };
+
+        let negative_z = self.read_u8(1)? != 0;
+        let sum = x * x + y * y;
+        let z = if sum < 1. { (1. - sum).sqrt() } else { 0. };
+
+        Ok([x, y, if negative_z { -z } else { z }])
+    }
+
+    // Read an angle in degrees quantized over `bits` bits
+    pub fn read_bit_angle(&mut self, bits: usize) -> ReadResult<f32> {
+        let value = self.read_u32(bits)?;
+        Ok(value as f32 * (360. / (1u64 << bits) as f32))
+    }
+
+    pub fn read_string(&mut self) -> ReadResult<CString> {
+        let start = self.pos;
+        let mut byte = 1;
+
+        let mut res = Vec::new();
+        while byte != 0 {
+            byte = self.read_u8(8)
+                .map_err(|err| ReadError::new(err.kind, start, res.len() * 8 + 8))?;
+            res.push(byte);
+        }
+
+        CString::from_vec_with_nul(res)
+            .map_err(|_| ReadError::new(ReadErrorKind::InvalidValue, start, self.pos - start))
+    }
+}
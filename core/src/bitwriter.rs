@@ -0,0 +1,275 @@
+use std::ffi::CStr;
+
+use crate::bitreader::{
+    COORD_DENOMINATOR, COORD_FRACTIONAL_BITS, COORD_INTEGER_BITS, COORD_RESOLUTION, NORMAL_DENOMINATOR,
+    NORMAL_FRACTIONAL_BITS, NORMAL_RESOLUTION,
+};
+
+#[derive(Debug)]
+pub struct BitWriter {
+    pub content: Vec<u8>,
+    // Bit position in the buffer
+    pub pos: usize
+}
+
+impl BitWriter {
+    pub fn new(content: Vec<u8>) -> Self {
+        Self {
+            content,
+            pos: 0
+        }
+    }
+
+    // Write at most 8 bits
+    #[track_caller]
+    pub fn write_u8(&mut self, content: u8, bits: usize) {
+        assert!(bits <= 8);
+
+        if bits == 0 {
+            return;
+        }
+
+        // Drop the bits above the requested width
+        let content = if bits < 8 { content & ((1 << bits) - 1) } else { content };
+
+        // Calculate the byte position in the buffer
+        let byte_pos = self.pos / 8;
+        // Bit position in the byte
+        let bit_pos = self.pos % 8;
+
+        if byte_pos >= self.content.len() {
+            self.content.push(0);
+        }
+
+        // Check if we have to write through 2 different parts
+        if bit_pos + bits > 8 {
+            if byte_pos + 1 >= self.content.len() {
+                self.content.push(0);
+            }
+
+            // Write first part
+            let p1_len =  8 - bit_pos;
+            let p1 = content & ((1 << p1_len) - 1);
+            self.content[byte_pos] |= p1 << bit_pos;
+
+            // Write second part
+            let p2_len = bits - p1_len;
+            let p2 = (content >> p1_len) & ((1 << p2_len) - 1);
+            self.content[byte_pos + 1] |= p2;
+        } else {
+            self.content[byte_pos] |= content << bit_pos;
+        }
+
+        self.pos += bits;
+    }
+
+    // Write at most 16 bits
+    #[track_caller]
+    pub fn write_u16(&mut self, content: u16, bits: usize) {
+        assert!(bits <= 16);
+
+        if bits <= 8 {
+            self.write_u8(content as u8, bits);
+            return;
+        }
+
+        // Write the first and second part
+        self.write_u8(content as u8, 8);
+        self.write_u8((content >> 8) as u8, bits - 8);
+    }
+
+    // Write at most 32 bits
+    #[track_caller]
+    pub fn write_u32(&mut self, content: u32, bits: usize) {
+        assert!(bits <= 32);
+
+        if bits <= 16 {
+            self.write_u16(content as u16, bits);
+            return;
+        }
+
+        // Write the first and second part
+        self.write_u16(content as u16, 16);
+        self.write_u16((content >> 16) as u16, bits - 16);
+    }
+
+    // Write at most 64 bits
+    #[track_caller]
+    pub fn write_u64(&mut self, content: u64, bits: usize) {
+        assert!(bits <= 64);
+
+        if bits <= 32 {
+            self.write_u32(content as u32, bits);
+            return;
+        }
+
+        // Write the first and second part
+        self.write_u32(content as u32, 32);
+        self.write_u32((content >> 32) as u32, bits - 32);
+    }
+
+    // Write at most 8 bits of a two's complement integer
+    #[track_caller]
+    pub fn write_i8(&mut self, content: i8, bits: usize) {
+        self.write_u8(content as u8, bits);
+    }
+
+    // Write at most 16 bits of a two's complement integer
+    #[track_caller]
+    pub fn write_i16(&mut self, content: i16, bits: usize) {
+        self.write_u16(content as u16, bits);
+    }
+
+    // Write at most 32 bits of a two's complement integer
+    #[track_caller]
+    pub fn write_i32(&mut self, content: i32, bits: usize) {
+        self.write_u32(content as u32, bits);
+    }
+
+    // Write at most 64 bits of a two's complement integer
+    #[track_caller]
+    pub fn write_i64(&mut self, content: i64, bits: usize) {
+        self.write_u64(content as u64, bits);
+    }
+
+    // Write a float as its 32 raw bits
+    pub fn write_f32(&mut self, content: f32) {
+        self.write_u32(content.to_bits(), 32);
+    }
+
+    // Write whole bytes
+    pub fn write_bytes(&mut self, content: &[u8]) {
+        for byte in content {
+            self.write_u8(*byte, 8);
+        }
+    }
+
+    // Write the first `bits` bits of a buffer, counterpart of BitReader::read_bits
+    #[track_caller]
+    pub fn write_bits(&mut self, content: &[u8], bits: usize) {
+        assert!(bits <= content.len() * 8);
+
+        let mut left = bits;
+        for byte in content {
+            if left == 0 {
+                break;
+            }
+            let len = left.min(8);
+            self.write_u8(*byte, len);
+            left -= len;
+        }
+    }
+
+    // Write a variable length integer, 7 bits at a time
+    pub fn write_var_u32(&mut self, mut content: u32) {
+        while content >= 0x80 {
+            self.write_u8((content & 0x7f) as u8 | 0x80, 8);
+            content >>= 7;
+        }
+        self.write_u8(content as u8, 8);
+    }
+
+    // Write a variable length integer of up to 64 bits, 7 bits at a time
+    pub fn write_var_u64(&mut self, mut content: u64) {
+        while content >= 0x80 {
+            self.write_u8((content & 0x7f) as u8 | 0x80, 8);
+            content >>= 7;
+        }
+        self.write_u8(content as u8, 8);
+    }
+
+    // Write a zigzag encoded variable length integer
+    pub fn write_var_i32(&mut self, content: i32) {
+        self.write_var_u32(((content << 1) ^ (content >> 31)) as u32);
+    }
+
+    // Write a zigzag encoded variable length integer of up to 64 bits
+    pub fn write_var_i64(&mut self, content: i64) {
+        self.write_var_u64(((content << 1) ^ (content >> 63)) as u64);
+    }
+
+    // Write an integer in the smallest of 4, 8, 12 or 32 bits, as CBitWrite::WriteUBitVar does
+    pub fn write_ubit_var(&mut self, content: u32) {
+        let (selector, bits) = match content {
+            0..=15 => (0, 0),
+            16..=255 => (16, 4),
+            256..=4095 => (32, 8),
+            _ => (48, 28),
+        };
+        self.write_u32((content & 15) | selector, 6);
+        if bits > 0 {
+            self.write_u32(content >> 4, bits);
+        }
+    }
+
+    // Write a world coordinate as CBitWrite::WriteBitCoord does, truncated to 1/32
+    pub fn write_bit_coord(&mut self, content: f32) {
+        let negative = content <= -COORD_RESOLUTION;
+        let int = content.abs() as u32;
+        let fract = (content * COORD_DENOMINATOR as f32).abs() as u32 & (COORD_DENOMINATOR - 1);
+
+        self.write_u8((int != 0) as u8, 1);
+        self.write_u8((fract != 0) as u8, 1);
+        if int == 0 && fract == 0 {
+            return;
+        }
+
+        self.write_u8(negative as u8, 1);
+        if int != 0 {
+            self.write_u16((int - 1) as u16, COORD_INTEGER_BITS);
+        }
+        if fract != 0 {
+            self.write_u8(fract as u8, COORD_FRACTIONAL_BITS);
+        }
+    }
+
+    // Write a component of a unit vector as CBitWrite::WriteBitNormal does
+    pub fn write_bit_normal(&mut self, content: f32) {
+        let negative = content <= -NORMAL_RESOLUTION;
+        let fract = ((content * NORMAL_DENOMINATOR as f32).abs() as u32).min(NORMAL_DENOMINATOR);
+
+        self.write_u8(negative as u8, 1);
+        self.write_u16(fract as u16, NORMAL_FRACTIONAL_BITS);
+    }
+
+    // Write a vector of world coordinates, components too close to 0 are left out
+    pub fn write_bit_vec3_coord(&mut self, content: [f32; 3]) {
+        let has = content.map(|component| component.abs() >= COORD_RESOLUTION);
+        for has in has {
+            self.write_u8(has as u8, 1);
+        }
+        for (component, has) in content.into_iter().zip(has) {
+            if has {
+                self.write_bit_coord(component);
+            }
+        }
+    }
+
+    // Write a unit vector, only the sign of z is sent
+    pub fn write_bit_vec3_normal(&mut self, content: [f32; 3]) {
+        let [x, y, z] = content;
+        let has_x = x.abs() >= NORMAL_RESOLUTION;
+        let has_y = y.abs() >= NORMAL_RESOLUTION;
+
+        self.write_u8(has_x as u8, 1);
+        self.write_u8(has_y as u8, 1);
+        if has_x {
+            self.write_bit_normal(x);
+        }
+        if has_y {
+            self.write_bit_normal(y);
+        }
+        self.write_u8((z <= -NORMAL_RESOLUTION) as u8, 1);
+    }
+
+    // Write an angle in degrees quantized over `bits` bits
+    pub fn write_bit_angle(&mut self, content: f32, bits: usize) {
+        let shift = 1u64 << bits;
+        let value = ((content / 360.) * shift as f32) as i64 as u64 & (shift - 1);
+        self.write_u32(value as u32, bits);
+    }
+
+    pub fn write_string(&mut self, string: &CStr) {
+        self.write_bytes(string.to_bytes_with_nul());
+    }
+}
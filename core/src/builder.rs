@@ -0,0 +1,251 @@
+use std::ffi::CString;
+
+use crate::bitwriter::BitWriter;
+use crate::checksum;
+use crate::message::NetMessage;
+use crate::netchannel::*;
+use crate::profile::GameProfile;
+
+// Content of a reliable stream, sent whole in one packet or in fragments over several
+#[derive(Debug, Clone, Default)]
+pub struct StreamData {
+    // Bytes as sent, already LZSS compressed when `uncompressed_size` is set
+    pub data: Vec<u8>,
+    // Transfer ID and filename of file streams
+    pub file: Option<(u32, CString)>,
+    pub uncompressed_size: Option<u32>,
+}
+
+impl StreamData {
+    // A stream carrying netchannel messages
+    pub fn messages(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            ..Default::default()
+        }
+    }
+
+    pub fn file(transfer_id: u32, filename: CString, data: Vec<u8>) -> Self {
+        Self {
+            data,
+            file: Some((transfer_id, filename)),
+            uncompressed_size: None,
+        }
+    }
+
+    pub fn num_fragments(&self) -> usize {
+        self.data.len().div_ceil(FRAGMENT_SIZE)
+    }
+}
+
+// What a packet carries for one reliable stream
+#[derive(Debug, Clone)]
+enum StreamPart {
+    // The whole stream without a fragment header
+    SingleBlock(StreamData),
+    // `num_fragments` fragments starting at `start_fragment`
+    Fragments {
+        stream: StreamData,
+        start_fragment: usize,
+        num_fragments: usize,
+    },
+}
+
+// Builds complete netchannel datagrams, as read by NetChannel::process_packet
+#[derive(Debug)]
+pub struct PacketBuilder {
+    sequence: u32,
+    sequence_ack: u32,
+    // Flags on top of the ones implied by the content
+    flags: u8,
+    rel_state: u8,
+    choke: Option<u8>,
+    // Index of the reliable subchannel the streams are sent on
+    subchannel: u8,
+    streams: [Option<StreamPart>; 2],
+    // Unreliable payload following the streams
+    payload: BitWriter,
+    state: ParseState,
+}
+
+impl PacketBuilder {
+    pub fn new(profile: &'static GameProfile) -> Self {
+        let state = ParseState { profile };
+
+        Self {
+            sequence: 0,
+            sequence_ack: 0,
+            flags: 0,
+            rel_state: 0,
+            choke: None,
+            subchannel: 0,
+            streams: [None, None],
+            payload: BitWriter::new(Vec::new()),
+            state,
+        }
+    }
+
+    pub fn sequence(mut self, sequence: u32) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub fn sequence_ack(mut self, sequence_ack: u32) -> Self {
+        self.sequence_ack = sequence_ack;
+        self
+    }
+
+    // Extra PACKET_FLAG_* bits, the reliable and choked ones follow from the content and are
+    // ignored here
+    pub fn flags(mut self, flags: u8) -> Self {
+        self.flags = flags & !(PACKET_FLAG_RELIABLE | PACKET_FLAG_CHOKED);
+        self
+    }
+
+    pub fn rel_state(mut self, rel_state: u8) -> Self {
+        self.rel_state = rel_state;
+        self
+    }
+
+    pub fn choke(mut self, choked_packets: u8) -> Self {
+        self.choke = Some(choked_packets);
+        self
+    }
+
+    // Returns `None` if the subchannel index doesn't fit in 3 bits
+    pub fn subchannel(mut self, subchannel: u8) -> Option<Self> {
+        if subchannel >= 8 {
+            return None;
+        }
+        self.subchannel = subchannel;
+        Some(self)
+    }
+
+    // Sends a whole stream in this packet without a fragment header. File streams always have
+    // one so they can't be sent that way, neither can streams larger than a payload.
+    pub fn single_block(mut self, stream: usize, data: StreamData) -> Option<Self> {
+        if data.file.is_some() || data.data.len() >= 1 << self.state.profile.max_payload_bits {
+            return None;
+        }
+        *self.streams.get_mut(stream)? = Some(StreamPart::SingleBlock(data));
+        Some(self)
+    }
+
+    // Sends fragments `start_fragment..start_fragment + num_fragments` of a stream, the stream
+    // header goes with the first fragment. Returns `None` if the fragments aren't part of the
+    // stream or are more than a packet can carry.
+    pub fn fragments(mut self, stream: usize, data: &StreamData, start_fragment: usize, num_fragments: usize) -> Option<Self> {
+        if num_fragments == 0 || num_fragments >= 8 || data.data.len() >= 1 << MAX_FILE_SIZE_BITS {
+            return None;
+        }
+        if start_fragment.checked_add(num_fragments)? > data.num_fragments() {
+            return None;
+        }
+        *self.streams.get_mut(stream)? = Some(StreamPart::Fragments {
+            stream: data.clone(),
+            start_fragment,
+            num_fragments,
+        });
+        Some(self)
+    }
+
+    // Appends an unreliable message, returns `None` if it can't be encoded
+    pub fn message(mut self, message: &NetMessage) -> Option<Self> {
+        message.write(&mut self.payload, &self.state).then_some(self)
+    }
+
+    // Appends raw bits to the unreliable payload, returns `None` if `content` is shorter than `bits`
+    pub fn raw(mut self, content: &[u8], bits: usize) -> Option<Self> {
+        if bits > content.len() * 8 {
+            return None;
+        }
+        self.payload.write_bits(content, bits);
+        Some(self)
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let reliable = self.streams.iter().any(Option::is_some);
+
+        let mut flags = self.flags;
+        if reliable {
+            flags |= PACKET_FLAG_RELIABLE;
+        }
+        if self.choke.is_some() {
+            flags |= PACKET_FLAG_CHOKED;
+        }
+
+        let header = NetPacketHeader {
+            sequence: self.sequence,
+            sequence_ack: self.sequence_ack,
+            flags: PacketFlag(flags),
+            checksum: 0,
+            rel_state: self.rel_state,
+        };
+
+        let mut packet = header.to_bytes().to_vec();
+        if let Some(choke) = self.choke {
+            packet.push(choke);
+        }
+
+        let mut writer = BitWriter::new(Vec::new());
+        if reliable {
+            writer.write_u8(self.subchannel, 3);
+            for part in &self.streams {
+                writer.write_u8(part.is_some() as u8, 1);
+                if let Some(part) = part {
+                    self.write_stream(&mut writer, part);
+                }
+            }
+        }
+        writer.write_bits(&self.payload.content, self.payload.pos);
+
+        packet.extend(writer.content);
+        checksum::update_checksum(&mut packet);
+        packet
+    }
+
+    // Counterpart of read_sub_channel_data
+    fn write_stream(&self, writer: &mut BitWriter, part: &StreamPart) {
+        match part {
+            StreamPart::SingleBlock(stream) => {
+                writer.write_u8(0, 1);
+                write_compression(writer, stream);
+                writer.write_u32(stream.data.len() as u32, self.state.profile.max_payload_bits);
+                writer.write_bytes(&stream.data);
+            },
+            StreamPart::Fragments { stream, start_fragment, num_fragments } => {
+                writer.write_u8(1, 1);
+                writer.write_u32(*start_fragment as u32, MAX_FILE_SIZE_BITS - FRAGMENT_BITS);
+                writer.write_u8(*num_fragments as u8, 3);
+
+                if *start_fragment == 0 {
+                    match &stream.file {
+                        Some((transfer_id, filename)) => {
+                            writer.write_u8(1, 1);
+                            writer.write_u32(*transfer_id, 32);
+                            writer.write_string(filename);
+                        },
+                        None => writer.write_u8(0, 1),
+                    }
+                    write_compression(writer, stream);
+                    writer.write_u32(stream.data.len() as u32, MAX_FILE_SIZE_BITS);
+                }
+
+                // The last fragment is shorter when the stream isn't a multiple of the fragment size
+                let offset = start_fragment * FRAGMENT_SIZE;
+                let end = ((start_fragment + num_fragments) * FRAGMENT_SIZE).min(stream.data.len());
+                writer.write_bytes(&stream.data[offset..end]);
+            },
+        }
+    }
+}
+
+fn write_compression(writer: &mut BitWriter, stream: &StreamData) {
+    match stream.uncompressed_size {
+        Some(size) => {
+            writer.write_u8(1, 1);
+            writer.write_u32(size, MAX_FILE_SIZE_BITS);
+        },
+        None => writer.write_u8(0, 1),
+    }
+}
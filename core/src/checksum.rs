@@ -0,0 +1,60 @@
+use std::mem::{offset_of, size_of};
+use std::ops::Range;
+
+use crate::netchannel::NetPacketHeader;
+
+// CRC32 (IEEE 802.3, reflected) lookup table
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+const CHECKSUM_FIELD: Range<usize> = offset_of!(NetPacketHeader, checksum)..offset_of!(NetPacketHeader, rel_state);
+// Checksummed data starts right after the checksum field, with the reliable state
+const CHECKSUM_OFFSET: usize = CHECKSUM_FIELD.end;
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in data {
+        crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+// BufferToShortChecksum, the CRC32 of the buffer folded to 16 bits
+pub fn short_checksum(data: &[u8]) -> u16 {
+    let crc = crc32(data);
+    (crc as u16) ^ ((crc >> 16) as u16)
+}
+
+// Returns the checksum a netchannel packet should carry, `None` if it's too short to have a header
+pub fn packet_checksum(packet: &[u8]) -> Option<u16> {
+    if packet.len() < size_of::<NetPacketHeader>() {
+        return None;
+    }
+    Some(short_checksum(&packet[CHECKSUM_OFFSET..]))
+}
+
+// Returns the checksum stored in the header of a netchannel packet
+pub fn header_checksum(packet: &[u8]) -> Option<u16> {
+    let field = packet.get(CHECKSUM_FIELD)?;
+    Some(u16::from_le_bytes([field[0], field[1]]))
+}
+
+// Recomputes the header checksum after a packet was built or modified
+pub fn update_checksum(packet: &mut [u8]) {
+    if let Some(checksum) = packet_checksum(packet) {
+        packet[CHECKSUM_FIELD].copy_from_slice(&checksum.to_le_bytes());
+    }
+}
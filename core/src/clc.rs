@@ -0,0 +1,539 @@
+use std::ffi::CString;
+
+use crate::bitreader::{BitReader, ReadResult};
+use crate::bitwriter::BitWriter;
+use crate::keyvalues::{self, KeyValues};
+use crate::profile::{GameProfile, UserCmdLayout};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CUserCmd {
+    pub command_number: i32,
+    pub tick_count: i32,
+    pub viewangles: QAngle,
+    pub forwardmove: f32,
+    pub sidemove: f32,
+    pub upmove: f32,
+    pub buttons: i32,
+    pub impulse: u8,
+    pub weaponselect: i32,
+    pub weaponsubtype: i32,
+    //random_seed: i32,
+    pub mousedx: i16,
+    pub mousedy: i16,
+    pub hasbeenpredicted: bool
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QAngle {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NETDisconnect {
+    pub reason: CString
+}
+
+impl NETDisconnect {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            reason: reader.read_string()?
+        })
+    }
+
+    pub fn write(&self, writer: &mut BitWriter) {
+        writer.write_string(&self.reason);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NETFile {
+    pub transfer_id: u32,
+    pub filename: CString,
+    // true if the file is requested, false if it's denied
+    pub requested: bool
+}
+
+impl NETFile {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            transfer_id: reader.read_u32(32)?,
+            filename: reader.read_string()?,
+            requested: reader.read_u8(1)? != 0
+        })
+    }
+
+    pub fn write(&self, writer: &mut BitWriter) {
+        writer.write_u32(self.transfer_id, 32);
+        writer.write_string(&self.filename);
+        writer.write_u8(self.requested as u8, 1);
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NETTick {
+    pub n_tick: i32,
+    pub fl_host_frame_time: f32,
+    pub fl_host_frame_time_std_deviation: f32
+}
+
+impl NETTick {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_tick = reader.read_i32(32)?;
+        let fl_host_frame_time = reader.read_u16(16)? as f32 / 100000.0;
+        let fl_host_frame_time_std_deviation = reader.read_u16(16)? as f32 / 100000.0;
+
+        Ok(NETTick {
+            n_tick,
+            fl_host_frame_time,
+            fl_host_frame_time_std_deviation
+        })
+    }
+
+    pub fn write(&self, writer: &mut BitWriter) {
+        writer.write_i32(self.n_tick, 32);
+        writer.write_u16((self.fl_host_frame_time * 100000.0).round() as u16, 16);
+        writer.write_u16((self.fl_host_frame_time_std_deviation * 100000.0).round() as u16, 16);
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CLCMove {
+    pub n_new_commands: u8,
+    pub n_backup_commands: u8,
+    pub n_length: u16,
+    // Oldest first, the backup commands already sent in previous packets then the new ones
+    pub user_cmds: Vec<CUserCmd>
+}
+
+impl CLCMove {
+    pub fn parse(reader: &mut BitReader, layout: &UserCmdLayout) -> ReadResult<Self> {
+        let n_new_commands = reader.read_u8(4)?;
+        let n_backup_commands = reader.read_u8(3)?;
+        // Length in bits
+        let n_length = reader.read_u16(16)?;
+
+        let buf = reader.read_bits(n_length as usize)?;
+        let mut reader = BitReader::new(buf);
+
+        // Each usercmd is delta encoded against the previous one, the first one against a
+        // zeroed usercmd
+        let total = n_new_commands as usize + n_backup_commands as usize;
+        let mut user_cmds: Vec<CUserCmd> = Vec::with_capacity(total);
+        for _ in 0..total {
+            let from = user_cmds.last().cloned().unwrap_or_default();
+            user_cmds.push(CUserCmd::parse_delta(&mut reader, &from, layout)?);
+        }
+
+        Ok(CLCMove {
+            n_new_commands,
+            n_backup_commands,
+            n_length,
+            user_cmds
+        })
+    }
+
+    // `n_length` is computed from the encoded size
+    pub fn write(&self, writer: &mut BitWriter, layout: &UserCmdLayout) {
+        let mut data = BitWriter::new(Vec::new());
+        let mut from = CUserCmd::default();
+        for user_cmd in &self.user_cmds {
+            user_cmd.write_delta(&mut data, &from, layout);
+            from = user_cmd.clone();
+        }
+
+        writer.write_u8(self.n_new_commands, 4);
+        writer.write_u8(self.n_backup_commands, 3);
+        writer.write_u16(data.pos as u16, 16);
+        writer.write_bits(&data.content, data.pos);
+    }
+
+    // Commands the client already sent, repeated in case those packets were lost
+    pub fn backup_commands(&self) -> &[CUserCmd] {
+        let backups = (self.n_backup_commands as usize).min(self.user_cmds.len());
+        &self.user_cmds[..backups]
+    }
+
+    pub fn new_commands(&self) -> &[CUserCmd] {
+        &self.user_cmds[self.backup_commands().len()..]
+    }
+}
+
+impl CUserCmd {
+    // ReadUsercmd, fields that aren't sent keep their value from `from`
+    pub fn parse_delta(reader: &mut BitReader, from: &CUserCmd, layout: &UserCmdLayout) -> ReadResult<Self> {
+        let mut user_cmd = from.clone();
+
+        if reader.read_u8(1)? == 1 {
+            user_cmd.command_number = reader.read_i32(32)?;
+        } else {
+            user_cmd.command_number = from.command_number.wrapping_add(1);
+        }
+
+        if reader.read_u8(1)? == 1 {
+            user_cmd.tick_count = reader.read_i32(32)?;
+        } else {
+            user_cmd.tick_count = from.tick_count.wrapping_add(1);
+        }
+
+        // Read direction
+        if reader.read_u8(1)? == 1 {
+            user_cmd.viewangles.x = reader.read_f32()?;
+        }
+        if reader.read_u8(1)? == 1 {
+            user_cmd.viewangles.y = reader.read_f32()?;
+        }
+        if reader.read_u8(1)? == 1 {
+            user_cmd.viewangles.z = reader.read_f32()?;
+        }
+
+        // Read movement
+        if reader.read_u8(1)? == 1 {
+            user_cmd.forwardmove = reader.read_f32()?;
+        }
+        if reader.read_u8(1)? == 1 {
+            user_cmd.sidemove = reader.read_f32()?;
+        }
+        if reader.read_u8(1)? == 1 {
+            user_cmd.upmove = reader.read_f32()?;
+        }
+
+        // Read buttons
+        if reader.read_u8(1)? == 1 {
+            user_cmd.buttons = reader.read_i32(32)?;
+        }
+        if reader.read_u8(1)? == 1 {
+            user_cmd.impulse = reader.read_u8(8)?;
+        }
+
+        if reader.read_u8(1)? == 1 {
+            user_cmd.weaponselect = reader.read_u16(layout.weaponselect_bits)? as i32;
+            if reader.read_u8(1)? == 1 {
+                user_cmd.weaponsubtype = reader.read_u8(layout.weaponsubtype_bits)? as i32;
+            }
+        }
+
+        if layout.mouse_deltas {
+            if reader.read_u8(1)? == 1 {
+                user_cmd.mousedx = reader.read_i16(16)?;
+            }
+            if reader.read_u8(1)? == 1 {
+                user_cmd.mousedy = reader.read_i16(16)?;
+            }
+        }
+
+        Ok(user_cmd)
+    }
+
+    // WriteUsercmd, only the fields that differ from `from` are sent
+    pub fn write_delta(&self, writer: &mut BitWriter, from: &CUserCmd, layout: &UserCmdLayout) {
+        fn write_changed(writer: &mut BitWriter, changed: bool, value: u32, bits: usize) {
+            writer.write_u8(changed as u8, 1);
+            if changed {
+                writer.write_u32(value, bits);
+            }
+        }
+
+        write_changed(writer, self.command_number != from.command_number.wrapping_add(1), self.command_number as u32, 32);
+        write_changed(writer, self.tick_count != from.tick_count.wrapping_add(1), self.tick_count as u32, 32);
+
+        // Write direction
+        write_changed(writer, self.viewangles.x != from.viewangles.x, self.viewangles.x.to_bits(), 32);
+        write_changed(writer, self.viewangles.y != from.viewangles.y, self.viewangles.y.to_bits(), 32);
+        write_changed(writer, self.viewangles.z != from.viewangles.z, self.viewangles.z.to_bits(), 32);
+
+        // Write movement
+        write_changed(writer, self.forwardmove != from.forwardmove, self.forwardmove.to_bits(), 32);
+        write_changed(writer, self.sidemove != from.sidemove, self.sidemove.to_bits(), 32);
+        write_changed(writer, self.upmove != from.upmove, self.upmove.to_bits(), 32);
+
+        // Write buttons
+        write_changed(writer, self.buttons != from.buttons, self.buttons as u32, 32);
+        write_changed(writer, self.impulse != from.impulse, self.impulse as u32, 8);
+
+        if self.weaponselect != from.weaponselect || self.weaponsubtype != from.weaponsubtype {
+            writer.write_u8(1, 1);
+            writer.write_u16(self.weaponselect as u16, layout.weaponselect_bits);
+            write_changed(writer, self.weaponsubtype != from.weaponsubtype, self.weaponsubtype as u32, layout.weaponsubtype_bits);
+        } else {
+            writer.write_u8(0, 1);
+        }
+
+        if layout.mouse_deltas {
+            write_changed(writer, self.mousedx != from.mousedx, self.mousedx as u16 as u32, 16);
+            write_changed(writer, self.mousedy != from.mousedy, self.mousedy as u16 as u32, 16);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CLCClientInfo {
+    pub n_server_count: i32,
+    pub n_send_table_crc: u32,
+    pub b_is_hltv: bool,
+    pub b_is_replay: bool,
+    pub n_friends_id: u32,
+    pub friends_name: CString,
+    pub n_custom_files: [u32; 4]
+}
+
+impl CLCClientInfo {
+    pub fn parse(reader: &mut BitReader, profile: &GameProfile) -> ReadResult<Self> {
+        let n_server_count = reader.read_i32(32)?;
+        let n_send_table_crc = reader.read_u32(32)?;
+        let b_is_hltv = reader.read_u8(1)? == 1;
+        let b_is_replay = profile.replay && reader.read_u8(1)? == 1;
+        let n_friends_id = reader.read_u32(32)?;
+        let friends_name = reader.read_string()?;
+
+        let mut n_custom_files = [0; 4];
+        for custom_file in n_custom_files.iter_mut() {
+            if reader.read_u8(1)? != 0 {
+                *custom_file = reader.read_u32(32)?;
+            }
+        }
+
+        Ok(CLCClientInfo {
+            n_server_count,
+            n_send_table_crc,
+            b_is_hltv,
+            b_is_replay,
+            n_friends_id,
+            friends_name,
+            n_custom_files
+        })
+    }
+
+    pub fn write(&self, writer: &mut BitWriter, profile: &GameProfile) {
+        writer.write_i32(self.n_server_count, 32);
+        writer.write_u32(self.n_send_table_crc, 32);
+        writer.write_u8(self.b_is_hltv as u8, 1);
+        if profile.replay {
+            writer.write_u8(self.b_is_replay as u8, 1);
+        }
+        writer.write_u32(self.n_friends_id, 32);
+        writer.write_string(&self.friends_name);
+
+        // Unset custom files aren't sent
+        for custom_file in self.n_custom_files {
+            writer.write_u8((custom_file != 0) as u8, 1);
+            if custom_file != 0 {
+                writer.write_u32(custom_file, 32);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConVar {
+    pub name: CString,
+    pub value: CString,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NETSetConVar {
+    pub convars: Vec<ConVar>
+}
+
+impl NETSetConVar {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let numvars = reader.read_u8(8)?;
+        let mut convars: Vec<ConVar> = Vec::new();
+
+        for _ in 0..numvars {
+            convars.push(ConVar {
+                name: reader.read_string()?,
+                value: reader.read_string()?
+            });
+        }
+
+        Ok(NETSetConVar {
+            convars
+        })
+    }
+
+    // Returns false and writes nothing if there are more than the 255 convars a message can hold
+    pub fn write(&self, writer: &mut BitWriter) -> bool {
+        let Ok(count) = u8::try_from(self.convars.len()) else {
+            return false;
+        };
+
+        writer.write_u8(count, 8);
+        for convar in &self.convars {
+            writer.write_string(&convar.name);
+            writer.write_string(&convar.value);
+        }
+        true
+    }
+}
+
+// CLC_CmdKeyValues and SVC_CmdKeyValues, a KeyValues tree in its binary form
+#[derive(Debug, Clone, PartialEq)]
+pub struct CmdKeyValues {
+    pub values: Vec<KeyValues>
+}
+
+impl CmdKeyValues {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let num_bytes = reader.read_u32(32)?;
+
+        let buffer = reader.read_bytes(num_bytes as usize)?;
+        let values = keyvalues::read_binary(&mut BitReader::new(buffer))?;
+
+        Ok(CmdKeyValues {
+            values
+        })
+    }
+
+    pub fn write(&self, writer: &mut BitWriter, profile: &GameProfile) {
+        let end = if profile.key_values_compiled_types {
+            keyvalues::TYPE_END_COMPILED
+        } else {
+            keyvalues::TYPE_END
+        };
+
+        let mut buffer = BitWriter::new(Vec::new());
+        keyvalues::write_binary(&mut buffer, &self.values, end);
+
+        writer.write_u32(buffer.content.len() as u32, 32);
+        writer.write_bytes(&buffer.content);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NETSignonState {
+    pub n_signon_state: u8,
+    pub n_spawn_count: u32,
+    pub idk1: u32,
+    pub idk2_len: u32,
+    pub idk2_buf: Vec<u8>,
+    pub idk3_len: u32,
+    pub idk3_buf: Vec<u8>
+}
+
+impl NETSignonState {
+    pub fn parse(reader: &mut BitReader, profile: &GameProfile) -> ReadResult<Self> {
+        let n_signon_state = reader.read_u8(8)?;
+        let n_spawn_count = reader.read_u32(32)?;
+
+        let mut idk1 = 0;
+        let mut idk2_len = 0;
+        let mut idk2_buf = Vec::new();
+        let mut idk3_len = 0;
+        let mut idk3_buf = Vec::new();
+        if profile.signon_state_extended {
+            idk1 = reader.read_u32(32)?;
+
+            idk2_len = reader.read_u32(32)?;
+            idk2_buf = reader.read_bytes(idk2_len as usize)?;
+
+            idk3_len = reader.read_u32(32)?;
+            idk3_buf = reader.read_bytes(idk3_len as usize)?;
+        }
+
+        Ok(NETSignonState {
+            n_signon_state,
+            n_spawn_count,
+            idk1,
+            idk2_len,
+            idk2_buf,
+            idk3_len,
+            idk3_buf
+        })
+    }
+
+    // The lengths are taken from the buffers
+    pub fn write(&self, writer: &mut BitWriter, profile: &GameProfile) {
+        writer.write_u8(self.n_signon_state, 8);
+        writer.write_u32(self.n_spawn_count, 32);
+
+        if profile.signon_state_extended {
+            writer.write_u32(self.idk1, 32);
+
+            writer.write_u32(self.idk2_buf.len() as u32, 32);
+            writer.write_bytes(&self.idk2_buf);
+
+            writer.write_u32(self.idk3_buf.len() as u32, 32);
+            writer.write_bytes(&self.idk3_buf);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CLCListenEvents {
+    pub events: Vec<u32>
+}
+
+impl CLCListenEvents {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let mut events = Vec::new();
+        for _ in 0..16 {
+            events.push(reader.read_u32(32)?);
+        }
+        Ok(CLCListenEvents {
+            events
+        })
+    }
+
+    // Always 16 words, missing ones are sent as zero
+    pub fn write(&self, writer: &mut BitWriter) {
+        for i in 0..16 {
+            writer.write_u32(self.events.get(i).copied().unwrap_or(0), 32);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NETStringCmd {
+    pub command: CString
+}
+
+impl NETStringCmd {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(NETStringCmd {
+            command: reader.read_string()?
+        })
+    }
+
+    pub fn write(&self, writer: &mut BitWriter) {
+        writer.write_string(&self.command);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CLCBaselineAck {
+    pub n_baseline_tick: u32,
+    pub n_baseline_nr: u32
+}
+
+impl CLCBaselineAck {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            n_baseline_tick: reader.read_u32(32)?,
+            n_baseline_nr: reader.read_u32(1)?
+        })
+    }
+
+    pub fn write(&self, writer: &mut BitWriter) {
+        writer.write_u32(self.n_baseline_tick, 32);
+        writer.write_u32(self.n_baseline_nr, 1);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CLCLoadingProgress {
+    pub idk: u8
+}
+
+impl CLCLoadingProgress {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            idk: reader.read_u8(8)?,
+        })
+    }
+
+    pub fn write(&self, writer: &mut BitWriter) {
+        writer.write_u8(self.idk, 8);
+    }
+}
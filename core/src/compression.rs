@@ -0,0 +1,84 @@
+use crate::bitreader::{ReadError, ReadErrorKind, ReadResult};
+use crate::lzss;
+use crate::profile::GameProfile;
+use crate::snappy;
+
+// NET_HEADER_FLAG_COMPRESSEDPACKET
+pub const COMPRESSED_PACKET_HEADER: [u8; 4] = (-3i32).to_le_bytes();
+// Compressed packets and string tables can't announce more than that
+pub const MAX_UNCOMPRESSED_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Lzss,
+    Snappy,
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compression::Lzss => write!(f, "LZSS"),
+            Compression::Snappy => write!(f, "Snappy"),
+        }
+    }
+}
+
+// How a compressed datagram was compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionInfo {
+    pub method: Compression,
+    // Sizes without the compressed packet header
+    pub compressed_size: usize,
+    pub uncompressed_size: usize,
+}
+
+impl CompressionInfo {
+    // Compressed size over uncompressed size, lower is better
+    pub fn ratio(&self) -> f64 {
+        if self.uncompressed_size == 0 {
+            return 1.;
+        }
+        self.compressed_size as f64 / self.uncompressed_size as f64
+    }
+}
+
+// Returns the size announced by a compressed buffer if it's at most `max_size`. It's checked
+// before anything is allocated for it, a forged header would otherwise make us allocate gigabytes.
+pub fn check_size(size: usize, max_size: usize) -> Option<usize> {
+    (size <= max_size).then_some(size)
+}
+
+pub fn is_compressed(packet: &[u8]) -> bool {
+    packet.starts_with(&COMPRESSED_PACKET_HEADER)
+}
+
+// Returns the datagram carried by a compressed packet, which is decoded like any other datagram
+pub fn decompress_packet(packet: &[u8], profile: &GameProfile) -> ReadResult<(Vec<u8>, CompressionInfo)> {
+    if !is_compressed(packet) {
+        return Err(ReadError::new(ReadErrorKind::InvalidValue, 0, 32));
+    }
+
+    let data = &packet[COMPRESSED_PACKET_HEADER.len()..];
+    let start = COMPRESSED_PACKET_HEADER.len() * 8;
+    let corrupted = ReadError::new(ReadErrorKind::InvalidValue, start, data.len() * 8);
+
+    let method = if lzss::uncompressed_size(data).is_some() {
+        Compression::Lzss
+    } else if profile.snappy && snappy::is_snappy(data) {
+        Compression::Snappy
+    } else {
+        return Err(ReadError::new(ReadErrorKind::InvalidValue, start, 32));
+    };
+
+    let datagram = match method {
+        Compression::Lzss => lzss::decompress(data, MAX_UNCOMPRESSED_SIZE),
+        Compression::Snappy => snappy::decompress(data, MAX_UNCOMPRESSED_SIZE),
+    }.ok_or(corrupted)?;
+
+    let info = CompressionInfo {
+        method,
+        compressed_size: data.len(),
+        uncompressed_size: datagram.len(),
+    };
+    Ok((datagram, info))
+}
@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::hash::Hash;
+use std::time::Duration;
+
+use crate::bitreader::{ReadErrorKind, ReadResult};
+use crate::clc::CLCListenEvents;
+use crate::connectionless::ConnectionlessMessage;
+use crate::entities::EntityTable;
+use crate::gameevents::{GameEvent, GameEventRegistry};
+use crate::message::NetMessage;
+use crate::netchannel::{process_payloads, Direction, NetChannel, PacketMetadata};
+use crate::players::Roster;
+use crate::profile::{GameProfile, PROFILES};
+use crate::split::{self, SplitReassembler};
+use crate::stringtables::{StringTables, INSTANCE_BASELINE, USER_INFO};
+use crate::transfer::FileTransfer;
+use crate::usermessages::{TranscriptLine, UserMessageRegistry};
+
+// Traffic counters of one direction
+#[derive(Debug, Default, Clone)]
+pub struct TrafficStats {
+    pub packets: u64,
+    pub bytes: u64,
+    pub messages: u64,
+    // Out-of-band packets, the handshake and server queries
+    pub connectionless: u64,
+    // Packets that couldn't be decoded
+    pub errors: u64,
+    // Part of `errors` rejected because of their checksum
+    pub checksum_errors: u64,
+    pub files: u64,
+    // SVC_PacketEntities and SVC_SendTable messages that couldn't be applied to the entity table
+    pub entity_errors: u64,
+    // SVC_CreateStringTable and SVC_UpdateStringTable messages whose entries couldn't be read
+    pub string_table_errors: u64,
+    // SVC_GameEventList and SVC_GameEvent messages that couldn't be decoded
+    pub game_event_errors: u64,
+    // SVC_UserMessage messages that couldn't be decoded
+    pub user_message_errors: u64,
+    // Packets that came with the compressed packet header, and their sizes before and after
+    // decompression
+    pub compressed_packets: u64,
+    pub compressed_bytes: u64,
+    pub uncompressed_bytes: u64,
+}
+
+// Reliable payloads tried against every candidate profile before giving up on guessing
+const MAX_PROBES: usize = 8;
+
+// Why a connection is decoded with its profile, from the weakest to the strongest hint. A hint
+// only replaces the profile picked by a weaker one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProfileSource {
+    // Nothing was learnt yet
+    Default,
+    // Protocol version of the connect packet
+    Protocol,
+    // First reliable payloads parsed cleanly with it
+    Heuristic,
+    // App ID or game directory of an A2S_INFO reply
+    ServerQuery,
+    // Game directory of SVC_ServerInfo
+    ServerInfo,
+    // Chosen by the user, never changed
+    Forced,
+}
+
+impl std::fmt::Display for ProfileSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileSource::Default => write!(f, "default"),
+            ProfileSource::Protocol => write!(f, "protocol version"),
+            ProfileSource::Heuristic => write!(f, "parsed cleanly"),
+            ProfileSource::ServerQuery => write!(f, "server query"),
+            ProfileSource::ServerInfo => write!(f, "server info"),
+            ProfileSource::Forced => write!(f, "forced"),
+        }
+    }
+}
+
+// A client/server conversation with all of its decoding state. Timestamps are supplied by the
+// caller, as the time since any fixed point (capture time when replaying, uptime when hooked).
+#[derive(Debug)]
+pub struct Connection {
+    channel: NetChannel,
+    client_to_server_splits: SplitReassembler,
+    server_to_client_splits: SplitReassembler,
+    profile_source: ProfileSource,
+    // Reliable payloads tried against the candidate profiles so far
+    probes: usize,
+    // Last SIGNONSTATE_* announced by either side
+    pub signon_state: u8,
+    // Protocol version the client asked for in its connect packet
+    pub protocol_version: Option<u32>,
+    // Why the server refused the connection, if it did
+    pub reject_reason: Option<CString>,
+    connectionless: Vec<ConnectionlessMessage>,
+    last_packet: Option<PacketMetadata>,
+    string_tables: StringTables,
+    roster: Roster,
+    entities: EntityTable,
+    game_event_registry: GameEventRegistry,
+    game_events: Vec<GameEvent>,
+    // Last CLC_ListenEvents of the client
+    listen_events: Option<CLCListenEvents>,
+    user_message_registry: UserMessageRegistry,
+    transcript: Vec<TranscriptLine>,
+    pub client_to_server: TrafficStats,
+    pub server_to_client: TrafficStats,
+    pub first_seen: Duration,
+    pub last_seen: Duration,
+}
+
+impl Connection {
+    pub fn new(now: Duration, profile: &'static GameProfile) -> Self {
+        Self {
+            channel: NetChannel::with_profile(profile),
+            client_to_server_splits: Default::default(),
+            server_to_client_splits: Default::default(),
+            profile_source: ProfileSource::Default,
+            probes: 0,
+            signon_state: 0,
+            protocol_version: None,
+            reject_reason: None,
+            connectionless: Vec::new(),
+            last_packet: None,
+            string_tables: StringTables::new(),
+            roster: Roster::new(),
+            entities: EntityTable::new(),
+            game_event_registry: GameEventRegistry::new(),
+            game_events: Vec::new(),
+            listen_events: None,
+            user_message_registry: UserMessageRegistry::new(),
+            transcript: Vec::new(),
+            client_to_server: Default::default(),
+            server_to_client: Default::default(),
+            first_seen: now,
+            last_seen: now,
+        }
+    }
+
+    pub fn profile(&self) -> &'static GameProfile {
+        self.channel.profile()
+    }
+
+    pub fn profile_source(&self) -> ProfileSource {
+        self.profile_source
+    }
+
+    // Decodes with `profile` from now on, whatever the handshake says
+    pub fn set_profile(&mut self, profile: &'static GameProfile) {
+        self.channel.set_profile(profile);
+        self.profile_source = ProfileSource::Forced;
+    }
+
+    // Switches to `profile` unless a stronger hint already picked one
+    fn detect_profile(&mut self, profile: &'static GameProfile, source: ProfileSource) {
+        if source > self.profile_source {
+            self.channel.set_profile(profile);
+            self.profile_source = source;
+        }
+    }
+
+    fn detect_protocol_version(&mut self, version: u32) {
+        if self.profile_source >= ProfileSource::Protocol {
+            return;
+        }
+        if self.profile().matches_protocol_version(version) {
+            self.profile_source = ProfileSource::Protocol;
+        } else if let Some(profile) = GameProfile::by_protocol_version(version).next() {
+            self.detect_profile(profile, ProfileSource::Protocol);
+        }
+    }
+
+    // Profiles worth trying, the current one first
+    fn candidates(&self) -> Vec<&'static GameProfile> {
+        let current = self.profile();
+        let mut candidates = vec![current];
+
+        let known_version = self.protocol_version
+            .filter(|version| GameProfile::by_protocol_version(*version).next().is_some());
+        for profile in PROFILES {
+            let plausible = known_version.is_none_or(|version| profile.matches_protocol_version(version));
+            if plausible && !std::ptr::eq(profile, current) {
+                candidates.push(profile);
+            }
+        }
+        candidates
+    }
+
+    // Decodes a whole datagram. Until something tells which branch the peers speak, the first
+    // reliable datagrams are read with every candidate profile and the first one whose messages
+    // parse cleanly is kept. Only its reading reaches the channel.
+    fn decode(&mut self, datagram: &[u8], direction: Direction) -> ReadResult<Vec<NetMessage>> {
+        let probing = self.profile_source < ProfileSource::Heuristic && self.probes < MAX_PROBES;
+        if !probing {
+            return self.channel.process_packet(datagram, direction);
+        }
+
+        let Some(datagram) = self.channel.receive_packet(datagram)? else {
+            return Ok(Vec::new());
+        };
+
+        let mut fallback = None;
+        for profile in self.candidates() {
+            let read = match self.channel.read_datagram(&datagram, direction, profile) {
+                // A corruption no profile would fix
+                Err(err) if err.kind == ReadErrorKind::ChecksumMismatch => return Err(err),
+                // Unreliable packets tell nothing about the profile, the current one is tried first
+                Ok(read) if !read.reliable => {
+                    let payloads = self.channel.receive(read, direction)?;
+                    return process_payloads(&payloads, direction, self.channel.parse_state_mut());
+                },
+                read => read,
+            };
+
+            let mut state = self.channel.parse_state().clone();
+            state.profile = profile;
+            let result = read.as_ref()
+                .map_err(|err| *err)
+                .and_then(|read| self.channel.preview(read, direction))
+                .and_then(|payloads| process_payloads(&payloads, direction, &mut state));
+
+            // Nothing to judge the profile on yet, e.g. a stream that isn't complete
+            let empty = matches!(&result, Ok(messages) if messages.iter().all(|message| matches!(message, NetMessage::Nop)));
+            if empty && std::ptr::eq(profile, self.profile()) {
+                self.channel.receive(read?, direction)?;
+                *self.channel.parse_state_mut() = state;
+                return result;
+            }
+
+            let clean = matches!(&result, Ok(messages) if !messages.iter().any(|message| matches!(message, NetMessage::Unknown(_))));
+            if clean {
+                // The preview already decoded the messages
+                self.channel.receive(read?, direction)?;
+                *self.channel.parse_state_mut() = state;
+                self.profile_source = ProfileSource::Heuristic;
+                return result;
+            }
+
+            fallback.get_or_insert((read, state, result));
+        }
+
+        // No candidate makes sense of it, keep decoding with the current profile
+        self.probes += 1;
+        let (read, state, result) = fallback.unwrap();
+        if let Ok(read) = read {
+            self.channel.receive(read, direction)?;
+        }
+        *self.channel.parse_state_mut() = state;
+        result
+    }
+
+    // Size and compression of the last packet, `None` if it was a piece of a split packet that
+    // didn't complete a datagram
+    pub fn last_packet(&self) -> Option<&PacketMetadata> {
+        self.last_packet.as_ref()
+    }
+
+    pub fn stats(&self, direction: Direction) -> &TrafficStats {
+        match direction {
+            Direction::ClientToServer => &self.client_to_server,
+            Direction::ServerToClient => &self.server_to_client,
+        }
+    }
+
+    fn stats_mut(&mut self, direction: Direction) -> &mut TrafficStats {
+        match direction {
+            Direction::ClientToServer => &mut self.client_to_server,
+            Direction::ServerToClient => &mut self.server_to_client,
+        }
+    }
+
+    // Decodes a packet of this connection travelling in `direction`. Pieces of split packets are
+    // held until the whole datagram is there, nothing is decoded until then. A reassembled
+    // datagram may itself be compressed.
+    pub fn process_packet(&mut self, packet: &[u8], direction: Direction, now: Duration) -> ReadResult<Vec<NetMessage>> {
+        // Merged captures aren't always in order, the connection only ever gets older
+        self.last_seen = self.last_seen.max(now);
+
+        let mut decoded = true;
+        let result = if split::is_split(packet) {
+            let splits = match direction {
+                Direction::ClientToServer => &mut self.client_to_server_splits,
+                Direction::ServerToClient => &mut self.server_to_client_splits,
+            };
+
+            match splits.push(packet, now) {
+                Ok(Some(datagram)) => self.decode(&datagram, direction),
+                Ok(None) => {
+                    decoded = false;
+                    Ok(Vec::new())
+                },
+                Err(err) => {
+                    decoded = false;
+                    Err(err)
+                },
+            }
+        } else {
+            self.decode(packet, direction)
+        };
+
+        self.last_packet = if decoded { Some(*self.channel.last_packet()) } else { None };
+        let compression = self.last_packet.and_then(|metadata| metadata.compression);
+
+        let connectionless = self.channel.take_connectionless();
+        for message in &connectionless {
+            match message {
+                ConnectionlessMessage::Connect(connect) => {
+                    self.protocol_version = Some(connect.protocol);
+                    self.detect_protocol_version(connect.protocol);
+                },
+                ConnectionlessMessage::InfoReply(info) => {
+                    // The game ID holds the full app ID in its low 24 bits
+                    let app_id = info.game_id.map_or(info.app_id as u32, |game_id| (game_id & 0xffffff) as u32);
+                    let profile = GameProfile::by_app_id(app_id)
+                        .or_else(|| GameProfile::by_game_dir(&info.folder.to_string_lossy()));
+                    if let Some(profile) = profile {
+                        self.detect_profile(profile, ProfileSource::ServerQuery);
+                    }
+                },
+                ConnectionlessMessage::Reject(reject) => self.reject_reason = Some(reject.reason.clone()),
+                _ => (),
+            }
+        }
+        let connectionless_count = connectionless.len() as u64;
+        self.connectionless.extend(connectionless);
+
+        let mut server_info_profile = None;
+        let stats = self.stats_mut(direction);
+        stats.packets += 1;
+        stats.bytes += packet.len() as u64;
+        stats.connectionless += connectionless_count;
+
+        if let Some(info) = compression {
+            stats.compressed_packets += 1;
+            stats.compressed_bytes += info.compressed_size as u64;
+            stats.uncompressed_bytes += info.uncompressed_size as u64;
+        }
+
+        match &result {
+            Ok(messages) => {
+                stats.messages += messages.len() as u64;
+
+                for message in messages {
+                    match message {
+                        NetMessage::SignonState(signon) => self.signon_state = signon.n_signon_state,
+                        NetMessage::ServerInfo(info) => server_info_profile = GameProfile::by_game_dir(&info.game_dir.to_string_lossy()),
+                        _ => (),
+                    }
+                }
+            },
+            Err(err) => {
+                stats.errors += 1;
+                if err.kind == ReadErrorKind::ChecksumMismatch {
+                    stats.checksum_errors += 1;
+                }
+            },
+        }
+
+        if let Some(profile) = server_info_profile {
+            self.detect_profile(profile, ProfileSource::ServerInfo);
+        }
+
+        if let Ok(messages) = &result {
+            let profile = self.profile();
+            let mut string_table_errors = 0;
+            let mut entity_errors = 0;
+            let mut game_event_errors = 0;
+            let mut user_message_errors = 0;
+            for message in messages {
+                self.roster.process_message(message);
+
+                match self.string_tables.process_message(message, profile) {
+                    Ok(Some(update)) => {
+                        let table = self.string_tables.get(update.table_id).unwrap();
+                        let name = table.name.to_bytes();
+                        if name == INSTANCE_BASELINE.as_bytes() {
+                            // Entries of the instance baselines are keyed by class ID
+                            for index in update.entries {
+                                let entry = table.get(index).unwrap();
+                                if let Ok(class_id) = entry.string.to_string_lossy().parse() {
+                                    self.entities.set_instance_baseline(class_id, entry.user_data.clone());
+                                }
+                            }
+                        } else if name == USER_INFO.as_bytes() {
+                            // Entries of the players are keyed by client slot
+                            if matches!(message, NetMessage::CreateStringTable(_)) {
+                                self.roster.sync(table, profile.player_info_big_endian, now);
+                            } else {
+                                for index in update.entries {
+                                    let user_data = &table.get(index).unwrap().user_data;
+                                    self.roster.update(index as u16, user_data, profile.player_info_big_endian, now);
+                                }
+                            }
+                        }
+                    },
+                    Ok(None) => (),
+                    Err(_) => string_table_errors += 1,
+                }
+
+                if self.entities.process_message(message, direction, profile).is_err() {
+                    entity_errors += 1;
+                }
+
+                let game_event = match message {
+                    NetMessage::GameEventList(list) => self.game_event_registry.load(list),
+                    NetMessage::GameEvent(event) => self.game_event_registry.decode(event).map(|event| self.game_events.push(event)),
+                    NetMessage::ListenEvents(listen) => {
+                        self.listen_events = Some(listen.clone());
+                        Ok(())
+                    },
+                    _ => Ok(()),
+                };
+                if game_event.is_err() {
+                    game_event_errors += 1;
+                }
+
+                if let NetMessage::UserMessage(message) = message {
+                    match self.user_message_registry.decode(message, profile) {
+                        Ok(Some(message)) => self.transcript.extend(message.transcript(&self.roster, now)),
+                        Ok(None) => (),
+                        Err(_) => user_message_errors += 1,
+                    }
+                }
+            }
+            let stats = self.stats_mut(direction);
+            stats.string_table_errors += string_table_errors;
+            stats.entity_errors += entity_errors;
+            stats.game_event_errors += game_event_errors;
+            stats.user_message_errors += user_message_errors;
+        }
+
+        result
+    }
+
+    // String tables the server created, model and sound names, player info and the like
+    pub fn string_tables(&self) -> &StringTables {
+        &self.string_tables
+    }
+
+    // Players seen on the server, from the userinfo table
+    pub fn roster(&self) -> &Roster {
+        &self.roster
+    }
+
+    // World state decoded from the entity messages
+    pub fn entities(&self) -> &EntityTable {
+        &self.entities
+    }
+
+    // Game events the server announced
+    pub fn game_event_registry(&self) -> &GameEventRegistry {
+        &self.game_event_registry
+    }
+
+    // Names of the game events the client subscribed to
+    pub fn listened_events(&self) -> Vec<&CStr> {
+        self.listen_events.as_ref().map_or(Vec::new(), |listen| self.game_event_registry.listened(listen))
+    }
+
+    // Returns the game events decoded since the last call
+    pub fn take_game_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.game_events)
+    }
+
+    // Decoders of the user messages, custom ones can be registered
+    pub fn user_message_registry_mut(&mut self) -> &mut UserMessageRegistry {
+        &mut self.user_message_registry
+    }
+
+    // Returns the chat, notices and hints shown since the last call
+    pub fn take_transcript(&mut self) -> Vec<TranscriptLine> {
+        std::mem::take(&mut self.transcript)
+    }
+
+    // Returns the connectionless packets decoded since the last call
+    pub fn take_connectionless(&mut self) -> Vec<ConnectionlessMessage> {
+        std::mem::take(&mut self.connectionless)
+    }
+
+    // Returns the file transfers completed since the last call
+    pub fn take_files(&mut self) -> Vec<FileTransfer> {
+        let files = self.channel.take_files();
+        for file in &files {
+            self.stats_mut(file.direction).files += 1;
+        }
+        files
+    }
+}
+
+// Connections indexed by whatever identifies them for the caller, e.g. socket and peer address
+// when hooked or the UDP 5-tuple when replaying a capture
+#[derive(Debug)]
+pub struct ConnectionTable<K> {
+    connections: HashMap<K, Connection>,
+    // Connections without traffic for that long are evicted
+    idle_timeout: Duration,
+    // Profile new connections start with
+    profile: &'static GameProfile,
+    // New connections keep `profile` instead of detecting theirs
+    forced: bool,
+}
+
+impl<K: Hash + Eq + Clone> ConnectionTable<K> {
+    // New connections start with `profile` and switch to the one their handshake points to
+    pub fn new(idle_timeout: Duration, profile: &'static GameProfile) -> Self {
+        Self {
+            connections: HashMap::new(),
+            idle_timeout,
+            profile,
+            forced: false,
+        }
+    }
+
+    // New connections are all decoded with `profile`
+    pub fn with_forced_profile(idle_timeout: Duration, profile: &'static GameProfile) -> Self {
+        Self {
+            forced: true,
+            ..Self::new(idle_timeout, profile)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&Connection> {
+        self.connections.get(key)
+    }
+
+    // Returns the connection identified by `key`, creating it on first use
+    pub fn get_or_insert(&mut self, key: K, now: Duration) -> &mut Connection {
+        self.connections.entry(key).or_insert_with(|| {
+            let mut connection = Connection::new(now, self.profile);
+            if self.forced {
+                connection.set_profile(self.profile);
+            }
+            connection
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &Connection)> {
+        self.connections.iter()
+    }
+
+    // Removes and returns the connections idle since before `now - idle_timeout`
+    pub fn evict_idle(&mut self, now: Duration) -> Vec<(K, Connection)> {
+        let idle: Vec<K> = self.connections.iter()
+            .filter(|(_, connection)| now.saturating_sub(connection.last_seen) > self.idle_timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        idle.into_iter()
+            .filter_map(|key| self.connections.remove_entry(&key))
+            .collect()
+    }
+
+    // Removes and returns every connection
+    pub fn drain(&mut self) -> Vec<(K, Connection)> {
+        self.connections.drain().collect()
+    }
+}
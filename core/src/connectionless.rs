@@ -0,0 +1,379 @@
+use std::ffi::CString;
+
+use crate::bitreader::{BitReader, ReadError, ReadErrorKind, ReadResult};
+
+// CONNECTIONLESS_HEADER, out-of-band packets sent outside of the netchannel
+pub const CONNECTIONLESS_HEADER: [u8; 4] = (-1i32).to_le_bytes();
+
+// Server queries and their replies
+pub const A2S_INFO: u8 = b'T';
+pub const S2A_INFO_SRC: u8 = b'I';
+pub const A2S_PLAYER: u8 = b'U';
+pub const S2A_PLAYER: u8 = b'D';
+pub const A2S_RULES: u8 = b'V';
+pub const S2A_RULES: u8 = b'E';
+// Handshake
+pub const A2S_GETCHALLENGE: u8 = b'q';
+pub const S2C_CHALLENGE: u8 = b'A';
+pub const C2S_CONNECT: u8 = b'k';
+pub const S2C_CONNECTION: u8 = b'B';
+pub const S2C_CONNREJECT: u8 = b'9';
+
+// Leads the handshake challenge, tells it apart from a query challenge
+pub const S2C_MAGICVERSION: u32 = 0x5a4f4933;
+
+pub const PROTOCOL_AUTHCERTIFICATE: u32 = 1;
+pub const PROTOCOL_HASHEDCDKEY: u32 = 2;
+pub const PROTOCOL_STEAM: u32 = 3;
+
+// Extra data flags of S2A_INFO_SRC
+const EDF_GAMEID: u8 = 0x01;
+const EDF_STEAMID: u8 = 0x10;
+const EDF_KEYWORDS: u8 = 0x20;
+const EDF_SOURCETV: u8 = 0x40;
+const EDF_PORT: u8 = 0x80;
+
+pub fn is_connectionless(packet: &[u8]) -> bool {
+    packet.starts_with(&CONNECTIONLESS_HEADER)
+}
+
+fn bytes_left(reader: &BitReader) -> usize {
+    reader.bits_left() / 8
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct A2SInfo {
+    // "Source Engine Query"
+    pub payload: CString,
+    // Sent back by clients once the server asked for a challenge
+    pub challenge: Option<u32>,
+}
+
+impl A2SInfo {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let payload = reader.read_string()?;
+        let challenge = if bytes_left(reader) >= 4 {
+            Some(reader.read_u32(32)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            payload,
+            challenge,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct S2AInfo {
+    pub protocol: u8,
+    pub name: CString,
+    pub map: CString,
+    pub folder: CString,
+    pub game: CString,
+    pub app_id: u16,
+    pub players: u8,
+    pub max_players: u8,
+    pub bots: u8,
+    // 'd' dedicated, 'l' listen, 'p' SourceTV
+    pub server_type: u8,
+    // 'l' Linux, 'w' Windows, 'm' or 'o' Mac
+    pub environment: u8,
+    pub password: bool,
+    pub vac: bool,
+    pub version: CString,
+    pub port: Option<u16>,
+    pub steam_id: Option<u64>,
+    pub source_tv_port: Option<u16>,
+    pub source_tv_name: Option<CString>,
+    pub keywords: Option<CString>,
+    pub game_id: Option<u64>,
+}
+
+impl S2AInfo {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let protocol = reader.read_u8(8)?;
+        let name = reader.read_string()?;
+        let map = reader.read_string()?;
+        let folder = reader.read_string()?;
+        let game = reader.read_string()?;
+        let app_id = reader.read_u16(16)?;
+        let players = reader.read_u8(8)?;
+        let max_players = reader.read_u8(8)?;
+        let bots = reader.read_u8(8)?;
+        let server_type = reader.read_u8(8)?;
+        let environment = reader.read_u8(8)?;
+        let password = reader.read_u8(8)? != 0;
+        let vac = reader.read_u8(8)? != 0;
+        let version = reader.read_string()?;
+
+        // Older servers stop there
+        let flags = if bytes_left(reader) > 0 { reader.read_u8(8)? } else { 0 };
+
+        let port = if flags & EDF_PORT != 0 { Some(reader.read_u16(16)?) } else { None };
+        let steam_id = if flags & EDF_STEAMID != 0 { Some(reader.read_u64(64)?) } else { None };
+        let (source_tv_port, source_tv_name) = if flags & EDF_SOURCETV != 0 {
+            (Some(reader.read_u16(16)?), Some(reader.read_string()?))
+        } else {
+            (None, None)
+        };
+        let keywords = if flags & EDF_KEYWORDS != 0 { Some(reader.read_string()?) } else { None };
+        let game_id = if flags & EDF_GAMEID != 0 { Some(reader.read_u64(64)?) } else { None };
+
+        Ok(Self {
+            protocol,
+            name,
+            map,
+            folder,
+            game,
+            app_id,
+            players,
+            max_players,
+            bots,
+            server_type,
+            environment,
+            password,
+            vac,
+            version,
+            port,
+            steam_id,
+            source_tv_port,
+            source_tv_name,
+            keywords,
+            game_id,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerInfo {
+    pub index: u8,
+    pub name: CString,
+    pub score: i32,
+    // Seconds since the player connected
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct S2APlayer {
+    pub players: Vec<PlayerInfo>,
+}
+
+impl S2APlayer {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let count = reader.read_u8(8)?;
+
+        let mut players = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            players.push(PlayerInfo {
+                index: reader.read_u8(8)?,
+                name: reader.read_string()?,
+                score: reader.read_i32(32)?,
+                duration: reader.read_f32()?,
+            });
+        }
+
+        Ok(Self {
+            players,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct S2ARules {
+    // Name and value of each public convar
+    pub rules: Vec<(CString, CString)>,
+}
+
+impl S2ARules {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let count = reader.read_u16(16)?;
+
+        let mut rules = Vec::new();
+        for _ in 0..count {
+            rules.push((reader.read_string()?, reader.read_string()?));
+        }
+
+        Ok(Self {
+            rules,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct A2SGetChallenge {
+    pub client_challenge: u32,
+}
+
+impl A2SGetChallenge {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        // Followed by a "0000000000" padding string
+        Ok(Self {
+            client_challenge: reader.read_u32(32)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct S2CChallenge {
+    pub challenge: u32,
+    pub client_challenge: u32,
+    pub auth_protocol: u32,
+    // Steam key, server SteamID and secure flag, their layout depends on the branch
+    pub data: Vec<u8>,
+}
+
+impl S2CChallenge {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let start = reader.pos;
+        if reader.read_u32(32)? != S2C_MAGICVERSION {
+            return Err(ReadError::new(ReadErrorKind::InvalidValue, start, 32));
+        }
+
+        let challenge = reader.read_u32(32)?;
+        let client_challenge = reader.read_u32(32)?;
+        let auth_protocol = reader.read_u32(32)?;
+        let data = reader.read_bytes(bytes_left(reader))?;
+
+        Ok(Self {
+            challenge,
+            client_challenge,
+            auth_protocol,
+            data,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct C2SConnect {
+    pub protocol: u32,
+    pub auth_protocol: u32,
+    pub challenge: u32,
+    pub client_challenge: u32,
+    pub name: CString,
+    pub password: CString,
+    // Client version, missing on older branches
+    pub product_version: Option<CString>,
+    // Steam auth ticket, empty if there is none or it couldn't be located
+    pub auth_ticket: Vec<u8>,
+    // Whatever follows that isn't understood, e.g. split screen players
+    pub extra: Vec<u8>,
+}
+
+impl C2SConnect {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let protocol = reader.read_u32(32)?;
+        let auth_protocol = reader.read_u32(32)?;
+        let challenge = reader.read_u32(32)?;
+        let client_challenge = reader.read_u32(32)?;
+        let name = reader.read_string()?;
+        let password = reader.read_string()?;
+
+        let rest = reader.read_bytes(bytes_left(reader))?;
+
+        // A ticket is its u16 length followed by that many bytes up to the end of the packet
+        let ticket_at = |data: &[u8]| {
+            data.len() >= 2 && u16::from_le_bytes([data[0], data[1]]) as usize == data.len() - 2
+        };
+
+        let mut product_version = None;
+        let mut data = &rest[..];
+        if !ticket_at(data) {
+            if let Some(end) = data.iter().position(|&byte| byte == 0) {
+                if end > 0 && data[..end].iter().all(|byte| byte.is_ascii_graphic()) {
+                    product_version = Some(CString::new(&data[..end]).unwrap());
+                    data = &data[end + 1..];
+                }
+            }
+        }
+
+        let (auth_ticket, extra) = if auth_protocol == PROTOCOL_STEAM && ticket_at(data) {
+            (data[2..].to_vec(), Vec::new())
+        } else {
+            (Vec::new(), data.to_vec())
+        };
+
+        Ok(Self {
+            protocol,
+            auth_protocol,
+            challenge,
+            client_challenge,
+            name,
+            password,
+            product_version,
+            auth_ticket,
+            extra,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct S2CConnReject {
+    pub client_challenge: u32,
+    pub reason: CString,
+}
+
+impl S2CConnReject {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            client_challenge: reader.read_u32(32)?,
+            reason: reader.read_string()?,
+        })
+    }
+}
+
+// Out-of-band packet, the handshake and server queries
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionlessMessage {
+    InfoQuery(A2SInfo),
+    InfoReply(S2AInfo),
+    // -1 asks for a challenge
+    PlayerQuery { challenge: u32 },
+    PlayerReply(S2APlayer),
+    RulesQuery { challenge: u32 },
+    RulesReply(S2ARules),
+    // Challenge a query has to be repeated with
+    QueryChallenge { challenge: u32 },
+    GetChallenge(A2SGetChallenge),
+    Challenge(S2CChallenge),
+    Connect(C2SConnect),
+    // The server accepted the connection, the netchannel starts
+    Connection { client_challenge: u32 },
+    Reject(S2CConnReject),
+    Unknown { kind: u8, data: Vec<u8> },
+}
+
+impl ConnectionlessMessage {
+    // Decodes a packet starting with the connectionless header
+    pub fn parse(packet: &[u8]) -> ReadResult<Self> {
+        if packet.len() < CONNECTIONLESS_HEADER.len() + 1 {
+            return Err(ReadError::new(ReadErrorKind::EndOfBuffer, 0, 40));
+        }
+        if !is_connectionless(packet) {
+            return Err(ReadError::new(ReadErrorKind::InvalidValue, 0, 32));
+        }
+
+        let mut reader = BitReader::new(packet.to_vec());
+        reader.pos = CONNECTIONLESS_HEADER.len() * 8;
+
+        let kind = reader.read_u8(8)?;
+        let message = match kind {
+            A2S_INFO => Self::InfoQuery(A2SInfo::parse(&mut reader)?),
+            S2A_INFO_SRC => Self::InfoReply(S2AInfo::parse(&mut reader)?),
+            A2S_PLAYER => Self::PlayerQuery { challenge: reader.read_u32(32)? },
+            S2A_PLAYER => Self::PlayerReply(S2APlayer::parse(&mut reader)?),
+            A2S_RULES => Self::RulesQuery { challenge: reader.read_u32(32)? },
+            S2A_RULES => Self::RulesReply(S2ARules::parse(&mut reader)?),
+            // Query challenges are a bare number
+            S2C_CHALLENGE if bytes_left(&reader) == 4 => Self::QueryChallenge { challenge: reader.read_u32(32)? },
+            S2C_CHALLENGE => Self::Challenge(S2CChallenge::parse(&mut reader)?),
+            A2S_GETCHALLENGE => Self::GetChallenge(A2SGetChallenge::parse(&mut reader)?),
+            C2S_CONNECT => Self::Connect(C2SConnect::parse(&mut reader)?),
+            S2C_CONNECTION => Self::Connection { client_challenge: reader.read_u32(32)? },
+            S2C_CONNREJECT => Self::Reject(S2CConnReject::parse(&mut reader)?),
+            _ => Self::Unknown { kind, data: reader.read_bytes(bytes_left(&reader))? },
+        };
+        Ok(message)
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ffi::CString;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::bitreader::{BitReader, ReadError, ReadErrorKind, ReadResult};
+use crate::message::NetMessage;
+use crate::netchannel::Direction;
+use crate::profile::{GameProfile, PropIndexEncoding};
+use crate::sendtable::{self, FlatProp, PropValue, SendTable};
+use crate::svc::{bits_for, SVCClassInfo, SVCPacketEntities};
+
+const MAX_EDICT_BITS: usize = 11;
+// NUM_NETWORKED_EHANDLE_SERIAL_NUMBER_BITS
+const SERIAL_NUMBER_BITS: usize = 10;
+// Ends the list of changed props with the FieldIndex encodings
+const FIELD_INDEX_END: u32 = 0xfff;
+// Frames kept for PacketEntities delta encoded against an older one than the last
+const MAX_FRAMES: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntityError {
+    Read(ReadError),
+    // PacketEntities delta encoded against a frame that wasn't decoded
+    MissingFrame(i32),
+    // Update of an entity that isn't in the frame it's delta encoded against
+    MissingEntity(u16),
+    UnknownClass(u16),
+    // Class whose send tables weren't all received
+    MissingTables(u16),
+}
+
+impl From<ReadError> for EntityError {
+    fn from(err: ReadError) -> Self {
+        EntityError::Read(err)
+    }
+}
+
+impl fmt::Display for EntityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityError::Read(err) => write!(f, "{}", err),
+            EntityError::MissingFrame(tick) => write!(f, "delta from tick {} which wasn't decoded", tick),
+            EntityError::MissingEntity(index) => write!(f, "update of entity {} which doesn't exist", index),
+            EntityError::UnknownClass(class_id) => write!(f, "unknown server class {}", class_id),
+            EntityError::MissingTables(class_id) => write!(f, "send tables of server class {} are incomplete", class_id),
+        }
+    }
+}
+
+impl std::error::Error for EntityError {}
+
+#[derive(Debug, Clone)]
+pub struct ServerClass {
+    pub id: u16,
+    pub name: CString,
+    pub table_name: CString,
+    // Flattened props, `None` when some of the class's send tables weren't received
+    pub props: Option<Vec<FlatProp>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    pub index: u16,
+    pub class_id: u16,
+    pub serial: u16,
+    // Value of each flattened prop of the class
+    pub props: Vec<PropValue>,
+}
+
+#[derive(Debug, Clone)]
+struct Frame {
+    tick: i32,
+    entities: BTreeMap<u16, Arc<Entity>>,
+}
+
+// World state mirrored from SVC_SendTable, SVC_ClassInfo and SVC_PacketEntities. Entities can only
+// be decoded when the server sends its tables, which it doesn't unless sv_sendtables is set:
+// the client builds its classes from its own tables when ClassInfo says so.
+#[derive(Debug, Default, Clone)]
+pub struct EntityTable {
+    send_tables: HashMap<CString, SendTable>,
+    classes: BTreeMap<u16, ServerClass>,
+    server_class_bits: usize,
+    // ClassInfo told the client to build its classes from its own tables
+    pub create_on_client: bool,
+    // Instance baselines by class, raw and decoded
+    instance_baselines: HashMap<u16, Vec<u8>>,
+    decoded_baselines: HashMap<u16, Arc<Vec<PropValue>>>,
+    // Entity states saved by PacketEntities updating the baseline, by baseline number
+    baselines: [BTreeMap<u16, Arc<Entity>>; 2],
+    frames: VecDeque<Frame>,
+    // Server tick of the messages being decoded, from NET_Tick
+    tick: i32,
+    // Last baseline the client acknowledged, as its tick and number
+    pub baseline_ack: Option<(u32, u32)>,
+}
+
+impl EntityTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Updates the table with a message travelling in `direction`
+    pub fn process_message(&mut self, message: &NetMessage, direction: Direction, profile: &GameProfile) -> Result<(), EntityError> {
+        match (message, direction) {
+            // A new map, everything is sent again
+            (NetMessage::ServerInfo(_), _) => *self = Self::default(),
+            (NetMessage::SendTable(message), _) => {
+                let table = SendTable::parse(&mut BitReader::new(message.data.clone()), profile)?;
+                self.send_tables.insert(table.name.clone(), table);
+            },
+            (NetMessage::ClassInfo(message), _) => self.set_classes(message),
+            (NetMessage::Tick(tick), Direction::ServerToClient) => self.tick = tick.n_tick,
+            (NetMessage::PacketEntities(message), _) => self.read_packet_entities(message, profile)?,
+            (NetMessage::BaselineAck(ack), _) => self.baseline_ack = Some((ack.n_baseline_tick, ack.n_baseline_nr)),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn set_classes(&mut self, message: &SVCClassInfo) {
+        self.server_class_bits = bits_for(message.n_num_server_classes as u32);
+        self.create_on_client = message.b_create_on_client;
+        self.decoded_baselines.clear();
+
+        self.classes = message.classes.iter().map(|class| {
+            (class.class_id, ServerClass {
+                id: class.class_id,
+                name: class.class_name.clone(),
+                table_name: class.data_table_name.clone(),
+                props: sendtable::flatten(&self.send_tables, &class.data_table_name),
+            })
+        }).collect();
+    }
+
+    // Sets the instance baseline of a class, the entry of the "instancebaseline" string table
+    pub fn set_instance_baseline(&mut self, class_id: u16, data: Vec<u8>) {
+        self.decoded_baselines.remove(&class_id);
+        self.instance_baselines.insert(class_id, data);
+    }
+
+    // Tick of the last frame decoded
+    pub fn tick(&self) -> Option<i32> {
+        self.frames.back().map(|frame| frame.tick)
+    }
+
+    pub fn class(&self, class_id: u16) -> Option<&ServerClass> {
+        self.classes.get(&class_id)
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = &ServerClass> {
+        self.classes.values()
+    }
+
+    // Entities of the last frame decoded
+    pub fn entities(&self) -> impl Iterator<Item = &Entity> {
+        self.frames.back().into_iter().flat_map(|frame| frame.entities.values().map(|entity| &**entity))
+    }
+
+    pub fn get(&self, index: u16) -> Option<&Entity> {
+        self.frames.back()?.entities.get(&index).map(|entity| &**entity)
+    }
+
+    // Value of the prop `name` of an entity, either the prop name or "table.prop"
+    pub fn prop(&self, index: u16, name: &str) -> Option<&PropValue> {
+        let entity = self.get(index)?;
+        let props = self.class(entity.class_id)?.props.as_ref()?;
+        let i = props.iter().position(|prop| prop.is_named(name))?;
+        entity.props.get(i)
+    }
+
+    fn class_props(&self, class_id: u16) -> Result<&[FlatProp], EntityError> {
+        let class = self.classes.get(&class_id).ok_or(EntityError::UnknownClass(class_id))?;
+        class.props.as_deref().ok_or(EntityError::MissingTables(class_id))
+    }
+
+    // Props of a class entering the PVS without a baseline of its own
+    fn instance_baseline(&mut self, class_id: u16, profile: &GameProfile) -> Result<Arc<Vec<PropValue>>, EntityError> {
+        if let Some(props) = self.decoded_baselines.get(&class_id) {
+            return Ok(props.clone());
+        }
+
+        let flat = self.class_props(class_id)?;
+        let mut props: Vec<PropValue> = flat.iter().map(|prop| PropValue::zero(&prop.prop)).collect();
+        if let Some(data) = self.instance_baselines.get(&class_id) {
+            read_props(&mut BitReader::new(data.clone()), flat, &mut props, profile)?;
+        }
+
+        let props = Arc::new(props);
+        self.decoded_baselines.insert(class_id, props.clone());
+        Ok(props)
+    }
+
+    // CL_ParsePacketEntities, the new frame is the one `message` is delta encoded against with
+    // the entities it lists entering, leaving or changing
+    fn read_packet_entities(&mut self, message: &SVCPacketEntities, profile: &GameProfile) -> Result<(), EntityError> {
+        if self.classes.is_empty() {
+            return Ok(());
+        }
+
+        let mut entities = if message.b_is_delta {
+            self.frames.iter()
+                .rfind(|frame| frame.tick == message.n_delta_from)
+                .ok_or(EntityError::MissingFrame(message.n_delta_from))?
+                .entities.clone()
+        } else {
+            BTreeMap::new()
+        };
+
+        // The other baseline starts as a copy of the current one, entities entering the PVS are
+        // then saved to it
+        let baseline = (message.n_baseline & 1) as usize;
+        if message.b_update_baseline {
+            let current = self.baselines[baseline].clone();
+            self.baselines[1 - baseline].extend(current);
+        }
+
+        let mut reader = BitReader::new(message.data.clone());
+        let mut index = -1i64;
+        for _ in 0..message.n_updated_entries {
+            let header_pos = reader.pos;
+            index += 1 + reader.read_ubit_var()? as i64;
+            if index >= 1 << MAX_EDICT_BITS {
+                return Err(ReadError::new(ReadErrorKind::InvalidValue, header_pos, reader.pos - header_pos).into());
+            }
+            let index = index as u16;
+
+            if reader.read_u8(1)? != 0 {
+                // Leaving the PVS, with the force delete bit. Either way the client drops it.
+                reader.read_u8(1)?;
+                entities.remove(&index);
+            } else if reader.read_u8(1)? != 0 {
+                let class_id = reader.read_u16(self.server_class_bits)?;
+                let serial = reader.read_u16(SERIAL_NUMBER_BITS)?;
+
+                // The saved baseline of the entity if it's still of the same class, the instance
+                // baseline of its class otherwise
+                let saved = self.baselines[baseline].get(&index)
+                    .filter(|entity| message.b_is_delta && entity.class_id == class_id)
+                    .map(|entity| entity.props.clone());
+                let mut props = match saved {
+                    Some(props) => props,
+                    None => self.instance_baseline(class_id, profile)?.to_vec(),
+                };
+                read_props(&mut reader, self.class_props(class_id)?, &mut props, profile)?;
+
+                let entity = Arc::new(Entity { index, class_id, serial, props });
+                if message.b_update_baseline {
+                    self.baselines[1 - baseline].insert(index, entity.clone());
+                }
+                entities.insert(index, entity);
+            } else {
+                let entity = entities.get_mut(&index).ok_or(EntityError::MissingEntity(index))?;
+                let entity = Arc::make_mut(entity);
+                let flat = self.classes.get(&entity.class_id)
+                    .and_then(|class| class.props.as_deref())
+                    .ok_or(EntityError::MissingTables(entity.class_id))?;
+                read_props(&mut reader, flat, &mut entity.props, profile)?;
+            }
+        }
+
+        // Explicit deletions of entities that left the PVS earlier
+        if message.b_is_delta {
+            while reader.bits_left() > 0 && reader.read_u8(1)? != 0 {
+                entities.remove(&reader.read_u16(MAX_EDICT_BITS)?);
+            }
+        }
+
+        self.frames.push_back(Frame { tick: self.tick, entities });
+        if self.frames.len() > MAX_FRAMES {
+            self.frames.pop_front();
+        }
+        Ok(())
+    }
+}
+
+// Reads the index of the next changed prop, `None` at the end of the list
+fn read_field_index(reader: &mut BitReader, last: i64, new_way: bool) -> ReadResult<Option<i64>> {
+    if new_way && reader.read_u8(1)? != 0 {
+        return Ok(Some(last + 1));
+    }
+
+    let value = if new_way && reader.read_u8(1)? != 0 {
+        reader.read_u32(3)?
+    } else {
+        let value = reader.read_u32(7)?;
+        match value & (32 | 64) {
+            32 => (value & !96) | (reader.read_u32(2)? << 5),
+            64 => (value & !96) | (reader.read_u32(4)? << 5),
+            96 => (value & !96) | (reader.read_u32(7)? << 5),
+            _ => value,
+        }
+    };
+
+    if value == FIELD_INDEX_END {
+        return Ok(None);
+    }
+    Ok(Some(last + 1 + value as i64))
+}
+
+// Reads the changed props of an entity over `props`
+fn read_props(reader: &mut BitReader, flat: &[FlatProp], props: &mut [PropValue], profile: &GameProfile) -> ReadResult<()> {
+    let new_way = profile.prop_index_encoding == PropIndexEncoding::FieldIndexNewWay && reader.read_u8(1)? != 0;
+
+    let mut index = -1i64;
+    loop {
+        let start = reader.pos;
+        let next = match profile.prop_index_encoding {
+            PropIndexEncoding::UBitVar => {
+                if reader.read_u8(1)? != 0 {
+                    Some(index + 1 + reader.read_ubit_var()? as i64)
+                } else {
+                    None
+                }
+            },
+            PropIndexEncoding::FieldIndex | PropIndexEncoding::FieldIndexNewWay => read_field_index(reader, index, new_way)?,
+        };
+        let Some(next) = next else {
+            return Ok(());
+        };
+        index = next;
+
+        let (Some(prop), Some(value)) = (flat.get(index as usize), props.get_mut(index as usize)) else {
+            return Err(ReadError::new(ReadErrorKind::InvalidValue, start, reader.pos - start));
+        };
+        *value = PropValue::decode(reader, prop)?;
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::fmt;
+
+use crate::bitreader::{BitReader, ReadError, ReadErrorKind, ReadResult};
+use crate::clc::CLCListenEvents;
+use crate::svc::{SVCGameEvent, SVCGameEventList, MAX_EVENT_BITS};
+
+// Width of the key types of SVC_GameEventList, TYPE_LOCAL ends the keys of an event
+const KEY_TYPE_BITS: usize = 3;
+const TYPE_LOCAL: u8 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEventKeyType {
+    String = 1,
+    Float = 2,
+    Long = 3,
+    Short = 4,
+    Byte = 5,
+    Bool = 6,
+    UInt64 = 7,
+}
+
+impl GameEventKeyType {
+    fn from_u8(key_type: u8) -> Option<Self> {
+        Some(match key_type {
+            1 => GameEventKeyType::String,
+            2 => GameEventKeyType::Float,
+            3 => GameEventKeyType::Long,
+            4 => GameEventKeyType::Short,
+            5 => GameEventKeyType::Byte,
+            6 => GameEventKeyType::Bool,
+            7 => GameEventKeyType::UInt64,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameEventDescriptor {
+    pub id: u16,
+    pub name: CString,
+    // In the order their values are sent
+    pub keys: Vec<(CString, GameEventKeyType)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEventValue {
+    String(CString),
+    Float(f32),
+    Long(i32),
+    Short(i16),
+    Byte(u8),
+    Bool(bool),
+    UInt64(u64),
+}
+
+impl GameEventValue {
+    fn read(reader: &mut BitReader, key_type: GameEventKeyType) -> ReadResult<Self> {
+        Ok(match key_type {
+            GameEventKeyType::String => GameEventValue::String(reader.read_string()?),
+            GameEventKeyType::Float => GameEventValue::Float(reader.read_f32()?),
+            GameEventKeyType::Long => GameEventValue::Long(reader.read_i32(32)?),
+            GameEventKeyType::Short => GameEventValue::Short(reader.read_i16(16)?),
+            GameEventKeyType::Byte => GameEventValue::Byte(reader.read_u8(8)?),
+            GameEventKeyType::Bool => GameEventValue::Bool(reader.read_u8(1)? != 0),
+            GameEventKeyType::UInt64 => GameEventValue::UInt64(reader.read_u64(64)?),
+        })
+    }
+
+    // Integer keys of any width, e.g. the user IDs of player_death
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            GameEventValue::Long(value) => Some(*value as i64),
+            GameEventValue::Short(value) => Some(*value as i64),
+            GameEventValue::Byte(value) => Some(*value as i64),
+            GameEventValue::Bool(value) => Some(*value as i64),
+            GameEventValue::UInt64(value) => Some(*value as i64),
+            GameEventValue::String(_) | GameEventValue::Float(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for GameEventValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameEventValue::String(string) => write!(f, "{:?}", string),
+            GameEventValue::Float(value) => write!(f, "{}", value),
+            GameEventValue::Long(value) => write!(f, "{}", value),
+            GameEventValue::Short(value) => write!(f, "{}", value),
+            GameEventValue::Byte(value) => write!(f, "{}", value),
+            GameEventValue::Bool(value) => write!(f, "{}", value),
+            GameEventValue::UInt64(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+// SVC_GameEvent decoded against its descriptor
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameEvent {
+    pub id: u16,
+    pub name: CString,
+    pub keys: Vec<(CString, GameEventValue)>,
+}
+
+impl GameEvent {
+    pub fn get(&self, key: &str) -> Option<&GameEventValue> {
+        self.keys.iter().find(|(name, _)| name.to_bytes() == key.as_bytes()).map(|(_, value)| value)
+    }
+}
+
+impl fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name.to_string_lossy())?;
+        for (key, value) in &self.keys {
+            write!(f, " {}={}", key.to_string_lossy(), value)?;
+        }
+        Ok(())
+    }
+}
+
+// Game events the server announced in SVC_GameEventList, by ID
+#[derive(Debug, Default, Clone)]
+pub struct GameEventRegistry {
+    descriptors: BTreeMap<u16, GameEventDescriptor>,
+}
+
+impl GameEventRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // CGameEventManager::ParseEventList, replaces the descriptors
+    pub fn load(&mut self, message: &SVCGameEventList) -> ReadResult<()> {
+        let mut reader = BitReader::new(message.data.clone());
+        let mut descriptors = BTreeMap::new();
+
+        for _ in 0..message.n_num_events {
+            let id = reader.read_u16(MAX_EVENT_BITS)?;
+            let name = reader.read_string()?;
+
+            let mut keys = Vec::new();
+            loop {
+                let start = reader.pos;
+                let key_type = reader.read_u8(KEY_TYPE_BITS)?;
+                if key_type == TYPE_LOCAL {
+                    break;
+                }
+                let key_type = GameEventKeyType::from_u8(key_type)
+                    .ok_or(ReadError::new(ReadErrorKind::InvalidValue, start, KEY_TYPE_BITS))?;
+                keys.push((reader.read_string()?, key_type));
+            }
+
+            descriptors.insert(id, GameEventDescriptor { id, name, keys });
+        }
+
+        self.descriptors = descriptors;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.descriptors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.descriptors.len()
+    }
+
+    pub fn get(&self, id: u16) -> Option<&GameEventDescriptor> {
+        self.descriptors.get(&id)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&GameEventDescriptor> {
+        self.descriptors.values().find(|descriptor| descriptor.name.to_bytes() == name.as_bytes())
+    }
+
+    pub fn descriptors(&self) -> impl Iterator<Item = &GameEventDescriptor> {
+        self.descriptors.values()
+    }
+
+    // Decodes an event with the keys of its descriptor
+    pub fn decode(&self, message: &SVCGameEvent) -> ReadResult<GameEvent> {
+        let mut reader = BitReader::new(message.data.clone());
+        let id = reader.read_u16(MAX_EVENT_BITS)?;
+        let descriptor = self.descriptors.get(&id)
+            .ok_or(ReadError::new(ReadErrorKind::InvalidValue, 0, MAX_EVENT_BITS))?;
+
+        let mut keys = Vec::with_capacity(descriptor.keys.len());
+        for (key, key_type) in &descriptor.keys {
+            keys.push((key.clone(), GameEventValue::read(&mut reader, *key_type)?));
+        }
+
+        Ok(GameEvent { id, name: descriptor.name.clone(), keys })
+    }
+
+    // Names of the events a client subscribed to, bit N of the mask is event ID N. IDs the server
+    // never announced are left out.
+    pub fn listened(&self, message: &CLCListenEvents) -> Vec<&CStr> {
+        self.descriptors.values()
+            .filter(|descriptor| {
+                let id = descriptor.id as usize;
+                message.events.get(id / 32).is_some_and(|word| word & (1 << (id % 32)) != 0)
+            })
+            .map(|descriptor| descriptor.name.as_c_str())
+            .collect()
+    }
+}
@@ -0,0 +1,374 @@
+use std::ffi::{CString, NulError};
+use std::fmt;
+
+use crate::bitreader::{BitReader, ReadError, ReadErrorKind, ReadResult};
+use crate::bitwriter::BitWriter;
+
+const TYPE_NONE: u8 = 0;
+const TYPE_STRING: u8 = 1;
+const TYPE_INT: u8 = 2;
+const TYPE_FLOAT: u8 = 3;
+const TYPE_PTR: u8 = 4;
+const TYPE_WSTRING: u8 = 5;
+const TYPE_COLOR: u8 = 6;
+const TYPE_UINT64: u8 = 7;
+// TYPE_NUMTYPES ends a list of keys. Branches that know the compiled int types (8 to 10) end
+// it with 11, the other ones with 8.
+pub const TYPE_END: u8 = 8;
+pub const TYPE_END_COMPILED: u8 = 11;
+
+// Sections nested deeper than that are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyValue {
+    Subkeys(Vec<KeyValues>),
+    String(CString),
+    Int(i32),
+    Float(f32),
+    Ptr(u32),
+    // The engine writes no data for wide strings, they're only found in text and sent as strings
+    WString(String),
+    Color([u8; 4]),
+    UInt64(u64),
+}
+
+// A KeyValues node, a named value or section
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyValues {
+    pub name: CString,
+    pub value: KeyValue,
+}
+
+impl KeyValues {
+    // Panics if `name` contains a NUL, see try_new
+    #[track_caller]
+    pub fn new(name: &str, value: KeyValue) -> Self {
+        Self::try_new(name, value).expect("NUL in key name")
+    }
+
+    pub fn try_new(name: &str, value: KeyValue) -> Result<Self, NulError> {
+        Ok(Self {
+            name: CString::new(name)?,
+            value,
+        })
+    }
+
+    pub fn section(name: &str, subkeys: Vec<KeyValues>) -> Self {
+        Self::new(name, KeyValue::Subkeys(subkeys))
+    }
+
+    // Empty for anything but a section
+    pub fn subkeys(&self) -> &[KeyValues] {
+        match &self.value {
+            KeyValue::Subkeys(subkeys) => subkeys,
+            _ => &[],
+        }
+    }
+
+    // Returns the first subkey named `name`, case insensitively as the engine does
+    pub fn get(&self, name: &str) -> Option<&KeyValues> {
+        self.subkeys().iter().find(|key| key.name.to_bytes().eq_ignore_ascii_case(name.as_bytes()))
+    }
+
+    fn binary_type(&self) -> u8 {
+        match self.value {
+            KeyValue::Subkeys(_) => TYPE_NONE,
+            KeyValue::String(_) => TYPE_STRING,
+            KeyValue::Int(_) => TYPE_INT,
+            KeyValue::Float(_) => TYPE_FLOAT,
+            KeyValue::Ptr(_) => TYPE_PTR,
+            KeyValue::WString(_) => TYPE_STRING,
+            KeyValue::Color(_) => TYPE_COLOR,
+            KeyValue::UInt64(_) => TYPE_UINT64,
+        }
+    }
+}
+
+// Reads a list of keys written by KeyValues::WriteAsBinary, up to and including its end type.
+// Both end types are accepted, the compiled int types are never sent.
+pub fn read_binary(reader: &mut BitReader) -> ReadResult<Vec<KeyValues>> {
+    read_binary_list(reader, 0)
+}
+
+fn read_binary_list(reader: &mut BitReader, depth: usize) -> ReadResult<Vec<KeyValues>> {
+    let mut keys = Vec::new();
+
+    loop {
+        let type_pos = reader.pos;
+        let key_type = reader.read_u8(8)?;
+        if key_type == TYPE_END || key_type == TYPE_END_COMPILED {
+            break;
+        }
+
+        let name = reader.read_string()?;
+        let value = match key_type {
+            TYPE_NONE => {
+                if depth >= MAX_DEPTH {
+                    return Err(ReadError::new(ReadErrorKind::InvalidValue, type_pos, 8));
+                }
+                KeyValue::Subkeys(read_binary_list(reader, depth + 1)?)
+            },
+            TYPE_STRING => KeyValue::String(reader.read_string()?),
+            TYPE_INT => KeyValue::Int(reader.read_i32(32)?),
+            TYPE_FLOAT => KeyValue::Float(reader.read_f32()?),
+            TYPE_PTR => KeyValue::Ptr(reader.read_u32(32)?),
+            TYPE_COLOR => {
+                let color = reader.read_bytes(4)?;
+                KeyValue::Color([color[0], color[1], color[2], color[3]])
+            },
+            TYPE_UINT64 => KeyValue::UInt64(reader.read_u64(64)?),
+            // The engine writes the type of wide strings without their value, anything after it
+            // would be misread
+            TYPE_WSTRING => return Err(ReadError::new(ReadErrorKind::InvalidValue, type_pos, 8)),
+            _ => return Err(ReadError::new(ReadErrorKind::InvalidValue, type_pos, 8)),
+        };
+        keys.push(KeyValues { name, value });
+    }
+
+    Ok(keys)
+}
+
+// Writes a list of keys as KeyValues::WriteAsBinary does, followed by `end`
+pub fn write_binary(writer: &mut BitWriter, keys: &[KeyValues], end: u8) {
+    for key in keys {
+        writer.write_u8(key.binary_type(), 8);
+        writer.write_string(&key.name);
+
+        match &key.value {
+            KeyValue::Subkeys(subkeys) => write_binary(writer, subkeys, end),
+            KeyValue::String(string) => writer.write_string(string),
+            KeyValue::Int(int) => writer.write_i32(*int, 32),
+            KeyValue::Float(float) => writer.write_f32(*float),
+            KeyValue::Ptr(ptr) => writer.write_u32(*ptr, 32),
+            // As GetString converts them, up to the first NUL like any C string
+            KeyValue::WString(string) => {
+                let string = string.split('\0').next().unwrap_or_default();
+                writer.write_string(&CString::new(string).unwrap());
+            },
+            KeyValue::Color(color) => writer.write_bytes(color),
+            KeyValue::UInt64(int) => writer.write_u64(*int, 64),
+        }
+    }
+    writer.write_u8(end, 8);
+}
+
+fn write_quoted(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            _ => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl KeyValues {
+    fn write_text(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let tabs = "\t".repeat(indent);
+        write!(f, "{}", tabs)?;
+        write_quoted(f, &self.name.to_string_lossy())?;
+
+        let value = match &self.value {
+            KeyValue::Subkeys(subkeys) => {
+                writeln!(f, "\n{}{{", tabs)?;
+                for key in subkeys {
+                    key.write_text(f, indent + 1)?;
+                }
+                return writeln!(f, "{}}}", tabs);
+            },
+            KeyValue::String(string) => string.to_string_lossy().into_owned(),
+            KeyValue::Int(int) => int.to_string(),
+            KeyValue::Float(float) => format!("{:?}", float),
+            KeyValue::Ptr(ptr) => ptr.to_string(),
+            KeyValue::WString(string) => string.clone(),
+            KeyValue::Color([r, g, b, a]) => format!("{} {} {} {}", r, g, b, a),
+            // The text parser only recognizes this exact form as a uint64
+            KeyValue::UInt64(int) => format!("0x{:016x}", int),
+        };
+
+        write!(f, "\t\t")?;
+        write_quoted(f, &value)?;
+        writeln!(f)
+    }
+}
+
+// Text format, as KeyValues::SaveToFile writes it
+impl fmt::Display for KeyValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_text(f, 0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextError {
+    // 1-based line the error was found on
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for TextError {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    String(String),
+    Open,
+    Close,
+}
+
+struct Tokenizer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl Tokenizer<'_> {
+    fn error(&self, message: &'static str) -> TextError {
+        TextError { line: self.line, message }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c == '\n' {
+                self.line += 1;
+                self.chars.next();
+            } else if c.is_whitespace() {
+                self.chars.next();
+            } else if c == '/' {
+                let mut ahead = self.chars.clone();
+                ahead.next();
+                if ahead.peek() != Some(&'/') {
+                    return;
+                }
+                while self.chars.peek().is_some_and(|&c| c != '\n') {
+                    self.chars.next();
+                }
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, TextError> {
+        self.skip_whitespace_and_comments();
+
+        let Some(c) = self.chars.next() else {
+            return Ok(None);
+        };
+
+        match c {
+            '{' => Ok(Some(Token::Open)),
+            '}' => Ok(Some(Token::Close)),
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some('"') => return Ok(Some(Token::String(string))),
+                        Some('\\') => match self.chars.next() {
+                            Some('n') => string.push('\n'),
+                            Some('t') => string.push('\t'),
+                            Some(c) => string.push(c),
+                            None => return Err(self.error("unterminated string")),
+                        },
+                        Some(c) => {
+                            if c == '\n' {
+                                self.line += 1;
+                            }
+                            string.push(c);
+                        },
+                        None => return Err(self.error("unterminated string")),
+                    }
+                }
+            },
+            _ => {
+                // Unquoted token, up to whitespace or a brace
+                let mut string = c.to_string();
+                while let Some(&c) = self.chars.peek() {
+                    if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                        break;
+                    }
+                    string.push(c);
+                    self.chars.next();
+                }
+                Ok(Some(Token::String(string)))
+            },
+        }
+    }
+
+    // Skips a [$PLATFORM] conditional following a key or value
+    fn skip_conditional(&mut self) {
+        self.skip_whitespace_and_comments();
+        if self.chars.peek() == Some(&'[') {
+            while self.chars.next().is_some_and(|c| c != ']') {}
+        }
+    }
+}
+
+// Guesses the type of a text value the way the engine's text loader does
+fn text_value(value: String, tokenizer: &Tokenizer) -> Result<KeyValue, TextError> {
+    let bytes = value.as_bytes();
+    if bytes.len() == 18 && bytes.starts_with(b"0x") {
+        if let Ok(int) = u64::from_str_radix(&value[2..], 16) {
+            return Ok(KeyValue::UInt64(int));
+        }
+    }
+    if let Ok(int) = value.parse::<i32>() {
+        return Ok(KeyValue::Int(int));
+    }
+    if value.contains('.') {
+        if let Ok(float) = value.parse::<f32>() {
+            return Ok(KeyValue::Float(float));
+        }
+    }
+    // Quoted strings keep a literal or escaped NUL, which a C string can't hold
+    CString::new(value).map(KeyValue::String).map_err(|_| tokenizer.error("NUL in value"))
+}
+
+fn parse_text_list(tokenizer: &mut Tokenizer, depth: usize, nested: bool) -> Result<Vec<KeyValues>, TextError> {
+    let mut keys = Vec::new();
+
+    loop {
+        let key = match tokenizer.next_token()? {
+            Some(Token::String(key)) => key,
+            Some(Token::Close) if nested => return Ok(keys),
+            None if !nested => return Ok(keys),
+            None => return Err(tokenizer.error("missing '}'")),
+            Some(_) => return Err(tokenizer.error("expected a key")),
+        };
+        tokenizer.skip_conditional();
+
+        let value = match tokenizer.next_token()? {
+            Some(Token::Open) => {
+                if depth >= MAX_DEPTH {
+                    return Err(tokenizer.error("sections nested too deep"));
+                }
+                KeyValue::Subkeys(parse_text_list(tokenizer, depth + 1, true)?)
+            },
+            Some(Token::String(value)) => text_value(value, tokenizer)?,
+            _ => return Err(tokenizer.error("expected a value or '{'")),
+        };
+        tokenizer.skip_conditional();
+
+        keys.push(KeyValues::try_new(&key, value).map_err(|_| tokenizer.error("NUL in key name"))?);
+    }
+}
+
+// Parses keys in the text format. Values are typed as the engine does: ints, floats with a
+// decimal point and 0x-prefixed 16 digit uint64s, strings otherwise. Colors and pointers come
+// back as strings and ints.
+pub fn parse_text(text: &str) -> Result<Vec<KeyValues>, TextError> {
+    let mut tokenizer = Tokenizer {
+        chars: text.chars().peekable(),
+        line: 1,
+    };
+    parse_text_list(&mut tokenizer, 0, false)
+}
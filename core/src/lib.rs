@@ -0,0 +1,26 @@
+//! Source engine netchannel decoder, free of any OS dependency so it can be used outside of the
+//! injected hook.
+
+pub mod bitreader;
+pub mod bitwriter;
+pub mod builder;
+pub mod checksum;
+pub mod clc;
+pub mod compression;
+pub mod connection;
+pub mod connectionless;
+pub mod entities;
+pub mod gameevents;
+pub mod keyvalues;
+pub mod lzss;
+pub mod message;
+pub mod netchannel;
+pub mod players;
+pub mod profile;
+pub mod sendtable;
+pub mod snappy;
+pub mod split;
+pub mod stringtables;
+pub mod svc;
+pub mod transfer;
+pub mod usermessages;
@@ -0,0 +1,71 @@
+use crate::compression;
+
+// "LZSS" read as a little endian integer
+pub const LZSS_ID: u32 = u32::from_le_bytes(*b"LZSS");
+pub const LZSS_HEADER_SIZE: usize = 8;
+
+const LZSS_LOOKSHIFT: usize = 4;
+
+// Returns the uncompressed size stored in the header, if `data` is LZSS compressed
+pub fn uncompressed_size(data: &[u8]) -> Option<u32> {
+    if data.len() < LZSS_HEADER_SIZE {
+        return None;
+    }
+    if u32::from_le_bytes(data[..4].try_into().unwrap()) != LZSS_ID {
+        return None;
+    }
+    Some(u32::from_le_bytes(data[4..8].try_into().unwrap()))
+}
+
+// Decompresses a buffer produced by CLZSS::Compress, header included. Returns `None` if the
+// data is corrupted, announces more than `max_size` or doesn't decompress to the size announced
+// in the header.
+pub fn decompress(data: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    let actual_size = compression::check_size(uncompressed_size(data)? as usize, max_size)?;
+
+    let mut input = data[LZSS_HEADER_SIZE..].iter().copied();
+    let mut output: Vec<u8> = Vec::with_capacity(actual_size);
+
+    let mut cmd_byte = 0;
+    let mut get_cmd_byte = 0;
+    loop {
+        if get_cmd_byte == 0 {
+            cmd_byte = input.next()?;
+        }
+        get_cmd_byte = (get_cmd_byte + 1) & 0x07;
+
+        if cmd_byte & 0x01 != 0 {
+            // Back reference into what was already decompressed
+            let high = input.next()? as usize;
+            let low = input.next()? as usize;
+            let position = (high << LZSS_LOOKSHIFT) | (low >> LZSS_LOOKSHIFT);
+            let count = (low & 0x0f) + 1;
+
+            if count == 1 {
+                break;
+            }
+            if position + 1 > output.len() || output.len() + count > actual_size {
+                return None;
+            }
+
+            // The source and destination may overlap, copy byte by byte
+            let start = output.len() - position - 1;
+            for i in 0..count {
+                let byte = output[start + i];
+                output.push(byte);
+            }
+        } else {
+            if output.len() + 1 > actual_size {
+                return None;
+            }
+            output.push(input.next()?);
+        }
+
+        cmd_byte >>= 1;
+    }
+
+    if output.len() != actual_size {
+        return None;
+    }
+    Some(output)
+}
@@ -0,0 +1,185 @@
+use crate::bitwriter::BitWriter;
+use crate::clc::*;
+use crate::netchannel::ParseState;
+use crate::profile::GameProfile;
+use crate::svc::*;
+
+// A decoded netchannel message
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetMessage {
+    Nop,
+    Disconnect(NETDisconnect),
+    File(NETFile),
+    Tick(NETTick),
+    StringCmd(NETStringCmd),
+    SetConVar(NETSetConVar),
+    SignonState(NETSignonState),
+    ClientInfo(CLCClientInfo),
+    Move(CLCMove),
+    BaselineAck(CLCBaselineAck),
+    ListenEvents(CLCListenEvents),
+    LoadingProgress(CLCLoadingProgress),
+    CmdKeyValues(CmdKeyValues),
+    ServerInfo(SVCServerInfo),
+    SendTable(SVCSendTable),
+    ClassInfo(SVCClassInfo),
+    SetPause(SVCSetPause),
+    CreateStringTable(SVCCreateStringTable),
+    UpdateStringTable(SVCUpdateStringTable),
+    VoiceInit(SVCVoiceInit),
+    VoiceData(SVCVoiceData),
+    Print(SVCPrint),
+    Sounds(SVCSounds),
+    SetView(SVCSetView),
+    FixAngle(SVCFixAngle),
+    CrosshairAngle(SVCCrosshairAngle),
+    BSPDecal(SVCBSPDecal),
+    SplitScreen(SVCSplitScreen),
+    UserMessage(SVCUserMessage),
+    EntityMessage(SVCEntityMessage),
+    GameEvent(SVCGameEvent),
+    PacketEntities(SVCPacketEntities),
+    TempEntities(SVCTempEntities),
+    Prefetch(SVCPrefetch),
+    Menu(SVCMenu),
+    GameEventList(SVCGameEventList),
+    GetCvarValue(SVCGetCvarValue),
+    ServerCmdKeyValues(CmdKeyValues),
+    // A message we don't know how to parse, everything after it is skipped
+    Unknown(u8),
+}
+
+impl NetMessage {
+    // Returns what kind of message this is, `None` for unknown ones
+    pub fn message_type(&self) -> Option<MessageType> {
+        Some(match self {
+            NetMessage::Nop => MessageType::Nop,
+            NetMessage::Disconnect(_) => MessageType::Disconnect,
+            NetMessage::File(_) => MessageType::File,
+            NetMessage::Tick(_) => MessageType::Tick,
+            NetMessage::StringCmd(_) => MessageType::StringCmd,
+            NetMessage::SetConVar(_) => MessageType::SetConVar,
+            NetMessage::SignonState(_) => MessageType::SignonState,
+            NetMessage::ClientInfo(_) => MessageType::ClientInfo,
+            NetMessage::Move(_) => MessageType::Move,
+            NetMessage::BaselineAck(_) => MessageType::BaselineAck,
+            NetMessage::ListenEvents(_) => MessageType::ListenEvents,
+            NetMessage::LoadingProgress(_) => MessageType::LoadingProgress,
+            NetMessage::CmdKeyValues(_) => MessageType::CmdKeyValues,
+            NetMessage::ServerInfo(_) => MessageType::ServerInfo,
+            NetMessage::SendTable(_) => MessageType::SendTable,
+            NetMessage::ClassInfo(_) => MessageType::ClassInfo,
+            NetMessage::SetPause(_) => MessageType::SetPause,
+            NetMessage::CreateStringTable(_) => MessageType::CreateStringTable,
+            NetMessage::UpdateStringTable(_) => MessageType::UpdateStringTable,
+            NetMessage::VoiceInit(_) => MessageType::VoiceInit,
+            NetMessage::VoiceData(_) => MessageType::VoiceData,
+            NetMessage::Print(_) => MessageType::Print,
+            NetMessage::Sounds(_) => MessageType::Sounds,
+            NetMessage::SetView(_) => MessageType::SetView,
+            NetMessage::FixAngle(_) => MessageType::FixAngle,
+            NetMessage::CrosshairAngle(_) => MessageType::CrosshairAngle,
+            NetMessage::BSPDecal(_) => MessageType::BSPDecal,
+            NetMessage::SplitScreen(_) => MessageType::SplitScreen,
+            NetMessage::UserMessage(_) => MessageType::UserMessage,
+            NetMessage::EntityMessage(_) => MessageType::EntityMessage,
+            NetMessage::GameEvent(_) => MessageType::GameEvent,
+            NetMessage::PacketEntities(_) => MessageType::PacketEntities,
+            NetMessage::TempEntities(_) => MessageType::TempEntities,
+            NetMessage::Prefetch(_) => MessageType::Prefetch,
+            NetMessage::Menu(_) => MessageType::Menu,
+            NetMessage::GameEventList(_) => MessageType::GameEventList,
+            NetMessage::GetCvarValue(_) => MessageType::GetCvarValue,
+            NetMessage::ServerCmdKeyValues(_) => MessageType::ServerCmdKeyValues,
+            NetMessage::Unknown(_) => return None,
+        })
+    }
+
+    // Returns the message ID as sent on the wire by `profile`, `None` if that branch doesn't have
+    // this message
+    pub fn id(&self, profile: &GameProfile) -> Option<u8> {
+        match self {
+            NetMessage::Unknown(id) => Some(*id),
+            _ => profile.message_id(self.message_type()?),
+        }
+    }
+
+    // Writes the message ID followed by the message as `state.profile` expects them. Returns false
+    // and writes nothing if the branch doesn't have this message, there is no encoder for it or
+    // it can't be encoded.
+    pub fn write(&self, writer: &mut BitWriter, state: &ParseState) -> bool {
+        let profile = state.profile;
+        let Some(id) = self.id(profile) else {
+            return false;
+        };
+
+        let mut body = BitWriter::new(Vec::new());
+        match self {
+            NetMessage::Nop | NetMessage::Unknown(_) => {},
+            NetMessage::Disconnect(message) => message.write(&mut body),
+            NetMessage::File(message) => message.write(&mut body),
+            NetMessage::Tick(message) => message.write(&mut body),
+            NetMessage::StringCmd(message) => message.write(&mut body),
+            NetMessage::SetConVar(message) => {
+                if !message.write(&mut body) {
+                    return false;
+                }
+            },
+            NetMessage::SignonState(message) => message.write(&mut body, profile),
+            NetMessage::ClientInfo(message) => message.write(&mut body, profile),
+            NetMessage::Move(message) => message.write(&mut body, &profile.user_cmd),
+            NetMessage::BaselineAck(message) => message.write(&mut body),
+            NetMessage::ListenEvents(message) => message.write(&mut body),
+            NetMessage::LoadingProgress(message) => message.write(&mut body),
+            NetMessage::CmdKeyValues(message) | NetMessage::ServerCmdKeyValues(message) => message.write(&mut body, profile),
+            _ => return false,
+        }
+
+        writer.write_u8(id, 6);
+        writer.write_bits(&body.content, body.pos);
+        true
+    }
+}
+
+// Kind of a message, independent of its ID which changes between engine branches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Nop,
+    Disconnect,
+    File,
+    Tick,
+    StringCmd,
+    SetConVar,
+    SignonState,
+    ClientInfo,
+    Move,
+    BaselineAck,
+    ListenEvents,
+    LoadingProgress,
+    CmdKeyValues,
+    ServerInfo,
+    SendTable,
+    ClassInfo,
+    SetPause,
+    CreateStringTable,
+    UpdateStringTable,
+    VoiceInit,
+    VoiceData,
+    Print,
+    Sounds,
+    SetView,
+    FixAngle,
+    CrosshairAngle,
+    BSPDecal,
+    SplitScreen,
+    UserMessage,
+    EntityMessage,
+    GameEvent,
+    PacketEntities,
+    TempEntities,
+    Prefetch,
+    Menu,
+    GameEventList,
+    GetCvarValue,
+    ServerCmdKeyValues,
+}
@@ -0,0 +1,670 @@
+use std::ffi::CString;
+
+use crate::bitreader::{BitReader, ReadError, ReadErrorKind, ReadResult};
+use crate::checksum;
+use crate::clc::*;
+use crate::compression::{self, CompressionInfo};
+use crate::connectionless::{self, ConnectionlessMessage};
+use crate::lzss;
+use crate::message::{MessageType, NetMessage};
+use crate::profile::{GameProfile, DEFAULT_PROFILE};
+use crate::svc::*;
+use crate::transfer::FileTransfer;
+
+// Which way a packet is travelling, message IDs above the shared net_* ones depend on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::ClientToServer => write!(f, "C->S"),
+            Direction::ServerToClient => write!(f, "S->C"),
+        }
+    }
+}
+
+pub const PACKET_FLAG_RELIABLE:   u8 = 1 << 0;
+pub const PACKET_FLAG_COMPRESSED: u8 = 1 << 1;
+pub const PACKET_FLAG_ENCRYPTED:  u8 = 1 << 2;
+pub const PACKET_FLAG_SPLIT:      u8 = 1 << 3;
+pub const PACKET_FLAG_CHOKED:     u8 = 1 << 4;
+
+// Reliable streams are sent in fragments of 256 bytes
+pub const FRAGMENT_BITS: usize = 8;
+pub const FRAGMENT_SIZE: usize = 1 << FRAGMENT_BITS;
+pub const MAX_FILE_SIZE_BITS: usize = 26;
+// Default net_maxfilesize, the engine refuses to send larger files
+pub const MAX_FILE_SIZE: usize = 16 << 20;
+// Longest filename of a file stream, with its NUL
+const MAX_OSPATH: usize = 260;
+
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct PacketFlag(pub u8);
+
+impl std::fmt::Debug for PacketFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut flags = String::new();
+
+        if self.0 & PACKET_FLAG_RELIABLE != 0 {
+            flags.push_str("Reliable ");
+        }
+        if self.0 & PACKET_FLAG_COMPRESSED != 0 {
+            flags.push_str("Compressed ");
+        }
+        if self.0 & PACKET_FLAG_ENCRYPTED != 0 {
+            flags.push_str("Encrypted ");
+        }
+        if self.0 & PACKET_FLAG_SPLIT != 0 {
+            flags.push_str("Split ");
+        }
+        if self.0 & PACKET_FLAG_CHOKED != 0 {
+            flags.push_str("Chocked ");
+        }
+
+        write!(f, "{}({})", flags, self.0)
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug)]
+pub struct NetPacketHeader {
+    pub sequence: u32,
+    pub sequence_ack: u32,
+    pub flags: PacketFlag,
+    pub checksum: u16,
+    pub rel_state: u8,
+}
+
+impl NetPacketHeader {
+    // Returns the header as sent on the wire
+    pub fn to_bytes(&self) -> [u8; std::mem::size_of::<NetPacketHeader>()] {
+        let mut bytes = [0; std::mem::size_of::<NetPacketHeader>()];
+        bytes[0..4].copy_from_slice(&{ self.sequence }.to_le_bytes());
+        bytes[4..8].copy_from_slice(&{ self.sequence_ack }.to_le_bytes());
+        bytes[8] = self.flags.0;
+        bytes[9..11].copy_from_slice(&{ self.checksum }.to_le_bytes());
+        bytes[11] = self.rel_state;
+        bytes
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DataFragment {
+    transfer_id: u32,
+    filename: Vec<u8>,
+    buffer: Vec<u8>,
+    bytes: u32,
+    is_compressed: bool,
+    uncompressed_size: u32,
+    num_fragments: i32,
+    acked_fragments: i32,
+}
+
+impl Default for DataFragment {
+    fn default() -> Self { 
+        Self {
+            transfer_id: 0,
+            filename: vec![0; MAX_OSPATH],
+            buffer: vec![],
+            bytes: 0,
+            is_compressed: false,
+            uncompressed_size: 0,
+            num_fragments: 0,
+            acked_fragments: 0,
+        }
+    }
+}
+
+// Header sent with a single block or with the first fragment of a stream
+#[derive(Debug, Clone, Default)]
+struct StreamHeader {
+    transfer_id: u32,
+    // Empty unless the stream is a file
+    filename: CString,
+    is_compressed: bool,
+    uncompressed_size: u32,
+    bytes: u32,
+}
+
+// Fragments of a reliable stream as read from one datagram
+#[derive(Debug, Clone)]
+struct StreamPart {
+    // Set when the stream starts over with this datagram
+    header: Option<StreamHeader>,
+    offset: u32,
+    num_fragments: i32,
+    data: Vec<u8>,
+}
+
+impl StreamPart {
+    fn is_file(&self, data: &DataFragment) -> bool {
+        match &self.header {
+            Some(stream) => !stream.filename.is_empty(),
+            None => data.filename[0] != 0,
+        }
+    }
+}
+
+// A datagram read with the stream layout of a profile, not yet added to the receive lists
+#[derive(Debug)]
+pub(crate) struct Datagram {
+    pub(crate) reliable: bool,
+    streams: [Option<StreamPart>; 2],
+    // Unreliable messages following the streams, `None` when a fragment of a stream that isn't
+    // being received ends the datagram early
+    payload: Option<BitReader>,
+}
+
+// State carried from one message to the next
+#[derive(Debug, Clone)]
+pub struct ParseState {
+    // Engine branch the peers speak, it decides message IDs and field layouts
+    pub profile: &'static GameProfile,
+}
+
+impl Default for ParseState {
+    fn default() -> Self {
+        Self {
+            profile: DEFAULT_PROFILE,
+        }
+    }
+}
+
+// What happened to the last packet on its way to the decoder
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PacketMetadata {
+    // Size as received, before decompression
+    pub wire_size: usize,
+    // Set if the whole datagram came compressed
+    pub compression: Option<CompressionInfo>,
+    // The packet carried subchannel data
+    pub reliable: bool,
+}
+
+// Decoding state of a netchannel. Each direction has its own receive list so incoming and
+// outgoing reliable streams are reassembled independently.
+#[derive(Debug, Default, Clone)]
+pub struct NetChannel {
+    client_receive_list: [DataFragment; 2],
+    server_receive_list: [DataFragment; 2],
+    // Completed file transfers not yet taken by the caller
+    files: Vec<FileTransfer>,
+    // Out-of-band packets not yet taken by the caller
+    connectionless: Vec<ConnectionlessMessage>,
+    state: ParseState,
+    last_packet: PacketMetadata,
+}
+
+impl NetChannel {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_profile(profile: &'static GameProfile) -> Self {
+        let mut channel = Self::new();
+        channel.state.profile = profile;
+        channel
+    }
+
+    pub fn profile(&self) -> &'static GameProfile {
+        self.state.profile
+    }
+
+    // Switches to another engine branch, e.g. once it's known from the handshake
+    pub fn set_profile(&mut self, profile: &'static GameProfile) {
+        self.state.profile = profile;
+    }
+
+    // Returns the file transfers completed since the last call
+    pub fn take_files(&mut self) -> Vec<FileTransfer> {
+        std::mem::take(&mut self.files)
+    }
+
+    // Returns the connectionless packets decoded since the last call
+    pub fn take_connectionless(&mut self) -> Vec<ConnectionlessMessage> {
+        std::mem::take(&mut self.connectionless)
+    }
+
+    pub fn last_packet(&self) -> &PacketMetadata {
+        &self.last_packet
+    }
+
+    // Decodes a netchannel packet travelling in `direction`. Compressed packets are decompressed
+    // first, the datagram they carry is decoded like any other one.
+    pub fn process_packet(&mut self, packet: &[u8], direction: Direction) -> ReadResult<Vec<NetMessage>> {
+        let Some(datagram) = self.receive_packet(packet)? else {
+            return Ok(Vec::new());
+        };
+        let datagram = self.read_datagram(&datagram, direction, self.state.profile)?;
+        let payloads = self.receive(datagram, direction)?;
+        process_payloads(&payloads, direction, &mut self.state)
+    }
+
+    // Decompresses a packet and queues it if it's connectionless. Returns the netchannel datagram
+    // it carries otherwise.
+    pub(crate) fn receive_packet(&mut self, packet: &[u8]) -> ReadResult<Option<Vec<u8>>> {
+        self.last_packet = PacketMetadata {
+            wire_size: packet.len(),
+            ..Default::default()
+        };
+
+        let datagram = if compression::is_compressed(packet) {
+            let (datagram, info) = compression::decompress_packet(packet, self.state.profile)?;
+            self.last_packet.compression = Some(info);
+
+            // The engine never compresses twice
+            if compression::is_compressed(&datagram) {
+                return Err(ReadError::new(ReadErrorKind::InvalidValue, 32, 32));
+            }
+            datagram
+        } else {
+            packet.to_vec()
+        };
+
+        // Out-of-band packets carry no netchannel message, they're queued for the caller
+        if connectionless::is_connectionless(&datagram) {
+            self.connectionless.push(ConnectionlessMessage::parse(&datagram)?);
+            return Ok(None);
+        }
+        Ok(Some(datagram))
+    }
+
+    // Reads the header and the reliable stream fragments of a datagram, with the stream layout
+    // of `profile`. Nothing reaches the receive lists until it's passed to `receive`.
+    pub(crate) fn read_datagram(&self, packet: &[u8], direction: Direction, profile: &GameProfile) -> ReadResult<Datagram> {
+        let header_len = std::mem::size_of::<NetPacketHeader>();
+        if packet.len() < header_len {
+            return Err(ReadError::new(ReadErrorKind::EndOfBuffer, 0, header_len * 8));
+        }
+
+        let header: NetPacketHeader = unsafe { std::ptr::read_unaligned(packet.as_ptr() as _) };
+
+        // The engine drops packets that don't match their checksum, so do we
+        if checksum::packet_checksum(packet) != Some(header.checksum) {
+            return Err(ReadError::new(ReadErrorKind::ChecksumMismatch, 0, packet.len() * 8));
+        }
+
+        let content = if header.flags.0 & PACKET_FLAG_CHOKED != 0 {
+            // Chocked packet
+            if packet.len() < header_len + 1 {
+                return Err(ReadError::new(ReadErrorKind::EndOfBuffer, header_len * 8, 8));
+            }
+            &packet[header_len + 1..]
+        } else {
+            &packet[header_len..]
+        };
+
+        let mut reader = BitReader::new(content.to_vec());
+        let mut datagram = Datagram {
+            reliable: header.flags.0 & PACKET_FLAG_RELIABLE != 0,
+            streams: [None, None],
+            payload: None,
+        };
+
+        // Read subchannel data
+        if datagram.reliable {
+            let _ = reader.read_u8(3)?;
+
+            for (data, part) in self.receive_list(direction).iter().zip(&mut datagram.streams) {
+                if reader.read_u8(1)? != 0 {
+                    match read_stream_part(&mut reader, data, profile)? {
+                        Some(stream) => *part = Some(stream),
+                        None => return Ok(datagram),
+                    }
+                }
+            }
+        }
+
+        datagram.payload = Some(reader);
+        Ok(datagram)
+    }
+
+    // Adds the fragments of a datagram to the receive lists. Returns the message buffers it
+    // completed, in the order the engine processes them, without decoding them.
+    pub(crate) fn receive(&mut self, datagram: Datagram, direction: Direction) -> ReadResult<Vec<BitReader>> {
+        self.last_packet.reliable = datagram.reliable;
+
+        let receive_list = match direction {
+            Direction::ClientToServer => &mut self.server_receive_list,
+            Direction::ServerToClient => &mut self.client_receive_list,
+        };
+        for (data, part) in receive_list.iter_mut().zip(datagram.streams) {
+            if let Some(part) = part {
+                apply_stream_part(data, part);
+            }
+        }
+
+        let mut payloads = Vec::new();
+        let Some(payload) = datagram.payload else {
+            return Ok(payloads);
+        };
+
+        if datagram.reliable {
+            for data in receive_list.iter_mut() {
+                if !check_receiving_list(data, direction, &mut payloads, &mut self.files)? {
+                    return Ok(payloads);
+                }
+            }
+        }
+
+        payloads.push(payload);
+        Ok(payloads)
+    }
+
+    // Message buffers `receive` would return for a datagram, without touching the receive lists.
+    // Only the message streams the datagram adds to are copied, file streams are left out.
+    pub(crate) fn preview(&self, datagram: &Datagram, direction: Direction) -> ReadResult<Vec<BitReader>> {
+        let mut payloads = Vec::new();
+        let Some(payload) = &datagram.payload else {
+            return Ok(payloads);
+        };
+
+        for (data, part) in self.receive_list(direction).iter().zip(&datagram.streams) {
+            let Some(part) = part else {
+                continue;
+            };
+            if part.is_file(data) {
+                continue;
+            }
+
+            let mut data = if part.header.is_some() { DataFragment::default() } else { data.clone() };
+            apply_stream_part(&mut data, part.clone());
+            if !check_receiving_list(&mut data, direction, &mut payloads, &mut Vec::new())? {
+                return Ok(payloads);
+            }
+        }
+
+        payloads.push(payload.clone());
+        Ok(payloads)
+    }
+
+    fn receive_list(&self, direction: Direction) -> &[DataFragment; 2] {
+        match direction {
+            Direction::ClientToServer => &self.server_receive_list,
+            Direction::ServerToClient => &self.client_receive_list,
+        }
+    }
+
+    pub(crate) fn parse_state(&self) -> &ParseState {
+        &self.state
+    }
+
+    pub(crate) fn parse_state_mut(&mut self) -> &mut ParseState {
+        &mut self.state
+    }
+}
+
+// Decodes the message buffers of a packet, nothing is processed after a disconnect
+pub(crate) fn process_payloads(payloads: &[BitReader], direction: Direction, state: &mut ParseState) -> ReadResult<Vec<NetMessage>> {
+    let mut messages = Vec::new();
+
+    for payload in payloads {
+        let decoded = process_messages(&mut payload.clone(), direction, state)?;
+        let disconnected = is_disconnect(&decoded);
+        messages.extend(decoded);
+
+        if disconnected {
+            break;
+        }
+    }
+
+    Ok(messages)
+}
+
+fn check_receiving_list(
+    data: &mut DataFragment,
+    direction: Direction,
+    payloads: &mut Vec<BitReader>,
+    files: &mut Vec<FileTransfer>
+) -> ReadResult<bool> {
+    if data.buffer.is_empty() {
+        // ProcessMesssages without data_buffer
+        return Ok(true);
+    }
+    if data.acked_fragments < data.num_fragments {
+        // ProcessMessages without data_buffer 
+        return Ok(true);
+    }
+    if data.acked_fragments > data.num_fragments {
+        // ProcessMessages with data_buffer
+        return Ok(false);
+    }
+
+    if data.is_compressed {
+        uncompress_fragments(data)?;
+    }
+
+    if data.filename[0] == 0 {
+        // ProcessMessages with data_buffer, the buffer is taken so the next transfer starts clean
+        let mut buffer = std::mem::take(&mut data.buffer);
+        buffer.truncate(data.bytes as usize);
+        payloads.push(BitReader::new(buffer));
+    } else {
+        // File stream, hand the reassembled file over to the caller
+        let filename_len = data.filename.iter().position(|&c| c == 0).unwrap_or(data.filename.len());
+        files.push(FileTransfer {
+            transfer_id: data.transfer_id,
+            filename: String::from_utf8_lossy(&data.filename[..filename_len]).into_owned(),
+            direction,
+            data: data.buffer[..data.bytes as usize].to_vec(),
+        });
+        data.buffer.clear();
+    }
+
+    if !data.buffer.is_empty() {
+        data.buffer.clear();
+    }
+
+    // ProcessMessages without data_buffer 
+    Ok(true)
+}
+
+// Replaces the received buffer by its uncompressed content
+fn uncompress_fragments(data: &mut DataFragment) -> ReadResult<()> {
+    let compressed = &data.buffer[..data.bytes as usize];
+    let corrupted = ReadError::new(ReadErrorKind::InvalidValue, 0, data.bytes as usize * 8);
+
+    // The size in the LZSS header must agree with the one announced by the subchannel
+    if data.uncompressed_size == 0 || lzss::uncompressed_size(compressed) != Some(data.uncompressed_size) {
+        data.buffer.clear();
+        return Err(corrupted);
+    }
+
+    let max_size = if data.filename[0] == 0 { compression::MAX_UNCOMPRESSED_SIZE } else { MAX_FILE_SIZE };
+    match lzss::decompress(compressed, max_size) {
+        Some(buffer) => {
+            data.bytes = buffer.len() as u32;
+            data.buffer = buffer;
+            data.is_compressed = false;
+            Ok(())
+        },
+        None => {
+            data.buffer.clear();
+            Err(corrupted)
+        }
+    }
+}
+
+// Returns true if the messages end with a disconnect, after which nothing else is processed
+fn is_disconnect(messages: &[NetMessage]) -> bool {
+    matches!(messages.last(), Some(NetMessage::Disconnect(_)))
+}
+
+pub fn process_messages(reader: &mut BitReader, direction: Direction, state: &mut ParseState) -> ReadResult<Vec<NetMessage>> {
+    let mut messages = Vec::new();
+
+    loop {
+        if reader.bits_left() < 6 {
+            break;
+        }
+
+        let command = reader.read_u8(6)?;
+
+        let message = match state.profile.message_type(command, direction) {
+            Some(message_type) => parse_message(message_type, reader, state)?,
+            None => NetMessage::Unknown(command),
+        };
+
+        let stop = matches!(message, NetMessage::Disconnect(_) | NetMessage::Unknown(_));
+        messages.push(message);
+        if stop {
+            break;
+        }
+    }
+
+    Ok(messages)
+}
+
+fn parse_message(message_type: MessageType, reader: &mut BitReader, state: &mut ParseState) -> ReadResult<NetMessage> {
+    let profile = state.profile;
+
+    Ok(match message_type {
+        MessageType::Nop => NetMessage::Nop,
+        MessageType::Disconnect => NetMessage::Disconnect(NETDisconnect::parse(reader)?),
+        MessageType::File => NetMessage::File(NETFile::parse(reader)?),
+        MessageType::Tick => NetMessage::Tick(NETTick::parse(reader)?),
+        MessageType::StringCmd => NetMessage::StringCmd(NETStringCmd::parse(reader)?),
+        MessageType::SetConVar => NetMessage::SetConVar(NETSetConVar::parse(reader)?),
+        MessageType::SignonState => NetMessage::SignonState(NETSignonState::parse(reader, profile)?),
+        MessageType::ClientInfo => NetMessage::ClientInfo(CLCClientInfo::parse(reader, profile)?),
+        MessageType::Move => NetMessage::Move(CLCMove::parse(reader, &profile.user_cmd)?),
+        MessageType::BaselineAck => NetMessage::BaselineAck(CLCBaselineAck::parse(reader)?),
+        MessageType::ListenEvents => NetMessage::ListenEvents(CLCListenEvents::parse(reader)?),
+        MessageType::LoadingProgress => NetMessage::LoadingProgress(CLCLoadingProgress::parse(reader)?),
+        MessageType::CmdKeyValues => NetMessage::CmdKeyValues(CmdKeyValues::parse(reader)?),
+        MessageType::ServerInfo => NetMessage::ServerInfo(SVCServerInfo::parse(reader, profile)?),
+        MessageType::SendTable => NetMessage::SendTable(SVCSendTable::parse(reader)?),
+        MessageType::ClassInfo => NetMessage::ClassInfo(SVCClassInfo::parse(reader)?),
+        MessageType::SetPause => NetMessage::SetPause(SVCSetPause::parse(reader)?),
+        MessageType::CreateStringTable => NetMessage::CreateStringTable(SVCCreateStringTable::parse(reader, profile)?),
+        MessageType::UpdateStringTable => NetMessage::UpdateStringTable(SVCUpdateStringTable::parse(reader)?),
+        MessageType::VoiceInit => NetMessage::VoiceInit(SVCVoiceInit::parse(reader)?),
+        MessageType::VoiceData => NetMessage::VoiceData(SVCVoiceData::parse(reader)?),
+        MessageType::Print => NetMessage::Print(SVCPrint::parse(reader)?),
+        MessageType::Sounds => NetMessage::Sounds(SVCSounds::parse(reader)?),
+        MessageType::SetView => NetMessage::SetView(SVCSetView::parse(reader)?),
+        MessageType::FixAngle => NetMessage::FixAngle(SVCFixAngle::parse(reader)?),
+        MessageType::CrosshairAngle => NetMessage::CrosshairAngle(SVCCrosshairAngle::parse(reader)?),
+        MessageType::BSPDecal => NetMessage::BSPDecal(SVCBSPDecal::parse(reader)?),
+        MessageType::SplitScreen => NetMessage::SplitScreen(SVCSplitScreen::parse(reader)?),
+        MessageType::UserMessage => NetMessage::UserMessage(SVCUserMessage::parse(reader)?),
+        MessageType::EntityMessage => NetMessage::EntityMessage(SVCEntityMessage::parse(reader)?),
+        MessageType::GameEvent => NetMessage::GameEvent(SVCGameEvent::parse(reader)?),
+        MessageType::PacketEntities => NetMessage::PacketEntities(SVCPacketEntities::parse(reader)?),
+        MessageType::TempEntities => NetMessage::TempEntities(SVCTempEntities::parse(reader, profile)?),
+        MessageType::Prefetch => NetMessage::Prefetch(SVCPrefetch::parse(reader)?),
+        MessageType::Menu => NetMessage::Menu(SVCMenu::parse(reader)?),
+        MessageType::GameEventList => NetMessage::GameEventList(SVCGameEventList::parse(reader)?),
+        MessageType::GetCvarValue => NetMessage::GetCvarValue(SVCGetCvarValue::parse(reader)?),
+        MessageType::ServerCmdKeyValues => NetMessage::ServerCmdKeyValues(CmdKeyValues::parse(reader)?),
+    })
+}
+
+fn read_stream_part(reader: &mut BitReader, data: &DataFragment, profile: &GameProfile) -> ReadResult<Option<StreamPart>> {
+    let mut start_fragment: i32 = 0;
+    let mut num_fragments: i32 = 0;
+    let mut offset: u32 = 0;
+    let mut length: u32 = 0;
+
+    let single_block: bool = reader.read_u8(1)? == 0;
+
+    if !single_block {
+        start_fragment = reader.read_u32(MAX_FILE_SIZE_BITS - FRAGMENT_BITS)? as i32;
+        num_fragments = reader.read_u8(3)? as i32;
+        offset = (start_fragment * (1 << 8)) as u32;
+        length = (num_fragments * (1 << 8)) as u32;
+    }
+
+    let mut header = None;
+    if offset == 0 {
+        let mut stream = StreamHeader::default();
+
+        if single_block {
+            // Check if the data is compressed
+            if reader.read_u8(1)? == 1 {
+                stream.is_compressed = true;
+                stream.uncompressed_size = reader.read_u32(MAX_FILE_SIZE_BITS)?;
+            }
+            stream.bytes = reader.read_u32(profile.max_payload_bits)?;
+        } else {
+            if reader.read_u8(1)? == 1 {
+                stream.transfer_id = reader.read_u32(32)?;
+                let filename = reader.read_string()?;
+                if filename.as_bytes_with_nul().len() > MAX_OSPATH {
+                    return Err(ReadError::new(ReadErrorKind::InvalidValue, reader.pos, filename.as_bytes_with_nul().len() * 8));
+                }
+                stream.filename = filename;
+            }
+
+            if reader.read_u8(1)? == 1 {
+                stream.is_compressed = true;
+                stream.uncompressed_size = reader.read_u32(MAX_FILE_SIZE_BITS)?;
+            }
+            stream.bytes = reader.read_u32(MAX_FILE_SIZE_BITS)?;
+        }
+
+        // Messages are sent from a buffer of one payload, files are limited by their own size
+        let max_size = if stream.filename.is_empty() { 1 << profile.max_payload_bits } else { MAX_FILE_SIZE };
+        if stream.bytes as usize > max_size {
+            return Err(ReadError::new(ReadErrorKind::InvalidValue, reader.pos, 0));
+        }
+
+        if single_block {
+            num_fragments = stream.bytes.div_ceil(1 << 8) as i32;
+            length = (num_fragments * (1 << 8)) as u32;
+        }
+        header = Some(stream);
+    } else {
+        if data.buffer.is_empty() {
+            return Ok(None);
+        }
+    }
+
+    let (bytes, total_fragments) = match &header {
+        Some(stream) => (stream.bytes, stream.bytes.div_ceil(1 << 8) as i32),
+        None => (data.bytes, data.num_fragments),
+    };
+
+    if start_fragment + num_fragments == total_fragments {
+        let rest = (1 << 8) - (bytes % (1 << 8));
+        if rest < (1 << 8) {
+            length = length.checked_sub(rest)
+                .ok_or(ReadError::new(ReadErrorKind::InvalidValue, reader.pos, 0))?;
+        }
+    }
+
+    if offset + length > bytes {
+        return Err(ReadError::new(ReadErrorKind::InvalidValue, reader.pos, length as usize * 8));
+    }
+
+    // buf.ReadBytes
+    Ok(Some(StreamPart {
+        header,
+        offset,
+        num_fragments,
+        data: reader.read_bytes(length as usize)?,
+    }))
+}
+
+fn apply_stream_part(data: &mut DataFragment, part: StreamPart) {
+    if let Some(stream) = part.header {
+        let filename = stream.filename.as_bytes_with_nul();
+        data.filename[..filename.len()].copy_from_slice(filename);
+        data.transfer_id = stream.transfer_id;
+        data.is_compressed = stream.is_compressed;
+        data.uncompressed_size = stream.uncompressed_size;
+        data.bytes = stream.bytes;
+        data.buffer = vec![0; (stream.bytes.div_ceil(4) * 4) as usize];
+        data.num_fragments = stream.bytes.div_ceil(1 << 8) as i32;
+        data.acked_fragments = 0;
+    }
+
+    let offset = part.offset as usize;
+    data.buffer[offset..offset + part.data.len()].copy_from_slice(&part.data);
+    data.acked_fragments += part.num_fragments;
+}
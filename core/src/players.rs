@@ -0,0 +1,302 @@
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::time::Duration;
+
+use crate::message::NetMessage;
+use crate::stringtables::StringTable;
+
+// SteamID64 of account 0, an individual account of the public universe on the desktop instance
+const STEAM_ID64_BASE: u64 = 76561197960265728;
+
+// MAX_CUSTOM_FILES
+const MAX_CUSTOM_FILES: usize = 4;
+// SIGNED_GUID_LEN + 1
+const GUID_SIZE: usize = 33;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SteamId(pub u64);
+
+impl SteamId {
+    pub fn from_account_id(account_id: u32) -> Self {
+        SteamId(STEAM_ID64_BASE + account_id as u64)
+    }
+
+    // Parses "STEAM_X:Y:Z", the form of the player_info_t guid
+    pub fn parse_steam2(steam2: &str) -> Option<Self> {
+        let mut parts = steam2.strip_prefix("STEAM_")?.split(':');
+        let _universe: u8 = parts.next()?.parse().ok()?;
+        let low: u32 = parts.next()?.parse().ok()?;
+        let high: u32 = parts.next()?.parse().ok()?;
+        if low > 1 || high > u32::MAX >> 1 || parts.next().is_some() {
+            return None;
+        }
+        Some(Self::from_account_id(high << 1 | low))
+    }
+
+    pub fn account_id(&self) -> u32 {
+        self.0 as u32
+    }
+
+    // "STEAM_0:Y:Z", with the universe as 0 like these engines print it
+    pub fn steam2(&self) -> String {
+        format!("STEAM_0:{}:{}", self.account_id() & 1, self.account_id() >> 1)
+    }
+
+    // "[U:1:account ID]"
+    pub fn steam3(&self) -> String {
+        format!("[U:1:{}]", self.account_id())
+    }
+
+    pub fn steam64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for SteamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.steam2())
+    }
+}
+
+// player_info_t, the user data of the userinfo table entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerInfo {
+    // SteamID64 when the struct starts with its version and XUID, 0 otherwise
+    pub xuid: u64,
+    pub name: CString,
+    // Unique on the server while it's running
+    pub user_id: i32,
+    // SteamID2 of the player, "BOT" for bots
+    pub guid: CString,
+    // Account ID of the player
+    pub friends_id: u32,
+    pub friends_name: CString,
+    pub fake_player: bool,
+    pub is_hltv: bool,
+    pub custom_files: [u32; MAX_CUSTOM_FILES],
+    pub files_downloaded: u8,
+}
+
+// How player_info_t is laid out, told apart by its size
+struct PlayerInfoLayout {
+    // Starts with a version and the XUID, both 64 bits
+    header: bool,
+    // MAX_PLAYER_NAME_LENGTH
+    name_length: usize,
+}
+
+const PLAYER_INFO_LAYOUTS: [PlayerInfoLayout; 2] = [
+    PlayerInfoLayout { header: true, name_length: 128 },
+    PlayerInfoLayout { header: false, name_length: 32 },
+];
+
+struct StructReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl StructReader<'_> {
+    fn bytes<const N: usize>(&mut self, align: usize) -> [u8; N] {
+        self.pos = self.pos.next_multiple_of(align);
+        let mut bytes: [u8; N] = self.data[self.pos..self.pos + N].try_into().unwrap();
+        self.pos += N;
+        if self.big_endian {
+            bytes.reverse();
+        }
+        bytes
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.bytes(4))
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.bytes(8))
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.pos += 1;
+        self.data[self.pos - 1]
+    }
+
+    // Fixed size array holding a NUL terminated string
+    fn string(&mut self, size: usize) -> CString {
+        let bytes = &self.data[self.pos..self.pos + size];
+        self.pos += size;
+        let len = bytes.iter().position(|byte| *byte == 0).unwrap_or(size);
+        CString::new(&bytes[..len]).unwrap()
+    }
+}
+
+impl PlayerInfo {
+    // Returns `None` if `data` is too short for any layout
+    pub fn parse(data: &[u8], big_endian: bool) -> Option<Self> {
+        let layout = PLAYER_INFO_LAYOUTS.iter().find(|layout| data.len() >= Self::size(layout))?;
+        let mut reader = StructReader { data, pos: 0, big_endian };
+
+        let xuid = if layout.header {
+            let _version = reader.u64();
+            reader.u64()
+        } else {
+            0
+        };
+        let name = reader.string(layout.name_length);
+        let user_id = reader.u32() as i32;
+        let guid = reader.string(GUID_SIZE);
+        let friends_id = reader.u32();
+        let friends_name = reader.string(layout.name_length);
+        let fake_player = reader.u8() != 0;
+        let is_hltv = reader.u8() != 0;
+        let custom_files = [(); MAX_CUSTOM_FILES].map(|_| reader.u32());
+        let files_downloaded = reader.u8();
+
+        Some(Self { xuid, name, user_id, guid, friends_id, friends_name, fake_player, is_hltv, custom_files, files_downloaded })
+    }
+
+    // Bytes used by the fields of a layout, up to files_downloaded
+    fn size(layout: &PlayerInfoLayout) -> usize {
+        let header = if layout.header { 16 } else { 0 };
+        let user_id = (header + layout.name_length).next_multiple_of(4) + 4;
+        let friends_id = (user_id + GUID_SIZE).next_multiple_of(4) + 4;
+        let custom_files = (friends_id + layout.name_length + 2).next_multiple_of(4) + 4 * MAX_CUSTOM_FILES;
+        custom_files + 1
+    }
+
+    // From the account ID, the guid when the server didn't fill it or the XUID
+    pub fn steam_id(&self) -> Option<SteamId> {
+        if self.friends_id != 0 {
+            return Some(SteamId::from_account_id(self.friends_id));
+        }
+        SteamId::parse_steam2(&self.guid.to_string_lossy())
+            .or((self.xuid != 0).then_some(SteamId(self.xuid)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    // Client slot, the index of its userinfo entry
+    pub slot: u16,
+    pub info: PlayerInfo,
+    pub joined: Duration,
+    pub left: Option<Duration>,
+    // Names the player went by before, with when each was replaced
+    pub name_history: Vec<(CString, Duration)>,
+}
+
+impl Player {
+    pub fn name(&self) -> &CStr {
+        &self.info.name
+    }
+
+    pub fn user_id(&self) -> i32 {
+        self.info.user_id
+    }
+
+    pub fn steam_id(&self) -> Option<SteamId> {
+        self.info.steam_id()
+    }
+
+    pub fn is_bot(&self) -> bool {
+        self.info.fake_player
+    }
+}
+
+// Players of a connection's server from the userinfo table, the ones that left included
+#[derive(Debug, Default, Clone)]
+pub struct Roster {
+    // In the order they joined
+    players: Vec<Player>,
+    // Slot of the client itself, from SVC_ServerInfo
+    pub local_slot: Option<u8>,
+    // Who the client said it is in CLC_ClientInfo
+    pub local_steam_id: Option<SteamId>,
+    pub local_friends_name: Option<CString>,
+}
+
+impl Roster {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn process_message(&mut self, message: &NetMessage) {
+        match message {
+            NetMessage::ServerInfo(info) => self.local_slot = Some(info.n_player_slot),
+            NetMessage::ClientInfo(info) => {
+                self.local_steam_id = (info.n_friends_id != 0).then(|| SteamId::from_account_id(info.n_friends_id));
+                self.local_friends_name = Some(info.friends_name.clone());
+            },
+            _ => (),
+        }
+    }
+
+    // Applies the userinfo entry of a slot, empty when the slot was freed. A different user ID
+    // in the slot is a new player.
+    pub fn update(&mut self, slot: u16, user_data: &[u8], big_endian: bool, now: Duration) {
+        let info = match user_data {
+            [] => None,
+            data => match PlayerInfo::parse(data, big_endian) {
+                Some(info) => Some(info),
+                // Garbage, the slot is left as it was
+                None => return,
+            },
+        };
+
+        let current = self.players.iter_mut().rev().find(|player| player.slot == slot && player.left.is_none());
+        match (current, info) {
+            (Some(player), Some(info)) if player.info.user_id == info.user_id => {
+                if player.info.name != info.name {
+                    let name = std::mem::replace(&mut player.info.name, info.name.clone());
+                    player.name_history.push((name, now));
+                }
+                player.info = info;
+            },
+            (current, info) => {
+                if let Some(player) = current {
+                    player.left = Some(now);
+                }
+                if let Some(info) = info {
+                    self.players.push(Player { slot, info, joined: now, left: None, name_history: Vec::new() });
+                }
+            },
+        }
+    }
+
+    // Applies a whole userinfo table, as created for a new map. Players whose slot it doesn't
+    // fill have left.
+    pub fn sync(&mut self, table: &StringTable, big_endian: bool, now: Duration) {
+        for (slot, entry) in table.entries().enumerate() {
+            self.update(slot as u16, &entry.user_data, big_endian, now);
+        }
+
+        let filled = |slot: u16| table.get(slot as usize).is_some_and(|entry| !entry.user_data.is_empty());
+        for player in &mut self.players {
+            if player.left.is_none() && !filled(player.slot) {
+                player.left = Some(now);
+            }
+        }
+    }
+
+    // Everyone who was seen, in the order they joined
+    pub fn players(&self) -> impl Iterator<Item = &Player> {
+        self.players.iter()
+    }
+
+    // Players still on the server
+    pub fn present(&self) -> impl Iterator<Item = &Player> {
+        self.players.iter().filter(|player| player.left.is_none())
+    }
+
+    pub fn by_slot(&self, slot: u16) -> Option<&Player> {
+        self.present().find(|player| player.slot == slot)
+    }
+
+    pub fn by_user_id(&self, user_id: i32) -> Option<&Player> {
+        self.present().find(|player| player.user_id() == user_id)
+    }
+
+    // The client itself once the server sent its userinfo entry
+    pub fn local_player(&self) -> Option<&Player> {
+        self.by_slot(self.local_slot? as u16)
+    }
+}
@@ -0,0 +1,491 @@
+use crate::message::MessageType;
+use crate::netchannel::Direction;
+
+// Wire ID of each message kind known to a branch
+pub type MessageTable = &'static [(u8, MessageType)];
+
+// Name of each user message by ID, the order the game registers them in
+pub type UserMessageTable = &'static [&'static str];
+
+// Fields present in the CUserCmd delta sent in CLC_Move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserCmdLayout {
+    pub weaponselect_bits: usize,
+    pub weaponsubtype_bits: usize,
+    // mousedx and mousedy follow the weapon fields
+    pub mouse_deltas: bool,
+}
+
+// How PacketEntities number the props that changed in an entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropIndexEncoding {
+    // Distance to the previous index over 7 bits widened up to 12, 0xfff ends the list
+    FieldIndex,
+    // FieldIndex, with a bit per entity enabling shorter forms for small distances
+    FieldIndexNewWay,
+    // A set bit followed by the distance as a UBitVar, a clear bit ends the list
+    UBitVar,
+}
+
+// Everything that differs between the engine branches we decode. The packet header (sequence,
+// ack, flags, checksum, reliable state and optional choke byte) is the same on all of them.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GameProfile {
+    // Short name used to select the profile, e.g. "l4d2"
+    pub name: &'static str,
+    pub title: &'static str,
+    // Width of the size of single-block subchannel transfers and of temp entities
+    pub max_payload_bits: usize,
+    pub net_messages: MessageTable,
+    pub clc_messages: MessageTable,
+    pub svc_messages: MessageTable,
+    pub user_messages: UserMessageTable,
+    pub user_cmd: UserCmdLayout,
+    // NET_SignonState carries the server player count, their network IDs and the map name
+    pub signon_state_extended: bool,
+    // SVC_ServerInfo carries the string table CRC after the client CRC
+    pub server_info_string_table_crc: bool,
+    // SVC_ServerInfo carries the map MD5 instead of its CRC
+    pub server_info_map_md5: bool,
+    // SVC_ServerInfo ends with the mission and mutation names
+    pub server_info_mission: bool,
+    // Replay system, SVC_ServerInfo and CLC_ClientInfo carry an is_replay bit
+    pub replay: bool,
+    // SVC_CreateStringTable length is a varint instead of `max_payload_bits + 3` bits
+    pub string_table_varint_length: bool,
+    // Width of the SVC_CreateStringTable flags, 0 when there are none
+    pub string_table_flags_bits: usize,
+    // String table updates say whether their strings are encoded with the shared dictionary
+    pub string_table_dictionaries: bool,
+    // The integers of the player_info_t entries of the userinfo table are byte swapped, the
+    // struct is stored the way the Xbox 360 reads it
+    pub player_info_big_endian: bool,
+    // Compressed packets may use Snappy besides LZSS
+    pub snappy: bool,
+    // Width of the flags of each SendTable prop
+    pub send_prop_flags_bits: usize,
+    // SendTable props carry a priority, it orders the flattened props
+    pub send_prop_priority: bool,
+    pub prop_index_encoding: PropIndexEncoding,
+    // KeyValues know the compiled int types, binary lists end with type 11 instead of 8
+    pub key_values_compiled_types: bool,
+    // Lowest and highest version clients of this branch put in their connect packet
+    pub protocol_versions: (u32, u32),
+    // Steam app IDs reported by A2S_INFO, those past 65535 only in its game ID
+    pub app_ids: &'static [u32],
+    // Game directories reported by A2S_INFO and SVC_ServerInfo
+    pub game_dirs: &'static [&'static str],
+}
+
+const USER_CMD: UserCmdLayout = UserCmdLayout {
+    weaponselect_bits: 11,
+    weaponsubtype_bits: 6,
+    mouse_deltas: true,
+};
+
+const NET_MESSAGES_OB: MessageTable = &[
+    (0, MessageType::Nop),
+    (1, MessageType::Disconnect),
+    (2, MessageType::File),
+    (3, MessageType::Tick),
+    (4, MessageType::StringCmd),
+    (5, MessageType::SetConVar),
+    (6, MessageType::SignonState),
+];
+
+// net_SplitScreenUser took ID 3 and shifted the other ones
+const NET_MESSAGES_L4D: MessageTable = &[
+    (0, MessageType::Nop),
+    (1, MessageType::Disconnect),
+    (2, MessageType::File),
+    (4, MessageType::Tick),
+    (5, MessageType::StringCmd),
+    (6, MessageType::SetConVar),
+    (7, MessageType::SignonState),
+];
+
+const CLC_MESSAGES_2007: MessageTable = &[
+    (8, MessageType::ClientInfo),
+    (9, MessageType::Move),
+    (11, MessageType::BaselineAck),
+    (12, MessageType::ListenEvents),
+];
+
+const CLC_MESSAGES_2013: MessageTable = &[
+    (8, MessageType::ClientInfo),
+    (9, MessageType::Move),
+    (11, MessageType::BaselineAck),
+    (12, MessageType::ListenEvents),
+    (16, MessageType::CmdKeyValues),
+];
+
+const CLC_MESSAGES_L4D: MessageTable = &[
+    (8, MessageType::ClientInfo),
+    (9, MessageType::Move),
+    (11, MessageType::BaselineAck),
+    (12, MessageType::ListenEvents),
+    (16, MessageType::LoadingProgress),
+];
+
+const CLC_MESSAGES_L4D2: MessageTable = &[
+    (8, MessageType::ClientInfo),
+    (9, MessageType::Move),
+    (11, MessageType::BaselineAck),
+    (12, MessageType::ListenEvents),
+    (16, MessageType::LoadingProgress),
+    (18, MessageType::CmdKeyValues),
+];
+
+const SVC_MESSAGES_2007: MessageTable = &[
+    (7, MessageType::Print),
+    (8, MessageType::ServerInfo),
+    (9, MessageType::SendTable),
+    (10, MessageType::ClassInfo),
+    (11, MessageType::SetPause),
+    (12, MessageType::CreateStringTable),
+    (13, MessageType::UpdateStringTable),
+    (14, MessageType::VoiceInit),
+    (15, MessageType::VoiceData),
+    (17, MessageType::Sounds),
+    (18, MessageType::SetView),
+    (19, MessageType::FixAngle),
+    (20, MessageType::CrosshairAngle),
+    (21, MessageType::BSPDecal),
+    (23, MessageType::UserMessage),
+    (24, MessageType::EntityMessage),
+    (25, MessageType::GameEvent),
+    (26, MessageType::PacketEntities),
+    (27, MessageType::TempEntities),
+    (28, MessageType::Prefetch),
+    (29, MessageType::Menu),
+    (30, MessageType::GameEventList),
+    (31, MessageType::GetCvarValue),
+];
+
+const SVC_MESSAGES_2013: MessageTable = &[
+    (7, MessageType::Print),
+    (8, MessageType::ServerInfo),
+    (9, MessageType::SendTable),
+    (10, MessageType::ClassInfo),
+    (11, MessageType::SetPause),
+    (12, MessageType::CreateStringTable),
+    (13, MessageType::UpdateStringTable),
+    (14, MessageType::VoiceInit),
+    (15, MessageType::VoiceData),
+    (17, MessageType::Sounds),
+    (18, MessageType::SetView),
+    (19, MessageType::FixAngle),
+    (20, MessageType::CrosshairAngle),
+    (21, MessageType::BSPDecal),
+    (23, MessageType::UserMessage),
+    (24, MessageType::EntityMessage),
+    (25, MessageType::GameEvent),
+    (26, MessageType::PacketEntities),
+    (27, MessageType::TempEntities),
+    (28, MessageType::Prefetch),
+    (29, MessageType::Menu),
+    (30, MessageType::GameEventList),
+    (31, MessageType::GetCvarValue),
+    (32, MessageType::ServerCmdKeyValues),
+];
+
+// svc_Print moved to 16 to make room for net_SplitScreenUser, svc_SplitScreen took 22
+const SVC_MESSAGES_L4D: MessageTable = &[
+    (8, MessageType::ServerInfo),
+    (9, MessageType::SendTable),
+    (10, MessageType::ClassInfo),
+    (11, MessageType::SetPause),
+    (12, MessageType::CreateStringTable),
+    (13, MessageType::UpdateStringTable),
+    (14, MessageType::VoiceInit),
+    (15, MessageType::VoiceData),
+    (16, MessageType::Print),
+    (17, MessageType::Sounds),
+    (18, MessageType::SetView),
+    (19, MessageType::FixAngle),
+    (20, MessageType::CrosshairAngle),
+    (21, MessageType::BSPDecal),
+    (22, MessageType::SplitScreen),
+    (23, MessageType::UserMessage),
+    (24, MessageType::EntityMessage),
+    (25, MessageType::GameEvent),
+    (26, MessageType::PacketEntities),
+    (27, MessageType::TempEntities),
+    (28, MessageType::Prefetch),
+    (29, MessageType::Menu),
+    (30, MessageType::GameEventList),
+    (31, MessageType::GetCvarValue),
+];
+
+const SVC_MESSAGES_L4D2: MessageTable = &[
+    (8, MessageType::ServerInfo),
+    (9, MessageType::SendTable),
+    (10, MessageType::ClassInfo),
+    (11, MessageType::SetPause),
+    (12, MessageType::CreateStringTable),
+    (13, MessageType::UpdateStringTable),
+    (14, MessageType::VoiceInit),
+    (15, MessageType::VoiceData),
+    (16, MessageType::Print),
+    (17, MessageType::Sounds),
+    (18, MessageType::SetView),
+    (19, MessageType::FixAngle),
+    (20, MessageType::CrosshairAngle),
+    (21, MessageType::BSPDecal),
+    (22, MessageType::SplitScreen),
+    (23, MessageType::UserMessage),
+    (24, MessageType::EntityMessage),
+    (25, MessageType::GameEvent),
+    (26, MessageType::PacketEntities),
+    (27, MessageType::TempEntities),
+    (28, MessageType::Prefetch),
+    (29, MessageType::Menu),
+    (30, MessageType::GameEventList),
+    (31, MessageType::GetCvarValue),
+    (32, MessageType::ServerCmdKeyValues),
+];
+
+// RegisterUserMessages of HL2, HL2DM and the SDK mods
+const USER_MESSAGES_HL2: UserMessageTable = &[
+    "Geiger", "Train", "HudText", "SayText", "SayText2", "TextMsg", "HudMsg", "ResetHUD",
+    "GameTitle", "ItemPickup", "ShowMenu", "Shake", "Fade", "VGUIMenu", "Rumble", "Battery",
+    "Damage", "VoiceMask", "RequestState", "CloseCaption", "HintText", "KeyHintText",
+    "SquadMemberDied", "AmmoDenied", "CreditsMsg", "LogoTimeMsg", "AchievementEvent",
+    "UpdateJalopyRadar",
+];
+
+const USER_MESSAGES_TF2: UserMessageTable = &[
+    "Geiger", "Train", "HudText", "SayText", "SayText2", "TextMsg", "ResetHUD", "GameTitle",
+    "ItemPickup", "ShowMenu", "Shake", "Fade", "VGUIMenu", "Rumble", "CloseCaption", "SendAudio",
+    "VoiceMask", "RequestState", "Damage", "HintText", "KeyHintText", "HudMsg", "AmmoDenied",
+    "AchievementEvent", "UpdateRadar", "VoiceSubtitle", "HudNotify", "HudNotifyCustom",
+    "PlayerStatsUpdate",
+];
+
+const USER_MESSAGES_CSS: UserMessageTable = &[
+    "Geiger", "Train", "HudText", "SayText", "SayText2", "TextMsg", "HudMsg", "ResetHUD",
+    "GameTitle", "ItemPickup", "ShowMenu", "Shake", "Fade", "VGUIMenu", "Rumble", "CloseCaption",
+    "SendAudio", "RawAudio", "VoiceMask", "RequestState", "BarTime", "Damage", "RadioText",
+    "HintText", "KeyHintText", "ReloadEffect", "PlayerAnimEvent", "AmmoDenied", "UpdateRadar",
+    "KillCam",
+];
+
+const USER_MESSAGES_L4D: UserMessageTable = &[
+    "Geiger", "Train", "HudText", "SayText", "SayText2", "TextMsg", "HudMsg", "ResetHUD",
+    "GameTitle", "ItemPickup", "ShowMenu", "Shake", "Fade", "VGUIMenu", "Rumble", "CloseCaption",
+    "CloseCaptionDirect", "SendAudio", "RawAudio", "VoiceMask", "RequestState", "BarTime",
+    "Damage", "RadioText", "HintText", "KeyHintText", "ReloadEffect", "PlayerAnimEvent",
+    "AmmoDenied", "UpdateRadar", "KillCam", "MarkAchievement", "Splatter", "MeleeSlashSplatter",
+    "MeleeClub", "MudSplatter", "SplatterClear", "MessageText", "TransitionRestore", "Spawn",
+    "CreditsLine", "CreditsMsg", "JoinLateMsg", "StatsCrawlMsg", "StatsSkipState", "ShowStats",
+    "BlurFade", "MusicCmd", "WitchBloodSplatter", "AchievementEvent", "PZDmgMsg",
+    "AllPlayersConnectedGameStarting", "VoteStart", "VoteRegistered", "DisconnectToLobby",
+    "CallVoteFailed",
+];
+
+const USER_MESSAGES_PORTAL2: UserMessageTable = &[
+    "Geiger", "Train", "HudText", "SayText", "SayText2", "TextMsg", "HudMsg", "ResetHUD",
+    "GameTitle", "ItemPickup", "ShowMenu", "Shake", "Tilt", "Fade", "VGUIMenu", "Rumble",
+    "Battery", "Damage", "VoiceMask", "RequestState", "CloseCaption", "CloseCaptionDirect",
+    "HintText", "KeyHintText", "SquadMemberDied", "AmmoDenied", "CreditsMsg", "LogoTimeMsg",
+    "AchievementEvent",
+];
+
+pub const SOURCE_2007: GameProfile = GameProfile {
+    name: "source2007",
+    title: "Source 2007 / Orange Box",
+    max_payload_bits: 17,
+    net_messages: NET_MESSAGES_OB,
+    clc_messages: CLC_MESSAGES_2007,
+    svc_messages: SVC_MESSAGES_2007,
+    user_messages: USER_MESSAGES_HL2,
+    user_cmd: USER_CMD,
+    signon_state_extended: false,
+    server_info_string_table_crc: false,
+    server_info_map_md5: false,
+    server_info_mission: false,
+    replay: false,
+    string_table_varint_length: false,
+    string_table_flags_bits: 1,
+    string_table_dictionaries: false,
+    player_info_big_endian: false,
+    snappy: false,
+    send_prop_flags_bits: 16,
+    send_prop_priority: false,
+    prop_index_encoding: PropIndexEncoding::FieldIndex,
+    key_values_compiled_types: false,
+    protocol_versions: (14, 15),
+    // Source SDK Base 2007, the games themselves moved to newer branches
+    app_ids: &[218],
+    game_dirs: &[],
+};
+
+pub const SOURCE_2013_SP: GameProfile = GameProfile {
+    name: "source2013sp",
+    title: "Source SDK 2013 Singleplayer",
+    max_payload_bits: 17,
+    net_messages: NET_MESSAGES_OB,
+    clc_messages: CLC_MESSAGES_2013,
+    svc_messages: SVC_MESSAGES_2013,
+    user_messages: USER_MESSAGES_HL2,
+    user_cmd: USER_CMD,
+    signon_state_extended: false,
+    server_info_string_table_crc: false,
+    server_info_map_md5: true,
+    server_info_mission: false,
+    replay: false,
+    string_table_varint_length: false,
+    string_table_flags_bits: 1,
+    string_table_dictionaries: false,
+    player_info_big_endian: false,
+    snappy: false,
+    send_prop_flags_bits: 16,
+    send_prop_priority: false,
+    prop_index_encoding: PropIndexEncoding::UBitVar,
+    key_values_compiled_types: false,
+    protocol_versions: (24, 24),
+    app_ids: &[243730, 220, 380, 420, 400, 280],
+    game_dirs: &["hl2", "episodic", "ep2", "portal", "hl1"],
+};
+
+pub const SOURCE_2013_MP: GameProfile = GameProfile {
+    name: "source2013mp",
+    title: "Source SDK 2013 Multiplayer",
+    replay: true,
+    app_ids: &[243750, 320, 360],
+    game_dirs: &["hl2mp", "hl1mp"],
+    ..SOURCE_2013_SP
+};
+
+pub const TF2: GameProfile = GameProfile {
+    name: "tf2",
+    title: "Team Fortress 2",
+    user_messages: USER_MESSAGES_TF2,
+    app_ids: &[440],
+    game_dirs: &["tf"],
+    ..SOURCE_2013_MP
+};
+
+pub const CSS: GameProfile = GameProfile {
+    name: "css",
+    title: "Counter-Strike: Source",
+    user_messages: USER_MESSAGES_CSS,
+    app_ids: &[240],
+    game_dirs: &["cstrike"],
+    ..SOURCE_2013_MP
+};
+
+pub const L4D: GameProfile = GameProfile {
+    name: "l4d",
+    title: "Left 4 Dead",
+    max_payload_bits: 17,
+    net_messages: NET_MESSAGES_L4D,
+    clc_messages: CLC_MESSAGES_L4D,
+    svc_messages: SVC_MESSAGES_L4D,
+    user_messages: USER_MESSAGES_L4D,
+    user_cmd: USER_CMD,
+    signon_state_extended: true,
+    server_info_string_table_crc: true,
+    server_info_map_md5: false,
+    server_info_mission: false,
+    replay: false,
+    string_table_varint_length: true,
+    string_table_flags_bits: 2,
+    string_table_dictionaries: true,
+    player_info_big_endian: true,
+    snappy: false,
+    // Cell coordinates took three more flags
+    send_prop_flags_bits: 19,
+    send_prop_priority: false,
+    prop_index_encoding: PropIndexEncoding::FieldIndexNewWay,
+    key_values_compiled_types: true,
+    // The L4D branches send their build number, e.g. 1041 for 1.0.4.1
+    protocol_versions: (1000, 1999),
+    app_ids: &[500],
+    game_dirs: &["left4dead"],
+};
+
+pub const L4D2: GameProfile = GameProfile {
+    name: "l4d2",
+    title: "Left 4 Dead 2",
+    max_payload_bits: 18,
+    clc_messages: CLC_MESSAGES_L4D2,
+    svc_messages: SVC_MESSAGES_L4D2,
+    server_info_mission: true,
+    send_prop_priority: true,
+    protocol_versions: (2000, 2999),
+    app_ids: &[550],
+    game_dirs: &["left4dead2"],
+    ..L4D
+};
+
+pub const PORTAL2: GameProfile = GameProfile {
+    name: "portal2",
+    title: "Portal 2",
+    user_messages: USER_MESSAGES_PORTAL2,
+    server_info_mission: false,
+    snappy: true,
+    app_ids: &[620],
+    game_dirs: &["portal2"],
+    ..L4D2
+};
+
+// Profile used until told otherwise, the sniffer started out on L4D2
+pub const DEFAULT_PROFILE: &GameProfile = &L4D2;
+
+pub const PROFILES: [&GameProfile; 8] = [
+    &L4D, &L4D2, &SOURCE_2007, &SOURCE_2013_SP, &SOURCE_2013_MP, &TF2, &CSS, &PORTAL2,
+];
+
+impl GameProfile {
+    // Looks a profile up by its short name, case insensitively
+    pub fn by_name(name: &str) -> Option<&'static GameProfile> {
+        PROFILES.into_iter().find(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    // Looks a profile up by the Steam app ID a server reports
+    pub fn by_app_id(app_id: u32) -> Option<&'static GameProfile> {
+        PROFILES.into_iter().find(|profile| profile.app_ids.contains(&app_id))
+    }
+
+    // Looks a profile up by the game directory a server reports, case insensitively
+    pub fn by_game_dir(game_dir: &str) -> Option<&'static GameProfile> {
+        PROFILES.into_iter().find(|profile| profile.game_dirs.iter().any(|dir| dir.eq_ignore_ascii_case(game_dir)))
+    }
+
+    // Returns the profiles whose clients send `version` in their connect packet
+    pub fn by_protocol_version(version: u32) -> impl Iterator<Item = &'static GameProfile> {
+        PROFILES.into_iter().filter(move |profile| profile.matches_protocol_version(version))
+    }
+
+    pub fn matches_protocol_version(&self, version: u32) -> bool {
+        (self.protocol_versions.0..=self.protocol_versions.1).contains(&version)
+    }
+
+    // Returns the kind of message `id` is when travelling in `direction`
+    pub fn message_type(&self, id: u8, direction: Direction) -> Option<MessageType> {
+        let table = match direction {
+            Direction::ClientToServer => self.clc_messages,
+            Direction::ServerToClient => self.svc_messages,
+        };
+
+        self.net_messages.iter()
+            .chain(table.iter())
+            .find(|(message_id, _)| *message_id == id)
+            .map(|(_, message_type)| *message_type)
+    }
+
+    // Returns the name of user message `id`, e.g. "SayText2"
+    pub fn user_message_name(&self, id: u8) -> Option<&'static str> {
+        self.user_messages.get(id as usize).copied()
+    }
+
+    // Returns the wire ID of `message_type`, `None` if this branch doesn't have it
+    pub fn message_id(&self, message_type: MessageType) -> Option<u8> {
+        self.net_messages.iter()
+            .chain(self.clc_messages.iter())
+            .chain(self.svc_messages.iter())
+            .find(|(_, kind)| *kind == message_type)
+            .map(|(id, _)| *id)
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fmt;
+
+use crate::bitreader::{BitReader, ReadError, ReadErrorKind, ReadResult};
+use crate::profile::GameProfile;
+
+const PROPINFOBITS_NUMPROPS: usize = 10;
+const PROPINFOBITS_TYPE: usize = 5;
+const PROPINFOBITS_NUMELEMENTS: usize = 10;
+const PROPINFOBITS_NUMBITS: usize = 7;
+// Width of the length of string props
+const DT_MAX_STRING_BITS: usize = 9;
+
+// Prop flags, as the L4D branches number them. The 16 bits of the older branches are moved to
+// these positions when parsed.
+pub const SPROP_UNSIGNED: u32 = 1 << 0;
+pub const SPROP_COORD: u32 = 1 << 1;
+pub const SPROP_NOSCALE: u32 = 1 << 2;
+pub const SPROP_ROUNDDOWN: u32 = 1 << 3;
+pub const SPROP_ROUNDUP: u32 = 1 << 4;
+pub const SPROP_NORMAL: u32 = 1 << 5;
+pub const SPROP_EXCLUDE: u32 = 1 << 6;
+pub const SPROP_XYZE: u32 = 1 << 7;
+pub const SPROP_INSIDEARRAY: u32 = 1 << 8;
+pub const SPROP_PROXY_ALWAYS_YES: u32 = 1 << 9;
+pub const SPROP_IS_A_VECTOR_ELEM: u32 = 1 << 10;
+pub const SPROP_COLLAPSIBLE: u32 = 1 << 11;
+pub const SPROP_COORD_MP: u32 = 1 << 12;
+pub const SPROP_COORD_MP_LOWPRECISION: u32 = 1 << 13;
+pub const SPROP_COORD_MP_INTEGRAL: u32 = 1 << 14;
+pub const SPROP_CELL_COORD: u32 = 1 << 15;
+pub const SPROP_CELL_COORD_LOWPRECISION: u32 = 1 << 16;
+pub const SPROP_CELL_COORD_INTEGRAL: u32 = 1 << 17;
+pub const SPROP_CHANGES_OFTEN: u32 = 1 << 18;
+
+// Priority of the props of branches that don't send one, and the one CHANGES_OFTEN props get
+pub const DEFAULT_PRIORITY: u8 = 128;
+pub const CHANGES_OFTEN_PRIORITY: u8 = 64;
+
+// Tables nested deeper than that are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+// The older branches put CHANGES_OFTEN right after PROXY_ALWAYS_YES and have no cell coords
+fn flags_from_ob(flags: u32) -> u32 {
+    let mut res = flags & 0x3ff;
+    if flags & 1 << 10 != 0 {
+        res |= SPROP_CHANGES_OFTEN;
+    }
+    res | (flags >> 11 & 0x1f) << 10
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendPropType {
+    Int,
+    Float,
+    Vector,
+    // Only x and y of a vector
+    VectorXY,
+    String,
+    Array,
+    DataTable,
+}
+
+impl SendPropType {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => SendPropType::Int,
+            1 => SendPropType::Float,
+            2 => SendPropType::Vector,
+            3 => SendPropType::VectorXY,
+            4 => SendPropType::String,
+            5 => SendPropType::Array,
+            6 => SendPropType::DataTable,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SendProp {
+    pub prop_type: SendPropType,
+    pub name: CString,
+    pub flags: u32,
+    pub priority: u8,
+    // Table of a datatable prop, or table holding the prop an exclude prop removes
+    pub dt_name: CString,
+    pub num_elements: u16,
+    pub low_value: f32,
+    pub high_value: f32,
+    pub bits: u8,
+}
+
+// Description of a networked class or one of its base classes, as SendTable_WriteInfos sends it
+// in SVC_SendTable
+#[derive(Debug, Clone, PartialEq)]
+pub struct SendTable {
+    pub name: CString,
+    pub props: Vec<SendProp>,
+}
+
+impl SendTable {
+    pub fn parse(reader: &mut BitReader, profile: &GameProfile) -> ReadResult<Self> {
+        let name = reader.read_string()?;
+        let num_props = reader.read_u16(PROPINFOBITS_NUMPROPS)?;
+
+        let mut props = Vec::with_capacity(num_props as usize);
+        for _ in 0..num_props {
+            let type_pos = reader.pos;
+            let prop_type = SendPropType::from_u8(reader.read_u8(PROPINFOBITS_TYPE)?)
+                .ok_or(ReadError::new(ReadErrorKind::InvalidValue, type_pos, PROPINFOBITS_TYPE))?;
+            let name = reader.read_string()?;
+
+            let flags = reader.read_u32(profile.send_prop_flags_bits)?;
+            let flags = if profile.send_prop_flags_bits < 19 { flags_from_ob(flags) } else { flags };
+            let priority = if profile.send_prop_priority { reader.read_u8(8)? } else { DEFAULT_PRIORITY };
+
+            let mut prop = SendProp {
+                prop_type,
+                name,
+                flags,
+                priority,
+                dt_name: CString::default(),
+                num_elements: 0,
+                low_value: 0.,
+                high_value: 0.,
+                bits: 0,
+            };
+
+            if prop_type == SendPropType::DataTable || flags & SPROP_EXCLUDE != 0 {
+                prop.dt_name = reader.read_string()?;
+            } else if prop_type == SendPropType::Array {
+                prop.num_elements = reader.read_u16(PROPINFOBITS_NUMELEMENTS)?;
+            } else {
+                prop.low_value = reader.read_f32()?;
+                prop.high_value = reader.read_f32()?;
+                prop.bits = reader.read_u8(PROPINFOBITS_NUMBITS)?;
+            }
+            props.push(prop);
+        }
+
+        Ok(Self {
+            name,
+            props,
+        })
+    }
+}
+
+// A prop of a class once its tables are flattened, the unit PacketEntities index
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatProp {
+    // Table the prop is declared in
+    pub table: CString,
+    pub prop: SendProp,
+    // Layout of the elements of an array prop, the prop declared right before it
+    pub element: Option<SendProp>,
+}
+
+impl FlatProp {
+    // Matches either the prop name or "table.prop"
+    pub fn is_named(&self, name: &str) -> bool {
+        let prop = self.prop.name.to_bytes();
+        match name.split_once('.') {
+            Some((table, name)) if self.table.to_bytes() == table.as_bytes() => prop == name.as_bytes(),
+            _ => prop == name.as_bytes(),
+        }
+    }
+}
+
+fn gather_excludes<'a>(
+    tables: &'a HashMap<CString, SendTable>,
+    table: &'a SendTable,
+    excludes: &mut Vec<(&'a CStr, &'a CStr)>,
+    depth: usize,
+) -> Option<()> {
+    if depth >= MAX_DEPTH {
+        return None;
+    }
+
+    for prop in &table.props {
+        if prop.flags & SPROP_EXCLUDE != 0 {
+            excludes.push((&prop.dt_name, &prop.name));
+        } else if prop.prop_type == SendPropType::DataTable {
+            gather_excludes(tables, tables.get(&prop.dt_name)?, excludes, depth + 1)?;
+        }
+    }
+    Some(())
+}
+
+// SendTable_BuildHierarchy, the props of child tables come before the table's own props, those
+// of collapsible ones (base classes) are inlined among them
+fn gather_props(
+    tables: &HashMap<CString, SendTable>,
+    table: &SendTable,
+    excludes: &[(&CStr, &CStr)],
+    props: &mut Vec<FlatProp>,
+    depth: usize,
+) -> Option<()> {
+    let mut own = Vec::new();
+    iterate_props(tables, table, excludes, &mut own, props, depth)?;
+    props.append(&mut own);
+    Some(())
+}
+
+fn iterate_props(
+    tables: &HashMap<CString, SendTable>,
+    table: &SendTable,
+    excludes: &[(&CStr, &CStr)],
+    own: &mut Vec<FlatProp>,
+    props: &mut Vec<FlatProp>,
+    depth: usize,
+) -> Option<()> {
+    if depth >= MAX_DEPTH {
+        return None;
+    }
+
+    for (i, prop) in table.props.iter().enumerate() {
+        if prop.flags & (SPROP_EXCLUDE | SPROP_INSIDEARRAY) != 0
+            || excludes.contains(&(table.name.as_c_str(), prop.name.as_c_str()))
+        {
+            continue;
+        }
+
+        match prop.prop_type {
+            SendPropType::DataTable => {
+                let child = tables.get(&prop.dt_name)?;
+                if prop.flags & SPROP_COLLAPSIBLE != 0 {
+                    iterate_props(tables, child, excludes, own, props, depth + 1)?;
+                } else {
+                    gather_props(tables, child, excludes, props, depth + 1)?;
+                }
+            },
+            _ => own.push(FlatProp {
+                table: table.name.clone(),
+                prop: prop.clone(),
+                element: match prop.prop_type {
+                    SendPropType::Array => Some(table.props.get(i.checked_sub(1)?)?.clone()),
+                    _ => None,
+                },
+            }),
+        }
+    }
+    Some(())
+}
+
+// Flattens the props of the class described by table `name` in the order PacketEntities index
+// them. `None` if one of the tables it refers to is missing.
+pub fn flatten(tables: &HashMap<CString, SendTable>, name: &CStr) -> Option<Vec<FlatProp>> {
+    let table = tables.get(name)?;
+
+    let mut excludes = Vec::new();
+    gather_excludes(tables, table, &mut excludes, 0)?;
+
+    let mut props = Vec::new();
+    gather_props(tables, table, &excludes, &mut props, 0)?;
+
+    // Props are moved to the front by increasing priority, CHANGES_OFTEN ones count as
+    // CHANGES_OFTEN_PRIORITY. Props of the same priority keep their order only when they
+    // already follow each other.
+    let mut priorities: Vec<u8> = props.iter().map(|prop| prop.prop.priority).collect();
+    priorities.push(CHANGES_OFTEN_PRIORITY);
+    priorities.sort_unstable();
+    priorities.dedup();
+
+    let mut start = 0;
+    for priority in priorities {
+        let mut next = start;
+        for i in start..props.len() {
+            let prop = &props[i].prop;
+            if prop.priority == priority || (priority == CHANGES_OFTEN_PRIORITY && prop.flags & SPROP_CHANGES_OFTEN != 0) {
+                props.swap(next, i);
+                next += 1;
+            }
+        }
+        start = next;
+    }
+
+    Some(props)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropValue {
+    Int(i32),
+    Float(f32),
+    Vector([f32; 3]),
+    VectorXY([f32; 2]),
+    String(CString),
+    Array(Vec<PropValue>),
+}
+
+impl PropValue {
+    // Value of a prop that was never sent
+    pub fn zero(prop: &SendProp) -> Self {
+        match prop.prop_type {
+            SendPropType::Int => PropValue::Int(0),
+            SendPropType::Float => PropValue::Float(0.),
+            SendPropType::Vector => PropValue::Vector([0.; 3]),
+            SendPropType::VectorXY => PropValue::VectorXY([0.; 2]),
+            SendPropType::String => PropValue::String(CString::default()),
+            SendPropType::Array | SendPropType::DataTable => PropValue::Array(Vec::new()),
+        }
+    }
+
+    // Reads a value of `prop` as the dt_encode decoders do
+    pub fn decode(reader: &mut BitReader, prop: &FlatProp) -> ReadResult<Self> {
+        decode_value(reader, &prop.prop, prop.element.as_ref())
+    }
+}
+
+impl fmt::Display for PropValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropValue::Int(int) => write!(f, "{}", int),
+            PropValue::Float(float) => write!(f, "{}", float),
+            PropValue::Vector([x, y, z]) => write!(f, "({}, {}, {})", x, y, z),
+            PropValue::VectorXY([x, y]) => write!(f, "({}, {})", x, y),
+            PropValue::String(string) => write!(f, "{:?}", string),
+            PropValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            },
+        }
+    }
+}
+
+fn decode_float(reader: &mut BitReader, prop: &SendProp) -> ReadResult<f32> {
+    let flags = prop.flags;
+    let bits = prop.bits as usize;
+
+    if flags & SPROP_COORD != 0 {
+        reader.read_bit_coord()
+    } else if flags & SPROP_COORD_MP != 0 {
+        reader.read_bit_coord_mp(false, false)
+    } else if flags & SPROP_COORD_MP_LOWPRECISION != 0 {
+        reader.read_bit_coord_mp(false, true)
+    } else if flags & SPROP_COORD_MP_INTEGRAL != 0 {
+        reader.read_bit_coord_mp(true, false)
+    } else if flags & SPROP_NOSCALE != 0 {
+        reader.read_f32()
+    } else if flags & SPROP_NORMAL != 0 {
+        reader.read_bit_normal()
+    } else if flags & SPROP_CELL_COORD != 0 {
+        reader.read_bit_cell_coord(bits, false, false)
+    } else if flags & SPROP_CELL_COORD_LOWPRECISION != 0 {
+        reader.read_bit_cell_coord(bits, false, true)
+    } else if flags & SPROP_CELL_COORD_INTEGRAL != 0 {
+        reader.read_bit_cell_coord(bits, true, false)
+    } else {
+        // Quantized over `bits` bits between the low and high values
+        let interp = reader.read_u32(bits)?;
+        if bits == 0 {
+            return Ok(prop.low_value);
+        }
+        let fraction = interp as f32 / ((1u64 << bits) - 1) as f32;
+        Ok(prop.low_value + (prop.high_value - prop.low_value) * fraction)
+    }
+}
+
+fn decode_value(reader: &mut BitReader, prop: &SendProp, element: Option<&SendProp>) -> ReadResult<PropValue> {
+    let start = reader.pos;
+
+    Ok(match prop.prop_type {
+        SendPropType::Int => {
+            let bits = prop.bits as usize;
+            if prop.flags & SPROP_UNSIGNED != 0 {
+                PropValue::Int(reader.read_u32(bits)? as i32)
+            } else {
+                PropValue::Int(reader.read_i32(bits)?)
+            }
+        },
+        SendPropType::Float => PropValue::Float(decode_float(reader, prop)?),
+        SendPropType::Vector => {
+            let x = decode_float(reader, prop)?;
+            let y = decode_float(reader, prop)?;
+
+            // Only the sign of z is sent for normals
+            let z = if prop.flags & SPROP_NORMAL != 0 {
+                let negative = reader.read_u8(1)? != 0;
+                let sum = x * x + y * y;
+                let z = if sum < 1. { (1. - sum).sqrt() } else { 0. };
+                if negative { -z } else { z }
+            } else {
+                decode_float(reader, prop)?
+            };
+            PropValue::Vector([x, y, z])
+        },
+        SendPropType::VectorXY => PropValue::VectorXY([decode_float(reader, prop)?, decode_float(reader, prop)?]),
+        SendPropType::String => {
+            let len = reader.read_u16(DT_MAX_STRING_BITS)?;
+            let mut bytes = reader.read_bytes(len as usize)?;
+            if let Some(end) = bytes.iter().position(|&byte| byte == 0) {
+                bytes.truncate(end);
+            }
+            PropValue::String(CString::new(bytes).unwrap())
+        },
+        SendPropType::Array => {
+            let element = element.ok_or(ReadError::new(ReadErrorKind::InvalidValue, start, 0))?;
+            let count = reader.read_u16(crate::svc::bits_for(prop.num_elements as u32))?;
+
+            let mut values = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                values.push(decode_value(reader, element, None)?);
+            }
+            PropValue::Array(values)
+        },
+        SendPropType::DataTable => return Err(ReadError::new(ReadErrorKind::InvalidValue, start, 0)),
+    })
+}
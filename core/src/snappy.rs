@@ -0,0 +1,108 @@
+use crate::compression;
+
+// "SNAP" read as a little endian integer
+pub const SNAPPY_ID: u32 = u32::from_le_bytes(*b"SNAP");
+pub const SNAPPY_HEADER_SIZE: usize = 4;
+
+const TAG_LITERAL: u8 = 0;
+const TAG_COPY_1: u8 = 1;
+const TAG_COPY_2: u8 = 2;
+
+// Returns true if `data` starts with the id the engine puts before Snappy blocks
+pub fn is_snappy(data: &[u8]) -> bool {
+    data.len() >= SNAPPY_HEADER_SIZE && u32::from_le_bytes(data[..4].try_into().unwrap()) == SNAPPY_ID
+}
+
+// Reads the uncompressed length at the start of a raw Snappy block, returns it with its size
+fn read_length(data: &[u8]) -> Option<(usize, usize)> {
+    let mut length: u32 = 0;
+    for (i, byte) in data.iter().take(5).enumerate() {
+        length |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((length as usize, i + 1));
+        }
+    }
+    None
+}
+
+// Returns the uncompressed size announced by a buffer produced by the engine, header included
+pub fn uncompressed_size(data: &[u8]) -> Option<usize> {
+    if !is_snappy(data) {
+        return None;
+    }
+    read_length(&data[SNAPPY_HEADER_SIZE..]).map(|(length, _)| length)
+}
+
+// Decompresses a raw Snappy block preceded by the "SNAP" id. Returns `None` if the data is
+// corrupted, announces more than `max_size` or doesn't decompress to the announced size.
+pub fn decompress(data: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    if !is_snappy(data) {
+        return None;
+    }
+    let data = &data[SNAPPY_HEADER_SIZE..];
+    let (actual_size, mut pos) = read_length(data)?;
+    let actual_size = compression::check_size(actual_size, max_size)?;
+    let mut output: Vec<u8> = Vec::with_capacity(actual_size);
+
+    while pos < data.len() {
+        let tag = data[pos];
+        pos += 1;
+
+        match tag & 0x03 {
+            TAG_LITERAL => {
+                let mut length = (tag >> 2) as usize;
+                // Lengths past 59 are stored in the next 1 to 4 bytes
+                if length >= 60 {
+                    let bytes = length - 59;
+                    let extra = data.get(pos..pos + bytes)?;
+                    length = extra.iter().rev().fold(0, |acc, byte| (acc << 8) | *byte as usize);
+                    pos += bytes;
+                }
+                length = length.checked_add(1)?;
+
+                let literal = data.get(pos..pos.checked_add(length)?)?;
+                if output.len() + length > actual_size {
+                    return None;
+                }
+                output.extend_from_slice(literal);
+                pos += length;
+            },
+            kind => {
+                let (length, offset) = match kind {
+                    TAG_COPY_1 => {
+                        let next = *data.get(pos)? as usize;
+                        pos += 1;
+                        (((tag >> 2) & 0x07) as usize + 4, ((tag as usize >> 5) << 8) | next)
+                    },
+                    TAG_COPY_2 => {
+                        let bytes = data.get(pos..pos + 2)?;
+                        pos += 2;
+                        ((tag >> 2) as usize + 1, u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+                    },
+                    // TAG_COPY_4
+                    _ => {
+                        let bytes = data.get(pos..pos + 4)?;
+                        pos += 4;
+                        ((tag >> 2) as usize + 1, u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+                    },
+                };
+
+                if offset == 0 || offset > output.len() || output.len() + length > actual_size {
+                    return None;
+                }
+
+                // The source and destination may overlap, copy byte by byte
+                let start = output.len() - offset;
+                for i in 0..length {
+                    let byte = output[start + i];
+                    output.push(byte);
+                }
+            },
+        }
+    }
+
+    if output.len() != actual_size {
+        return None;
+    }
+    Some(output)
+}
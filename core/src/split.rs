@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::bitreader::{ReadError, ReadErrorKind, ReadResult};
+
+// NET_HEADER_FLAG_SPLITPACKET
+pub const SPLIT_PACKET_HEADER: [u8; 4] = (-2i32).to_le_bytes();
+pub const SPLIT_HEADER_SIZE: usize = 12;
+// Pieces of a datagram not completed within that time are dropped
+pub const SPLIT_TIMEOUT: Duration = Duration::from_secs(5);
+// Datagrams being reassembled at once, the oldest one is dropped past that
+const MAX_PENDING: usize = 16;
+
+// SPLITPACKET, the header of each piece of a datagram too large to be sent at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitHeader {
+    // Shared by every piece of the same datagram
+    pub sequence: i32,
+    pub packet_number: u8,
+    pub packet_count: u8,
+    // Payload size of every piece but the last one
+    pub split_size: u16,
+}
+
+pub fn is_split(packet: &[u8]) -> bool {
+    packet.starts_with(&SPLIT_PACKET_HEADER)
+}
+
+impl SplitHeader {
+    // Parses the header of a split packet and returns it along with the piece it carries
+    pub fn parse(packet: &[u8]) -> ReadResult<(Self, &[u8])> {
+        if packet.len() < SPLIT_HEADER_SIZE {
+            return Err(ReadError::new(ReadErrorKind::EndOfBuffer, 0, SPLIT_HEADER_SIZE * 8));
+        }
+        if !is_split(packet) {
+            return Err(ReadError::new(ReadErrorKind::InvalidValue, 0, 32));
+        }
+
+        // The packet number is in the high byte of the packet ID and the count in the low one
+        let header = Self {
+            sequence: i32::from_le_bytes(packet[4..8].try_into().unwrap()),
+            packet_number: packet[9],
+            packet_count: packet[8],
+            split_size: u16::from_le_bytes(packet[10..12].try_into().unwrap()),
+        };
+
+        if header.packet_count == 0 || header.packet_number >= header.packet_count || header.split_size == 0 {
+            return Err(ReadError::new(ReadErrorKind::InvalidValue, 64, 32));
+        }
+
+        let piece = &packet[SPLIT_HEADER_SIZE..];
+        if piece.len() > header.split_size as usize {
+            return Err(ReadError::new(ReadErrorKind::InvalidValue, 80, 16));
+        }
+        Ok((header, piece))
+    }
+
+    pub fn to_bytes(&self) -> [u8; SPLIT_HEADER_SIZE] {
+        let mut bytes = [0; SPLIT_HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&SPLIT_PACKET_HEADER);
+        bytes[4..8].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[8] = self.packet_count;
+        bytes[9] = self.packet_number;
+        bytes[10..12].copy_from_slice(&self.split_size.to_le_bytes());
+        bytes
+    }
+}
+
+// Splits a datagram into pieces of at most `split_size` bytes, as NET_SendLong does
+#[track_caller]
+pub fn split_packet(packet: &[u8], sequence: i32, split_size: u16) -> Vec<Vec<u8>> {
+    assert!(split_size > 0);
+    let count = packet.len().div_ceil(split_size as usize).max(1);
+    assert!(count <= u8::MAX as usize);
+
+    (0..count).map(|number| {
+        let start = number * split_size as usize;
+        let end = (start + split_size as usize).min(packet.len());
+        let header = SplitHeader {
+            sequence,
+            packet_number: number as u8,
+            packet_count: count as u8,
+            split_size,
+        };
+
+        let mut piece = header.to_bytes().to_vec();
+        piece.extend_from_slice(&packet[start..end]);
+        piece
+    }).collect()
+}
+
+#[derive(Debug)]
+struct PendingPacket {
+    packet_count: u8,
+    split_size: u16,
+    pieces: Vec<Option<Vec<u8>>>,
+    received: usize,
+    first_seen: Duration,
+}
+
+// Puts split packets back together. Pieces may arrive in any order and more than once, several
+// datagrams can be in flight at the same time.
+#[derive(Debug)]
+pub struct SplitReassembler {
+    pending: HashMap<i32, PendingPacket>,
+    timeout: Duration,
+}
+
+impl Default for SplitReassembler {
+    fn default() -> Self {
+        Self::new(SPLIT_TIMEOUT)
+    }
+}
+
+impl SplitReassembler {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            pending: HashMap::new(),
+            timeout,
+        }
+    }
+
+    // Number of datagrams waiting for more pieces
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Drops the datagrams started before `now - timeout`
+    pub fn evict_expired(&mut self, now: Duration) {
+        let timeout = self.timeout;
+        self.pending.retain(|_, pending| now.saturating_sub(pending.first_seen) <= timeout);
+    }
+
+    // Adds a split packet, returns the whole datagram once its last missing piece arrived
+    pub fn push(&mut self, packet: &[u8], now: Duration) -> ReadResult<Option<Vec<u8>>> {
+        let (header, piece) = SplitHeader::parse(packet)?;
+
+        self.evict_expired(now);
+
+        if !self.pending.contains_key(&header.sequence) && self.pending.len() >= MAX_PENDING {
+            let oldest = self.pending.iter()
+                .min_by_key(|(_, pending)| pending.first_seen)
+                .map(|(sequence, _)| *sequence);
+            if let Some(oldest) = oldest {
+                self.pending.remove(&oldest);
+            }
+        }
+
+        let pending = self.pending.entry(header.sequence).or_insert_with(|| PendingPacket {
+            packet_count: header.packet_count,
+            split_size: header.split_size,
+            pieces: vec![None; header.packet_count as usize],
+            received: 0,
+            first_seen: now,
+        });
+
+        // Every piece of a datagram must agree on how it was split
+        if pending.packet_count != header.packet_count || pending.split_size != header.split_size {
+            self.pending.remove(&header.sequence);
+            return Err(ReadError::new(ReadErrorKind::InvalidValue, 64, 48));
+        }
+
+        let slot = &mut pending.pieces[header.packet_number as usize];
+        if slot.is_some() {
+            // Duplicate
+            return Ok(None);
+        }
+        *slot = Some(piece.to_vec());
+        pending.received += 1;
+
+        if pending.received < pending.packet_count as usize {
+            return Ok(None);
+        }
+
+        let pending = self.pending.remove(&header.sequence).unwrap();
+        let last = pending.pieces.len() - 1;
+        let mut datagram = Vec::with_capacity(pending.pieces.len() * pending.split_size as usize);
+        for (number, piece) in pending.pieces.into_iter().enumerate() {
+            let piece = piece.unwrap();
+            // Only the last piece may be shorter than the split size
+            if number != last && piece.len() != pending.split_size as usize {
+                return Err(ReadError::new(ReadErrorKind::InvalidValue, 80, 16));
+            }
+            datagram.extend(piece);
+        }
+        Ok(Some(datagram))
+    }
+}
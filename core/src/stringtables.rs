@@ -0,0 +1,280 @@
+use std::ffi::{CStr, CString};
+
+use crate::bitreader::{BitReader, ReadError, ReadErrorKind, ReadResult};
+use crate::compression::MAX_UNCOMPRESSED_SIZE;
+use crate::message::NetMessage;
+use crate::profile::GameProfile;
+use crate::svc::{SVCCreateStringTable, SVCUpdateStringTable};
+use crate::{lzss, snappy};
+
+pub const MODEL_PRECACHE: &str = "modelprecache";
+pub const GENERIC_PRECACHE: &str = "genericprecache";
+pub const SOUND_PRECACHE: &str = "soundprecache";
+pub const DECAL_PRECACHE: &str = "decalprecache";
+pub const INSTANCE_BASELINE: &str = "instancebaseline";
+pub const LIGHT_STYLES: &str = "lightstyles";
+pub const USER_INFO: &str = "userinfo";
+pub const DOWNLOADABLES: &str = "downloadables";
+
+// CNetworkStringTableItem::MAX_USERDATA_BITS, width of the size of variable sized user data
+const MAX_USERDATA_BITS: usize = 14;
+// Strings can reuse the start of one of the last MAX_HISTORY strings of the update
+const MAX_HISTORY: usize = 32;
+const HISTORY_INDEX_BITS: usize = 5;
+const SUBSTRING_BITS: usize = 5;
+// Strings are truncated to that by the client
+const MAX_STRING_SIZE: usize = 1024;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StringTableEntry {
+    pub string: CString,
+    // Empty when the entry has none
+    pub user_data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StringTable {
+    pub name: CString,
+    pub max_entries: u16,
+    // Size of the user data of every entry when fixed, in bytes and bits
+    pub user_data_fixed_size: bool,
+    pub user_data_size: u16,
+    pub user_data_size_bits: u8,
+    entries: Vec<StringTableEntry>,
+}
+
+impl StringTable {
+    fn new(message: &SVCCreateStringTable) -> Self {
+        Self {
+            name: message.table_name.clone(),
+            max_entries: message.n_max_entries,
+            user_data_fixed_size: message.b_user_data_fixed_size,
+            user_data_size: message.n_user_data_size,
+            user_data_size_bits: message.n_user_data_size_bits,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&StringTableEntry> {
+        self.entries.get(index)
+    }
+
+    // String of an entry, e.g. the model name of a model index
+    pub fn string(&self, index: usize) -> Option<&CStr> {
+        self.entries.get(index).map(|entry| entry.string.as_c_str())
+    }
+
+    // Index of the entry holding `string`
+    pub fn find(&self, string: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.string.to_bytes() == string.as_bytes())
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &StringTableEntry> {
+        self.entries.iter()
+    }
+
+    // CNetworkStringTable::ParseUpdate, returns the indices of the entries changed
+    fn parse_update(&mut self, reader: &mut BitReader, entries: usize, profile: &GameProfile) -> ReadResult<Vec<usize>> {
+        if !self.max_entries.is_power_of_two() {
+            return Err(ReadError::new(ReadErrorKind::InvalidValue, reader.pos, 0));
+        }
+        let entry_bits = self.max_entries.trailing_zeros() as usize;
+
+        // Dictionary encoded strings need the dictionary file shipped with the game
+        if profile.string_table_dictionaries && reader.read_u8(1)? != 0 {
+            return Err(ReadError::new(ReadErrorKind::InvalidValue, reader.pos - 1, 1));
+        }
+
+        let mut changed = Vec::with_capacity(entries);
+        let mut history: Vec<Vec<u8>> = Vec::with_capacity(MAX_HISTORY);
+        let mut last = -1i64;
+        for _ in 0..entries {
+            let start = reader.pos;
+            let index = if reader.read_u8(1)? != 0 {
+                (last + 1) as usize
+            } else {
+                reader.read_u32(entry_bits)? as usize
+            };
+            last = index as i64;
+            if index >= self.max_entries as usize {
+                return Err(ReadError::new(ReadErrorKind::InvalidValue, start, reader.pos - start));
+            }
+
+            let string = if reader.read_u8(1)? != 0 {
+                let mut string = Vec::new();
+                if reader.read_u8(1)? != 0 {
+                    // The start of a recent string followed by the rest
+                    let start = reader.pos;
+                    let from = reader.read_u8(HISTORY_INDEX_BITS)? as usize;
+                    let len = reader.read_u8(SUBSTRING_BITS)? as usize;
+                    let from = history.get(from).ok_or(ReadError::new(ReadErrorKind::InvalidValue, start, reader.pos - start))?;
+                    string.extend_from_slice(&from[..len.min(from.len())]);
+                }
+                string.extend_from_slice(reader.read_string()?.as_bytes());
+                string.truncate(MAX_STRING_SIZE - 1);
+                Some(string)
+            } else {
+                None
+            };
+
+            let user_data = if reader.read_u8(1)? != 0 {
+                let user_data = if self.user_data_fixed_size {
+                    reader.read_bits(self.user_data_size_bits as usize)?
+                } else {
+                    let size = reader.read_u16(MAX_USERDATA_BITS)? as usize;
+                    reader.read_bytes(size)?
+                };
+                Some(user_data)
+            } else {
+                None
+            };
+
+            if let Some(entry) = self.entries.get_mut(index) {
+                // The string of an existing entry never changes, only its user data
+                if let Some(user_data) = user_data {
+                    entry.user_data = user_data;
+                }
+            } else {
+                let string = string.map(|string| CString::new(string).unwrap()).unwrap_or_default();
+                self.entries.push(StringTableEntry { string, user_data: user_data.unwrap_or_default() });
+            }
+            // Out of order entries were appended
+            let index = index.min(self.entries.len() - 1);
+            changed.push(index);
+
+            if history.len() == MAX_HISTORY {
+                history.remove(0);
+            }
+            history.push(self.entries[index].string.as_bytes().to_vec());
+        }
+        Ok(changed)
+    }
+}
+
+// Reader over the entries of a new table. The same bit as the OB m_bDataCompressed tells they
+// are compressed, the sizes are then followed by an LZSS or Snappy buffer.
+fn entries_reader(message: &SVCCreateStringTable, profile: &GameProfile) -> ReadResult<BitReader> {
+    let mut reader = BitReader::new(message.data.clone());
+    if message.n_flags & 1 == 0 {
+        return Ok(reader);
+    }
+
+    let uncompressed_size = reader.read_u32(32)? as usize;
+    let compressed_size = reader.read_u32(32)? as usize;
+    let start = reader.pos;
+    let data = reader.read_bytes(compressed_size)?;
+    let corrupted = ReadError::new(ReadErrorKind::InvalidValue, start, compressed_size * 8);
+
+    // The size in the inner header must agree with the announced one
+    let data = if let Some(size) = lzss::uncompressed_size(&data) {
+        if size as usize != uncompressed_size {
+            return Err(corrupted);
+        }
+        lzss::decompress(&data, MAX_UNCOMPRESSED_SIZE)
+    } else if profile.snappy && snappy::is_snappy(&data) {
+        if snappy::uncompressed_size(&data) != Some(uncompressed_size) {
+            return Err(corrupted);
+        }
+        snappy::decompress(&data, MAX_UNCOMPRESSED_SIZE)
+    } else {
+        None
+    }.ok_or(corrupted)?;
+    Ok(BitReader::new(data))
+}
+
+// Where an update landed, the table and the entries changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringTableUpdate {
+    pub table_id: usize,
+    pub entries: Vec<usize>,
+}
+
+// The string tables of a connection mirrored from SVC_CreateStringTable and
+// SVC_UpdateStringTable, indexed by the order the server created them in
+#[derive(Debug, Default, Clone)]
+pub struct StringTables {
+    tables: Vec<StringTable>,
+}
+
+impl StringTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Updates the tables with a message, returns what it changed
+    pub fn process_message(&mut self, message: &NetMessage, profile: &GameProfile) -> ReadResult<Option<StringTableUpdate>> {
+        match message {
+            // A new map, the tables are created again
+            NetMessage::ServerInfo(_) => self.tables.clear(),
+            NetMessage::CreateStringTable(message) => return self.create(message, profile).map(Some),
+            NetMessage::UpdateStringTable(message) => return self.update(message, profile).map(Some),
+            _ => (),
+        }
+        Ok(None)
+    }
+
+    fn create(&mut self, message: &SVCCreateStringTable, profile: &GameProfile) -> ReadResult<StringTableUpdate> {
+        let mut table = StringTable::new(message);
+        let entries = entries_reader(message, profile)
+            .and_then(|mut reader| table.parse_update(&mut reader, message.n_num_entries as usize, profile));
+
+        // The table exists even if its entries couldn't be read, later updates refer to it by index
+        self.tables.push(table);
+        Ok(StringTableUpdate { table_id: self.tables.len() - 1, entries: entries? })
+    }
+
+    fn update(&mut self, message: &SVCUpdateStringTable, profile: &GameProfile) -> ReadResult<StringTableUpdate> {
+        let table_id = message.n_table_id as usize;
+        let table = self.tables.get_mut(table_id).ok_or(ReadError::new(ReadErrorKind::InvalidValue, 0, 0))?;
+        let entries = table.parse_update(&mut BitReader::new(message.data.clone()), message.n_changed_entries as usize, profile)?;
+        Ok(StringTableUpdate { table_id, entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    pub fn get(&self, table_id: usize) -> Option<&StringTable> {
+        self.tables.get(table_id)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&StringTable> {
+        self.tables.iter().find(|table| table.name.to_bytes() == name.as_bytes())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StringTable> {
+        self.tables.iter()
+    }
+
+    pub fn model_precache(&self) -> Option<&StringTable> {
+        self.by_name(MODEL_PRECACHE)
+    }
+
+    pub fn sound_precache(&self) -> Option<&StringTable> {
+        self.by_name(SOUND_PRECACHE)
+    }
+
+    pub fn downloadables(&self) -> Option<&StringTable> {
+        self.by_name(DOWNLOADABLES)
+    }
+
+    pub fn user_info(&self) -> Option<&StringTable> {
+        self.by_name(USER_INFO)
+    }
+
+    pub fn instance_baseline(&self) -> Option<&StringTable> {
+        self.by_name(INSTANCE_BASELINE)
+    }
+}
@@ -0,0 +1,657 @@
+use std::ffi::CString;
+
+use crate::bitreader::{BitReader, ReadResult};
+use crate::profile::GameProfile;
+
+const MAX_EDICT_BITS: usize = 11;
+const MAX_DECAL_INDEX_BITS: usize = 9;
+const SP_MODEL_INDEX_BITS: usize = 11;
+const MAX_SOUND_INDEX_BITS: usize = 13;
+const MAX_TABLES_BITS: usize = 5;
+pub(crate) const MAX_EVENT_BITS: usize = 9;
+const NETMSG_LENGTH_BITS: usize = 11;
+const DELTASIZE_BITS: usize = 20;
+const MAX_SERVER_CLASS_BITS: usize = 9;
+
+// Number of bits needed to store values up to `max`
+pub(crate) fn bits_for(max: u32) -> usize {
+    (u32::BITS - max.leading_zeros()) as usize
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Vector {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32
+}
+
+impl Vector {
+    // Read a vector written by CBitWrite::WriteBitVec3Coord
+    pub fn parse_coord(reader: &mut BitReader) -> ReadResult<Self> {
+        let [x, y, z] = reader.read_bit_vec3_coord()?;
+        Ok(Vector { x, y, z })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCPrint {
+    pub text: CString
+}
+
+impl SVCPrint {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            text: reader.read_string()?
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCServerInfo {
+    pub n_protocol: u16,
+    pub n_server_count: u32,
+    pub b_is_hltv: bool,
+    pub b_is_dedicated: bool,
+    pub n_client_crc: u32,
+    pub n_string_table_crc: u32,
+    pub n_max_classes: u16,
+    pub n_map_crc: u32,
+    pub map_md5: [u8; 16],
+    pub n_player_slot: u8,
+    pub n_max_clients: u8,
+    pub f_tick_interval: f32,
+    pub c_os: u8,
+    pub game_dir: CString,
+    pub map_name: CString,
+    pub sky_name: CString,
+    pub host_name: CString,
+    pub mission_name: CString,
+    pub mutation_name: CString,
+    pub b_is_replay: bool
+}
+
+impl SVCServerInfo {
+    pub fn parse(reader: &mut BitReader, profile: &GameProfile) -> ReadResult<Self> {
+        let n_protocol = reader.read_u16(16)?;
+        let n_server_count = reader.read_u32(32)?;
+        let b_is_hltv = reader.read_u8(1)? != 0;
+        let b_is_dedicated = reader.read_u8(1)? != 0;
+        let n_client_crc = reader.read_u32(32)?;
+        let n_string_table_crc = if profile.server_info_string_table_crc {
+            reader.read_u32(32)?
+        } else {
+            0
+        };
+        let n_max_classes = reader.read_u16(16)?;
+
+        let mut n_map_crc = 0;
+        let mut map_md5 = [0; 16];
+        if profile.server_info_map_md5 {
+            map_md5.copy_from_slice(&reader.read_bytes(16)?);
+        } else {
+            n_map_crc = reader.read_u32(32)?;
+        }
+
+        let n_player_slot = reader.read_u8(8)?;
+        let n_max_clients = reader.read_u8(8)?;
+        let f_tick_interval = reader.read_f32()?;
+        let c_os = reader.read_u8(8)?;
+        let game_dir = reader.read_string()?;
+        let map_name = reader.read_string()?;
+        let sky_name = reader.read_string()?;
+        let host_name = reader.read_string()?;
+
+        let mut mission_name = CString::default();
+        let mut mutation_name = CString::default();
+        if profile.server_info_mission {
+            mission_name = reader.read_string()?;
+            mutation_name = reader.read_string()?;
+        }
+        let b_is_replay = profile.replay && reader.read_u8(1)? != 0;
+
+        Ok(Self {
+            n_protocol,
+            n_server_count,
+            b_is_hltv,
+            b_is_dedicated,
+            n_client_crc,
+            n_string_table_crc,
+            n_max_classes,
+            n_map_crc,
+            map_md5,
+            n_player_slot,
+            n_max_clients,
+            f_tick_interval,
+            c_os,
+            game_dir,
+            map_name,
+            sky_name,
+            host_name,
+            mission_name,
+            mutation_name,
+            b_is_replay
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCSendTable {
+    pub b_needs_decoder: bool,
+    pub n_length: u16,
+    pub data: Vec<u8>
+}
+
+impl SVCSendTable {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let b_needs_decoder = reader.read_u8(1)? != 0;
+        let n_length = reader.read_u16(16)?;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            b_needs_decoder,
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassInfo {
+    pub class_id: u16,
+    pub class_name: CString,
+    pub data_table_name: CString
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCClassInfo {
+    pub n_num_server_classes: u16,
+    pub b_create_on_client: bool,
+    pub classes: Vec<ClassInfo>
+}
+
+impl SVCClassInfo {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_num_server_classes = reader.read_u16(16)?;
+        let b_create_on_client = reader.read_u8(1)? != 0;
+
+        let mut classes = Vec::new();
+        if !b_create_on_client {
+            let class_bits = bits_for(n_num_server_classes as u32);
+            for _ in 0..n_num_server_classes {
+                classes.push(ClassInfo {
+                    class_id: reader.read_u16(class_bits)?,
+                    class_name: reader.read_string()?,
+                    data_table_name: reader.read_string()?
+                });
+            }
+        }
+
+        Ok(Self {
+            n_num_server_classes,
+            b_create_on_client,
+            classes
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCSetPause {
+    pub b_paused: bool
+}
+
+impl SVCSetPause {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            b_paused: reader.read_u8(1)? != 0
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCCreateStringTable {
+    pub table_name: CString,
+    pub n_max_entries: u16,
+    pub n_num_entries: u32,
+    pub n_length: u32,
+    pub b_user_data_fixed_size: bool,
+    pub n_user_data_size: u16,
+    pub n_user_data_size_bits: u8,
+    pub n_flags: u8,
+    pub data: Vec<u8>
+}
+
+impl SVCCreateStringTable {
+    pub fn parse(reader: &mut BitReader, profile: &GameProfile) -> ReadResult<Self> {
+        let table_name = reader.read_string()?;
+        let n_max_entries = reader.read_u16(16)?;
+        let n_num_entries = reader.read_u32(bits_for(n_max_entries as u32))?;
+        // Length in bits
+        let n_length = if profile.string_table_varint_length {
+            reader.read_var_u32()?
+        } else {
+            reader.read_u32(profile.max_payload_bits + 3)?
+        };
+
+        let b_user_data_fixed_size = reader.read_u8(1)? != 0;
+        let mut n_user_data_size = 0;
+        let mut n_user_data_size_bits = 0;
+        if b_user_data_fixed_size {
+            n_user_data_size = reader.read_u16(12)?;
+            n_user_data_size_bits = reader.read_u8(4)?;
+        }
+
+        let n_flags = reader.read_u8(profile.string_table_flags_bits)?;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            table_name,
+            n_max_entries,
+            n_num_entries,
+            n_length,
+            b_user_data_fixed_size,
+            n_user_data_size,
+            n_user_data_size_bits,
+            n_flags,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCUpdateStringTable {
+    pub n_table_id: u8,
+    pub n_changed_entries: u16,
+    pub n_length: u32,
+    pub data: Vec<u8>
+}
+
+impl SVCUpdateStringTable {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_table_id = reader.read_u8(MAX_TABLES_BITS)?;
+        let n_changed_entries = if reader.read_u8(1)? != 0 {
+            reader.read_u16(16)?
+        } else {
+            1
+        };
+        let n_length = reader.read_u32(20)?;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            n_table_id,
+            n_changed_entries,
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCVoiceInit {
+    pub voice_codec: CString,
+    pub n_quality: u8,
+    pub n_sample_rate: u16
+}
+
+impl SVCVoiceInit {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let voice_codec = reader.read_string()?;
+        let n_quality = reader.read_u8(8)?;
+        // The sample rate is only sent when the quality is set to the magic value
+        let n_sample_rate = if n_quality == 255 {
+            reader.read_u16(16)?
+        } else {
+            0
+        };
+
+        Ok(Self {
+            voice_codec,
+            n_quality,
+            n_sample_rate
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCVoiceData {
+    pub n_from_client: u8,
+    pub b_proximity: u8,
+    pub n_length: u16,
+    pub data: Vec<u8>
+}
+
+impl SVCVoiceData {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_from_client = reader.read_u8(8)?;
+        let b_proximity = reader.read_u8(8)?;
+        let n_length = reader.read_u16(16)?;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            n_from_client,
+            b_proximity,
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCSounds {
+    pub b_reliable_sound: bool,
+    pub n_num_sounds: u8,
+    pub n_length: u16,
+    pub data: Vec<u8>
+}
+
+impl SVCSounds {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let b_reliable_sound = reader.read_u8(1)? != 0;
+
+        let n_num_sounds;
+        let n_length;
+        if b_reliable_sound {
+            n_num_sounds = 1;
+            n_length = reader.read_u16(8)?;
+        } else {
+            n_num_sounds = reader.read_u8(8)?;
+            n_length = reader.read_u16(16)?;
+        }
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            b_reliable_sound,
+            n_num_sounds,
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCSetView {
+    pub n_entity_index: u16
+}
+
+impl SVCSetView {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            n_entity_index: reader.read_u16(MAX_EDICT_BITS)?
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCFixAngle {
+    pub b_relative: bool,
+    pub angle: [f32; 3]
+}
+
+impl SVCFixAngle {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            b_relative: reader.read_u8(1)? != 0,
+            angle: [reader.read_bit_angle(16)?, reader.read_bit_angle(16)?, reader.read_bit_angle(16)?]
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCCrosshairAngle {
+    pub angle: [f32; 3]
+}
+
+impl SVCCrosshairAngle {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            angle: [reader.read_bit_angle(16)?, reader.read_bit_angle(16)?, reader.read_bit_angle(16)?]
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCBSPDecal {
+    pub pos: Vector,
+    pub n_decal_texture_index: u16,
+    pub n_entity_index: u16,
+    pub n_model_index: u16,
+    pub b_low_priority: bool
+}
+
+impl SVCBSPDecal {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let pos = Vector::parse_coord(reader)?;
+        let n_decal_texture_index = reader.read_u16(MAX_DECAL_INDEX_BITS)?;
+
+        let mut n_entity_index = 0;
+        let mut n_model_index = 0;
+        if reader.read_u8(1)? != 0 {
+            n_entity_index = reader.read_u16(MAX_EDICT_BITS)?;
+            n_model_index = reader.read_u16(SP_MODEL_INDEX_BITS)?;
+        }
+        let b_low_priority = reader.read_u8(1)? != 0;
+
+        Ok(Self {
+            pos,
+            n_decal_texture_index,
+            n_entity_index,
+            n_model_index,
+            b_low_priority
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCSplitScreen {
+    pub n_type: u8,
+    pub n_length: u16,
+    pub data: Vec<u8>
+}
+
+impl SVCSplitScreen {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_type = reader.read_u8(1)?;
+        let n_length = reader.read_u16(NETMSG_LENGTH_BITS)?;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            n_type,
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCUserMessage {
+    pub n_msg_type: u8,
+    pub n_length: u16,
+    pub data: Vec<u8>
+}
+
+impl SVCUserMessage {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_msg_type = reader.read_u8(8)?;
+        let n_length = reader.read_u16(NETMSG_LENGTH_BITS)?;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            n_msg_type,
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCEntityMessage {
+    pub n_entity_index: u16,
+    pub n_class_id: u16,
+    pub n_length: u16,
+    pub data: Vec<u8>
+}
+
+impl SVCEntityMessage {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_entity_index = reader.read_u16(MAX_EDICT_BITS)?;
+        let n_class_id = reader.read_u16(MAX_SERVER_CLASS_BITS)?;
+        let n_length = reader.read_u16(NETMSG_LENGTH_BITS)?;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            n_entity_index,
+            n_class_id,
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCGameEvent {
+    pub n_length: u16,
+    pub data: Vec<u8>
+}
+
+impl SVCGameEvent {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_length = reader.read_u16(NETMSG_LENGTH_BITS)?;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCPacketEntities {
+    pub n_max_entries: u16,
+    pub b_is_delta: bool,
+    pub n_delta_from: i32,
+    pub n_baseline: u8,
+    pub n_updated_entries: u16,
+    pub n_length: u32,
+    pub b_update_baseline: bool,
+    pub data: Vec<u8>
+}
+
+impl SVCPacketEntities {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_max_entries = reader.read_u16(MAX_EDICT_BITS)?;
+        let b_is_delta = reader.read_u8(1)? != 0;
+        let n_delta_from = if b_is_delta {
+            reader.read_i32(32)?
+        } else {
+            -1
+        };
+        let n_baseline = reader.read_u8(1)?;
+        let n_updated_entries = reader.read_u16(MAX_EDICT_BITS)?;
+        let n_length = reader.read_u32(DELTASIZE_BITS)?;
+        let b_update_baseline = reader.read_u8(1)? != 0;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            n_max_entries,
+            b_is_delta,
+            n_delta_from,
+            n_baseline,
+            n_updated_entries,
+            n_length,
+            b_update_baseline,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCTempEntities {
+    pub n_num_entries: u8,
+    pub n_length: u32,
+    pub data: Vec<u8>
+}
+
+impl SVCTempEntities {
+    pub fn parse(reader: &mut BitReader, profile: &GameProfile) -> ReadResult<Self> {
+        let n_num_entries = reader.read_u8(8)?;
+        let n_length = reader.read_u32(profile.max_payload_bits)?;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            n_num_entries,
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCPrefetch {
+    pub n_sound_index: u16
+}
+
+impl SVCPrefetch {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            n_sound_index: reader.read_u16(MAX_SOUND_INDEX_BITS)?
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCMenu {
+    pub n_type: i16,
+    pub n_length: u16,
+    pub data: Vec<u8>
+}
+
+impl SVCMenu {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_type = reader.read_i16(16)?;
+        // Length in bytes
+        let n_length = reader.read_u16(16)?;
+        let data = reader.read_bytes(n_length as usize)?;
+
+        Ok(Self {
+            n_type,
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCGameEventList {
+    pub n_num_events: u16,
+    pub n_length: u32,
+    pub data: Vec<u8>
+}
+
+impl SVCGameEventList {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        let n_num_events = reader.read_u16(MAX_EVENT_BITS)?;
+        let n_length = reader.read_u32(20)?;
+        let data = reader.read_bits(n_length as usize)?;
+
+        Ok(Self {
+            n_num_events,
+            n_length,
+            data
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SVCGetCvarValue {
+    pub i_cookie: i32,
+    pub cvar_name: CString
+}
+
+impl SVCGetCvarValue {
+    pub fn parse(reader: &mut BitReader) -> ReadResult<Self> {
+        Ok(Self {
+            i_cookie: reader.read_i32(32)?,
+            cvar_name: reader.read_string()?
+        })
+    }
+}
@@ -0,0 +1,49 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::netchannel::Direction;
+
+// A file sent over a reliable stream, once all of its fragments were received
+#[derive(Debug, Clone)]
+pub struct FileTransfer {
+    pub transfer_id: u32,
+    // Path as sent by the peer, it must not be trusted
+    pub filename: String,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+impl FileTransfer {
+    // Returns the filename as a relative path with every component that could escape the output
+    // directory removed
+    pub fn sanitized_path(&self) -> PathBuf {
+        let filename = self.filename.replace('\\', "/");
+
+        let mut path = PathBuf::new();
+        for component in Path::new(&filename).components() {
+            if let Component::Normal(part) = component {
+                // Drive letters and alternate data streams on Windows
+                if part.to_string_lossy().contains(':') {
+                    continue;
+                }
+                path.push(part);
+            }
+        }
+
+        if path.as_os_str().is_empty() {
+            path.push(format!("transfer_{}", self.transfer_id));
+        }
+        path
+    }
+
+    // Writes the file under `directory` and returns where it was written
+    pub fn save(&self, directory: &Path) -> io::Result<PathBuf> {
+        let path = directory.join(self.sanitized_path());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, &self.data)?;
+        Ok(path)
+    }
+}
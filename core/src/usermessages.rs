@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt;
+use std::time::Duration;
+
+use crate::bitreader::{BitReader, ReadResult};
+use crate::players::{Roster, SteamId};
+use crate::profile::GameProfile;
+use crate::svc::SVCUserMessage;
+
+// HUD_PRINTCENTER, TextMsg shown in the middle of the screen
+const HUD_PRINTCENTER: u8 = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserMessage {
+    // `client` is the entity index of the sender, 0 for the server
+    SayText { client: u8, text: CString, chat: bool },
+    // `format` is a localization token like "#L4D_Chat_All" taking the name and the text as
+    // parameters, or the text itself
+    SayText2 { client: u8, chat: bool, format: CString, params: Vec<CString> },
+    // HUD_PRINT* destination, text with up to 4 parameters
+    TextMsg { destination: u8, text: CString, params: Vec<CString> },
+    HintText(CString),
+    KeyHintText(Vec<CString>),
+    VGUIMenu { name: CString, show: bool, keys: Vec<(CString, CString)> },
+    Shake { command: u8, amplitude: f32, frequency: f32, duration: f32 },
+    // Durations in 1/512 seconds, RGBA color
+    Fade { duration: u16, hold_time: u16, flags: u16, color: [u8; 4] },
+    // Damage dealt to or by the infected, the layout of `values` depends on `kind`
+    PZDmgMsg { kind: u8, values: Vec<i16> },
+}
+
+// Strings until the payload ends, at most `max`
+fn read_strings(reader: &mut BitReader, max: usize) -> ReadResult<Vec<CString>> {
+    let mut strings = Vec::new();
+    while strings.len() < max && reader.bits_left() >= 8 {
+        strings.push(reader.read_string()?);
+    }
+    Ok(strings)
+}
+
+fn say_text(reader: &mut BitReader) -> ReadResult<UserMessage> {
+    Ok(UserMessage::SayText {
+        client: reader.read_u8(8)?,
+        text: reader.read_string()?,
+        chat: reader.bits_left() >= 8 && reader.read_u8(8)? != 0,
+    })
+}
+
+fn say_text2(reader: &mut BitReader) -> ReadResult<UserMessage> {
+    Ok(UserMessage::SayText2 {
+        client: reader.read_u8(8)?,
+        chat: reader.read_u8(8)? != 0,
+        format: reader.read_string()?,
+        params: read_strings(reader, 4)?,
+    })
+}
+
+fn text_msg(reader: &mut BitReader) -> ReadResult<UserMessage> {
+    Ok(UserMessage::TextMsg {
+        destination: reader.read_u8(8)?,
+        text: reader.read_string()?,
+        params: read_strings(reader, 4)?,
+    })
+}
+
+fn hint_text(reader: &mut BitReader) -> ReadResult<UserMessage> {
+    Ok(UserMessage::HintText(reader.read_string()?))
+}
+
+fn key_hint_text(reader: &mut BitReader) -> ReadResult<UserMessage> {
+    let count = reader.read_u8(8)?;
+    let mut lines = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        lines.push(reader.read_string()?);
+    }
+    Ok(UserMessage::KeyHintText(lines))
+}
+
+fn vgui_menu(reader: &mut BitReader) -> ReadResult<UserMessage> {
+    let name = reader.read_string()?;
+    let show = reader.read_u8(8)? != 0;
+    let count = reader.read_u8(8)?;
+    let mut keys = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        keys.push((reader.read_string()?, reader.read_string()?));
+    }
+    Ok(UserMessage::VGUIMenu { name, show, keys })
+}
+
+fn shake(reader: &mut BitReader) -> ReadResult<UserMessage> {
+    Ok(UserMessage::Shake {
+        command: reader.read_u8(8)?,
+        amplitude: reader.read_f32()?,
+        frequency: reader.read_f32()?,
+        duration: reader.read_f32()?,
+    })
+}
+
+fn fade(reader: &mut BitReader) -> ReadResult<UserMessage> {
+    Ok(UserMessage::Fade {
+        duration: reader.read_u16(16)?,
+        hold_time: reader.read_u16(16)?,
+        flags: reader.read_u16(16)?,
+        color: [reader.read_u8(8)?, reader.read_u8(8)?, reader.read_u8(8)?, reader.read_u8(8)?],
+    })
+}
+
+fn pz_dmg_msg(reader: &mut BitReader) -> ReadResult<UserMessage> {
+    let kind = reader.read_u8(8)?;
+    let mut values = Vec::new();
+    while reader.bits_left() >= 16 {
+        values.push(reader.read_i16(16)?);
+    }
+    Ok(UserMessage::PZDmgMsg { kind, values })
+}
+
+// Decodes the payload of a user message
+pub type UserMessageDecoder = fn(&mut BitReader) -> ReadResult<UserMessage>;
+
+// Decoders by user message name, the profile tells which name an ID stands for
+#[derive(Debug, Clone)]
+pub struct UserMessageRegistry {
+    decoders: HashMap<&'static str, UserMessageDecoder>,
+}
+
+impl Default for UserMessageRegistry {
+    fn default() -> Self {
+        let mut registry = Self { decoders: HashMap::new() };
+        registry.register("SayText", say_text);
+        registry.register("SayText2", say_text2);
+        registry.register("TextMsg", text_msg);
+        registry.register("HintText", hint_text);
+        registry.register("KeyHintText", key_hint_text);
+        registry.register("VGUIMenu", vgui_menu);
+        registry.register("Shake", shake);
+        registry.register("Fade", fade);
+        registry.register("PZDmgMsg", pz_dmg_msg);
+        registry
+    }
+}
+
+impl UserMessageRegistry {
+    // With the built-in decoders
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Decodes the user messages named `name` with `decoder`, replacing the previous one
+    pub fn register(&mut self, name: &'static str, decoder: UserMessageDecoder) {
+        self.decoders.insert(name, decoder);
+    }
+
+    // Returns `None` for messages the profile doesn't name or without a decoder
+    pub fn decode(&self, message: &SVCUserMessage, profile: &GameProfile) -> ReadResult<Option<UserMessage>> {
+        let Some(decoder) = profile.user_message_name(message.n_msg_type).and_then(|name| self.decoders.get(name)) else {
+            return Ok(None);
+        };
+        decoder(&mut BitReader::new(message.data.clone())).map(Some)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptKind {
+    Chat,
+    // TextMsg printed to the console, the notify area or the chat
+    Notice,
+    // TextMsg printed in the middle of the screen
+    Center,
+    Hint,
+}
+
+impl fmt::Display for TranscriptKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscriptKind::Chat => write!(f, "chat"),
+            TranscriptKind::Notice => write!(f, "notice"),
+            TranscriptKind::Center => write!(f, "center"),
+            TranscriptKind::Hint => write!(f, "hint"),
+        }
+    }
+}
+
+// A line of text the server showed the client
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptLine {
+    pub time: Duration,
+    pub kind: TranscriptKind,
+    // Name of the player who said it, from the roster when it knows the sender
+    pub speaker: Option<String>,
+    pub steam_id: Option<SteamId>,
+    // Without the color codes
+    pub text: String,
+}
+
+impl fmt::Display for TranscriptLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.kind)?;
+        if let Some(speaker) = &self.speaker {
+            write!(f, "{}: ", speaker)?;
+        }
+        write!(f, "{}", self.text)
+    }
+}
+
+// Chat text with the color codes and line breaks removed
+fn clean(text: &CString) -> String {
+    let text: String = text.to_string_lossy().chars().filter(|c| !c.is_control()).collect();
+    text.trim().to_string()
+}
+
+impl UserMessage {
+    // The line this message adds to the transcript, the sender is looked up in `roster`
+    pub fn transcript(&self, roster: &Roster, time: Duration) -> Option<TranscriptLine> {
+        let line = |kind, client: Option<u8>, name: Option<&CString>, text: String| {
+            // Entity index of the sender, its slot is one less
+            let player = client.filter(|client| *client > 0).and_then(|client| roster.by_slot(client as u16 - 1));
+            TranscriptLine {
+                time,
+                kind,
+                speaker: player.map(|player| clean(&player.info.name)).or(name.map(clean)),
+                steam_id: player.and_then(|player| player.steam_id()),
+                text,
+            }
+        };
+
+        Some(match self {
+            UserMessage::SayText { client, text, .. } => line(TranscriptKind::Chat, Some(*client), None, clean(text)),
+            UserMessage::SayText2 { client, format, params, .. } => {
+                if format.as_bytes().starts_with(b"#") && params.len() >= 2 {
+                    line(TranscriptKind::Chat, Some(*client), Some(&params[0]), clean(&params[1]))
+                } else {
+                    line(TranscriptKind::Chat, Some(*client), None, clean(format))
+                }
+            },
+            UserMessage::TextMsg { destination, text, params } => {
+                // %s1 to %s4 are replaced with the parameters
+                let mut text = clean(text);
+                for (i, param) in params.iter().enumerate() {
+                    text = text.replace(&format!("%s{}", i + 1), &clean(param));
+                }
+                let kind = if *destination == HUD_PRINTCENTER { TranscriptKind::Center } else { TranscriptKind::Notice };
+                line(kind, None, None, text)
+            },
+            UserMessage::HintText(text) => line(TranscriptKind::Hint, None, None, clean(text)),
+            UserMessage::KeyHintText(lines) => {
+                let lines: Vec<String> = lines.iter().map(clean).collect();
+                line(TranscriptKind::Hint, None, None, lines.join(" "))
+            },
+            _ => return None,
+        })
+    }
+}
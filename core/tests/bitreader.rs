@@ -0,0 +1,175 @@
+// Typed field readers and their writers, signed integers, floats, varints, coords and normals
+
+use src_sniffer_core::bitreader::{BitReader, ReadErrorKind};
+use src_sniffer_core::bitwriter::BitWriter;
+
+fn written(write: impl Fn(&mut BitWriter)) -> BitReader {
+    let mut writer = BitWriter::new(Vec::new());
+    // Start off a byte boundary
+    writer.write_u8(1, 3);
+    write(&mut writer);
+
+    let mut reader = BitReader::new(writer.content);
+    reader.read_u8(3).unwrap();
+    reader
+}
+
+#[test]
+fn signed_integers() {
+    // The top bit of the field is the sign
+    let mut reader = BitReader::new(vec![0b0001_1111, 0xff, 0x7f]);
+    assert_eq!(reader.read_i8(5).unwrap(), -1);
+    assert_eq!(reader.read_i8(3).unwrap(), 0);
+    assert_eq!(reader.read_i16(16).unwrap(), 0x7fff);
+
+    let mut reader = BitReader::new(vec![0xfe, 0xff, 0xff, 0xff]);
+    assert_eq!(reader.read_i32(32).unwrap(), -2);
+
+    let values = [(-1i64, 1), (-2048, 12), (2047, 12), (i32::MIN as i64, 32), (i64::MIN, 64), (i64::MAX, 64)];
+    let mut reader = written(|w| {
+        for (value, bits) in values {
+            w.write_i64(value, bits);
+        }
+    });
+    for (value, bits) in values {
+        assert_eq!(reader.read_i64(bits).unwrap(), value);
+    }
+
+    let mut reader = written(|w| {
+        w.write_i8(-128, 8);
+        w.write_i16(-300, 10);
+        w.write_i32(-70000, 20);
+    });
+    assert_eq!(reader.read_i8(8).unwrap(), -128);
+    assert_eq!(reader.read_i16(10).unwrap(), -300);
+    assert_eq!(reader.read_i32(20).unwrap(), -70000);
+}
+
+#[test]
+fn floats() {
+    // Raw IEEE 754 bits, not an integer conversion
+    let mut reader = BitReader::new(0.015f32.to_bits().to_le_bytes().to_vec());
+    assert_eq!(reader.read_f32().unwrap(), 0.015);
+
+    let values = [0., -0., 1.5, -45.25, f32::MAX, f32::MIN_POSITIVE, f32::INFINITY];
+    let mut reader = written(|w| {
+        for value in values {
+            w.write_f32(value);
+        }
+    });
+    for value in values {
+        assert_eq!(reader.read_f32().unwrap().to_bits(), value.to_bits());
+    }
+}
+
+#[test]
+fn varints() {
+    let mut reader = BitReader::new(vec![0xac, 0x02, 0x7f]);
+    assert_eq!(reader.read_var_u32().unwrap(), 300);
+    assert_eq!(reader.read_var_u32().unwrap(), 127);
+
+    // Zigzag, small negative values stay short
+    let mut reader = BitReader::new(vec![0x01, 0x02, 0x03]);
+    assert_eq!(reader.read_var_i32().unwrap(), -1);
+    assert_eq!(reader.read_var_i32().unwrap(), 1);
+    assert_eq!(reader.read_var_i32().unwrap(), -2);
+
+    let mut reader = written(|w| {
+        w.write_var_u64(u64::MAX);
+        w.write_var_i32(i32::MIN);
+        w.write_var_i32(i32::MAX);
+        w.write_var_i64(i64::MIN);
+        w.write_var_i64(-1234567890123);
+    });
+    assert_eq!(reader.read_var_u64().unwrap(), u64::MAX);
+    assert_eq!(reader.read_var_i32().unwrap(), i32::MIN);
+    assert_eq!(reader.read_var_i32().unwrap(), i32::MAX);
+    assert_eq!(reader.read_var_i64().unwrap(), i64::MIN);
+    assert_eq!(reader.read_var_i64().unwrap(), -1234567890123);
+    assert!(reader.is_empty());
+
+    // More continuation bytes than the type can hold
+    let mut reader = BitReader::new(vec![0xff; 11]);
+    assert_eq!(reader.read_var_u64().unwrap_err().kind, ReadErrorKind::InvalidValue);
+    let mut reader = BitReader::new(vec![0xff; 6]);
+    assert_eq!(reader.read_var_u32().unwrap_err().kind, ReadErrorKind::InvalidValue);
+}
+
+#[test]
+fn coords() {
+    // Integer and fraction flags, sign, integer minus one and fraction in 1/32
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(1, 1);
+    writer.write_u8(1, 1);
+    writer.write_u8(1, 1);
+    writer.write_u16(99, 14);
+    writer.write_u8(8, 5);
+    let mut reader = BitReader::new(writer.content);
+    assert_eq!(reader.read_bit_coord().unwrap(), -100.25);
+
+    let values = [0., 1., -1., 0.5, 16383.5, -16384., 123.03125];
+    let mut reader = written(|w| {
+        for value in values {
+            w.write_bit_coord(value);
+        }
+    });
+    for value in values {
+        assert_eq!(reader.read_bit_coord().unwrap(), value);
+    }
+
+    // Zero is only two bits
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_bit_coord(0.);
+    assert_eq!(writer.pos, 2);
+
+    let mut reader = written(|w| {
+        w.write_bit_vec3_coord([1.5, 0., -2048.]);
+        w.write_bit_vec3_coord([0., 0., 0.]);
+    });
+    assert_eq!(reader.read_bit_vec3_coord().unwrap(), [1.5, 0., -2048.]);
+    assert_eq!(reader.read_bit_vec3_coord().unwrap(), [0., 0., 0.]);
+    assert!(reader.bits_left() < 8);
+}
+
+#[test]
+fn normals() {
+    let mut reader = written(|w| {
+        w.write_bit_normal(1.);
+        w.write_bit_normal(-1.);
+        w.write_bit_normal(0.);
+    });
+    assert_eq!(reader.read_bit_normal().unwrap(), 1.);
+    assert_eq!(reader.read_bit_normal().unwrap(), -1.);
+    assert_eq!(reader.read_bit_normal().unwrap(), 0.);
+
+    let resolution = 1. / 2047.;
+    let mut reader = written(|w| w.write_bit_normal(0.6));
+    assert!((reader.read_bit_normal().unwrap() - 0.6).abs() <= resolution);
+
+    // z is rebuilt from x and y, only its sign is sent
+    let mut reader = written(|w| {
+        w.write_bit_vec3_normal([0.6, 0., -0.8]);
+        w.write_bit_vec3_normal([0., 0., 1.]);
+    });
+    let [x, y, z] = reader.read_bit_vec3_normal().unwrap();
+    assert!((x - 0.6).abs() <= resolution);
+    assert_eq!(y, 0.);
+    assert!((z + 0.8).abs() <= 0.001);
+    assert_eq!(reader.read_bit_vec3_normal().unwrap(), [0., 0., 1.]);
+}
+
+#[test]
+fn angles() {
+    let mut reader = BitReader::new(vec![0x40, 0x80]);
+    assert_eq!(reader.read_bit_angle(8).unwrap(), 90.);
+    assert_eq!(reader.read_bit_angle(8).unwrap(), 180.);
+
+    let mut reader = written(|w| {
+        w.write_bit_angle(45., 16);
+        w.write_bit_angle(-90., 16);
+        w.write_bit_angle(359., 8);
+    });
+    assert_eq!(reader.read_bit_angle(16).unwrap(), 45.);
+    assert_eq!(reader.read_bit_angle(16).unwrap(), 270.);
+    assert!((reader.read_bit_angle(8).unwrap() - 358.59375).abs() < 0.001);
+}
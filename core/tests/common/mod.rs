@@ -0,0 +1,38 @@
+// Helpers shared by the integration tests, each test crate only uses some of them
+#![allow(dead_code)]
+
+use std::ffi::CString;
+use std::time::Duration;
+
+use src_sniffer_core::bitwriter::BitWriter;
+use src_sniffer_core::builder::PacketBuilder;
+use src_sniffer_core::clc::NETStringCmd;
+use src_sniffer_core::message::NetMessage;
+use src_sniffer_core::netchannel::ParseState;
+use src_sniffer_core::profile::GameProfile;
+
+pub const SECOND: Duration = Duration::from_secs(1);
+
+pub fn cstring(string: &str) -> CString {
+    CString::new(string).unwrap()
+}
+
+pub fn string_cmd(command: &str) -> NetMessage {
+    NetMessage::StringCmd(NETStringCmd { command: cstring(command) })
+}
+
+// Messages written back to back, as they are in a payload
+pub fn encode(messages: &[NetMessage], profile: &'static GameProfile) -> Vec<u8> {
+    let state = ParseState { profile };
+
+    let mut writer = BitWriter::new(Vec::new());
+    for message in messages {
+        assert!(message.write(&mut writer, &state));
+    }
+    writer.content
+}
+
+// Datagram carrying messages written by hand, for the server ones that can't be written
+pub fn raw_packet(profile: &'static GameProfile, writer: &BitWriter) -> Vec<u8> {
+    PacketBuilder::new(profile).sequence(1).raw(&writer.content, writer.pos).unwrap().build()
+}
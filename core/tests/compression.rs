@@ -0,0 +1,265 @@
+// Whole-packet compression, the compressed datagram must decode like the original one
+
+mod common;
+
+use src_sniffer_core::bitreader::ReadErrorKind;
+use src_sniffer_core::builder::PacketBuilder;
+use src_sniffer_core::compression::{decompress_packet, Compression, COMPRESSED_PACKET_HEADER, MAX_UNCOMPRESSED_SIZE};
+use src_sniffer_core::connection::Connection;
+use src_sniffer_core::message::NetMessage;
+use src_sniffer_core::netchannel::{Direction, NetChannel};
+use src_sniffer_core::profile::{L4D2, PORTAL2};
+use src_sniffer_core::split::split_packet;
+use src_sniffer_core::{lzss, snappy};
+
+use common::{string_cmd, SECOND};
+
+// Greedy CLZSS::Compress, back references of 2 to 16 bytes within the last 4096
+fn lzss_compress(data: &[u8]) -> Vec<u8> {
+    let mut output = b"LZSS".to_vec();
+    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+    let mut cmd_pos = output.len();
+    let mut cmd_bits = 0;
+    output.push(0);
+
+    let mut pos = 0;
+    // The terminator is a back reference of count 1
+    while pos <= data.len() {
+        if cmd_bits == 8 {
+            cmd_pos = output.len();
+            cmd_bits = 0;
+            output.push(0);
+        }
+
+        if pos == data.len() {
+            output[cmd_pos] |= 1 << cmd_bits;
+            output.extend_from_slice(&[0, 0]);
+            break;
+        }
+
+        let (mut best_len, mut best_offset) = (0, 0);
+        for offset in 1..=pos.min(4096) {
+            let len = (0..16.min(data.len() - pos))
+                .take_while(|i| data[pos - offset + i] == data[pos + i])
+                .count();
+            if len > best_len {
+                (best_len, best_offset) = (len, offset);
+            }
+        }
+
+        if best_len >= 2 {
+            let position = best_offset - 1;
+            output[cmd_pos] |= 1 << cmd_bits;
+            output.push((position >> 4) as u8);
+            output.push((((position & 0x0f) << 4) | (best_len - 1)) as u8);
+            pos += best_len;
+        } else {
+            output.push(data[pos]);
+            pos += 1;
+        }
+        cmd_bits += 1;
+    }
+    output
+}
+
+fn snappy_length(mut length: usize, output: &mut Vec<u8>) {
+    while length >= 0x80 {
+        output.push((length as u8) | 0x80);
+        length >>= 7;
+    }
+    output.push(length as u8);
+}
+
+// Literals only, in chunks using the one and two byte length forms
+fn snappy_compress(data: &[u8]) -> Vec<u8> {
+    let mut output = b"SNAP".to_vec();
+    snappy_length(data.len(), &mut output);
+
+    for chunk in data.chunks(1000) {
+        let length = chunk.len() - 1;
+        if length < 60 {
+            output.push((length << 2) as u8);
+        } else if length < 0x100 {
+            output.push(60 << 2);
+            output.push(length as u8);
+        } else {
+            output.push(61 << 2);
+            output.extend_from_slice(&(length as u16).to_le_bytes());
+        }
+        output.extend_from_slice(chunk);
+    }
+    output
+}
+
+fn compressed_packet(data: &[u8]) -> Vec<u8> {
+    let mut packet = COMPRESSED_PACKET_HEADER.to_vec();
+    packet.extend_from_slice(data);
+    packet
+}
+
+fn commands(count: usize) -> Vec<NetMessage> {
+    (0..count)
+        .map(|i| string_cmd(&format!("echo {}", i)))
+        .collect()
+}
+
+fn datagram(messages: &[NetMessage]) -> Vec<u8> {
+    let mut builder = PacketBuilder::new(&L4D2).sequence(1);
+    for message in messages {
+        builder = builder.message(message).unwrap();
+    }
+    builder.build()
+}
+
+// The zero bits padding the last byte decode as NET_NOP, those are ignored
+fn without_nops(messages: &[NetMessage]) -> String {
+    let messages: Vec<&NetMessage> = messages.iter().filter(|message| !matches!(message, NetMessage::Nop)).collect();
+    format!("{:?}", messages)
+}
+
+#[test]
+fn lzss() {
+    let data: Vec<u8> = b"abcabcabcabcabcabc hello hello hello".repeat(20);
+    let compressed = lzss_compress(&data);
+    assert!(compressed.len() < data.len());
+    assert_eq!(lzss::uncompressed_size(&compressed), Some(data.len() as u32));
+    assert_eq!(lzss::decompress(&compressed, MAX_UNCOMPRESSED_SIZE), Some(data.clone()));
+    assert_eq!(lzss::decompress(&compressed, data.len() - 1), None);
+
+    // A forged header is refused without allocating what it announces
+    let mut forged = compressed.clone();
+    forged[4..8].copy_from_slice(&0xfffffff0u32.to_le_bytes());
+    assert_eq!(lzss::decompress(&forged, MAX_UNCOMPRESSED_SIZE), None);
+}
+
+#[test]
+fn snappy_copies() {
+    // "abcd" then copy1 of 8 bytes at offset 4, then copy2 of 6 bytes at offset 12
+    let mut data = b"SNAP".to_vec();
+    snappy_length(18, &mut data);
+    data.extend_from_slice(&[3 << 2, b'a', b'b', b'c', b'd']);
+    data.extend_from_slice(&[((8 - 4) << 2) | 1, 4]);
+    data.extend_from_slice(&[((6 - 1) << 2) | 2, 12, 0]);
+
+    assert!(snappy::is_snappy(&data));
+    assert_eq!(snappy::uncompressed_size(&data), Some(18));
+    assert_eq!(snappy::decompress(&data, MAX_UNCOMPRESSED_SIZE).unwrap(), b"abcdabcdabcdabcdab");
+    let long = vec![7u8; 3000];
+    assert_eq!(snappy::decompress(&snappy_compress(&long), MAX_UNCOMPRESSED_SIZE), Some(long));
+}
+
+#[test]
+fn snappy_corrupted() {
+    let mut data = b"SNAP".to_vec();
+    snappy_length(8, &mut data);
+    // Copy before anything was output
+    data.extend_from_slice(&[((8 - 4) << 2) | 1, 4]);
+    assert_eq!(snappy::decompress(&data, MAX_UNCOMPRESSED_SIZE), None);
+
+    // Shorter than announced
+    let mut data = b"SNAP".to_vec();
+    snappy_length(8, &mut data);
+    data.extend_from_slice(&[3 << 2, b'a', b'b', b'c', b'd']);
+    assert_eq!(snappy::decompress(&data, MAX_UNCOMPRESSED_SIZE), None);
+
+    // Truncated literal
+    let mut data = b"SNAP".to_vec();
+    snappy_length(4, &mut data);
+    data.extend_from_slice(&[3 << 2, b'a', b'b']);
+    assert_eq!(snappy::decompress(&data, MAX_UNCOMPRESSED_SIZE), None);
+
+    // Forged length, refused without allocating it
+    let mut data = b"SNAP".to_vec();
+    snappy_length(0xfffffff0, &mut data);
+    data.extend_from_slice(&[3 << 2, b'a', b'b', b'c', b'd']);
+    assert_eq!(snappy::decompress(&data, MAX_UNCOMPRESSED_SIZE), None);
+}
+
+#[test]
+fn lzss_packet() {
+    let commands = commands(50);
+    let original = datagram(&commands);
+    let packet = compressed_packet(&lzss_compress(&original));
+
+    let (decompressed, info) = decompress_packet(&packet, &L4D2).unwrap();
+    assert_eq!(decompressed, original);
+    assert_eq!(info.method, Compression::Lzss);
+    assert_eq!(info.compressed_size, packet.len() - 4);
+    assert_eq!(info.uncompressed_size, original.len());
+    assert!(info.ratio() < 1.);
+
+    let mut channel = NetChannel::with_profile(&L4D2);
+    let messages = channel.process_packet(&packet, Direction::ClientToServer).unwrap();
+    assert_eq!(without_nops(&messages), without_nops(&commands));
+    assert_eq!(channel.last_packet().wire_size, packet.len());
+    assert_eq!(channel.last_packet().compression, Some(info));
+}
+
+#[test]
+fn snappy_packet() {
+    let commands = commands(5);
+    let mut builder = PacketBuilder::new(&PORTAL2).sequence(1);
+    for message in &commands {
+        builder = builder.message(message).unwrap();
+    }
+    let original = builder.build();
+    let packet = compressed_packet(&snappy_compress(&original));
+
+    let mut channel = NetChannel::with_profile(&PORTAL2);
+    let messages = channel.process_packet(&packet, Direction::ClientToServer).unwrap();
+    assert_eq!(without_nops(&messages), without_nops(&commands));
+    assert_eq!(channel.last_packet().compression.unwrap().method, Compression::Snappy);
+
+    // Only the branches that ship Snappy use it
+    let err = decompress_packet(&packet, &L4D2).unwrap_err();
+    assert_eq!(err.kind, ReadErrorKind::InvalidValue);
+}
+
+#[test]
+fn invalid() {
+    // Announces more than a datagram can hold
+    let mut data = b"LZSS".to_vec();
+    data.extend_from_slice(&u32::MAX.to_le_bytes());
+    data.extend_from_slice(&[0xff, 0, 0]);
+    let err = decompress_packet(&compressed_packet(&data), &L4D2).unwrap_err();
+    assert_eq!(err.kind, ReadErrorKind::InvalidValue);
+
+    // Unknown compression
+    let err = decompress_packet(&compressed_packet(b"ZLIB\x10\0\0\0"), &L4D2).unwrap_err();
+    assert_eq!(err.kind, ReadErrorKind::InvalidValue);
+
+    // Compressed twice
+    let inner = compressed_packet(&lzss_compress(&datagram(&commands(1))));
+    let packet = compressed_packet(&lzss_compress(&inner));
+    let mut channel = NetChannel::with_profile(&L4D2);
+    assert!(channel.process_packet(&packet, Direction::ClientToServer).is_err());
+}
+
+#[test]
+fn split_and_compressed() {
+    let commands = commands(300);
+    let original = datagram(&commands);
+    let packet = compressed_packet(&lzss_compress(&original));
+    let pieces = split_packet(&packet, 7, 500);
+    assert!(pieces.len() > 1);
+
+    let mut connection = Connection::new(SECOND, &L4D2);
+    let mut decoded = Vec::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        decoded.extend(connection.process_packet(piece, Direction::ServerToClient, SECOND).unwrap());
+        if i + 1 < pieces.len() {
+            assert_eq!(connection.last_packet(), None);
+        }
+    }
+    assert_eq!(without_nops(&decoded), without_nops(&commands));
+
+    let metadata = connection.last_packet().unwrap();
+    assert_eq!(metadata.wire_size, packet.len());
+    assert_eq!(metadata.compression.unwrap().uncompressed_size, original.len());
+
+    let stats = connection.stats(Direction::ServerToClient);
+    assert_eq!(stats.compressed_packets, 1);
+    assert_eq!(stats.compressed_bytes, packet.len() as u64 - 4);
+    assert_eq!(stats.uncompressed_bytes, original.len() as u64);
+}
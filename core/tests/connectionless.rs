@@ -0,0 +1,241 @@
+// Out-of-band packets, the handshake and server queries
+
+mod common;
+
+use src_sniffer_core::bitreader::ReadErrorKind;
+use src_sniffer_core::connection::Connection;
+use src_sniffer_core::connectionless::*;
+use src_sniffer_core::netchannel::Direction;
+use src_sniffer_core::profile::L4D2;
+
+use common::{cstring, SECOND};
+
+// Builds an out-of-band packet the way bf_write does, little endian and NUL terminated strings
+struct Packet(Vec<u8>);
+
+impl Packet {
+    fn new(kind: u8) -> Self {
+        let mut data = CONNECTIONLESS_HEADER.to_vec();
+        data.push(kind);
+        Self(data)
+    }
+
+    fn byte(mut self, value: u8) -> Self {
+        self.0.push(value);
+        self
+    }
+
+    fn short(mut self, value: u16) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn long(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn long_long(mut self, value: u64) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn string(mut self, value: &str) -> Self {
+        self.0.extend_from_slice(value.as_bytes());
+        self.0.push(0);
+        self
+    }
+
+    fn bytes(mut self, value: &[u8]) -> Self {
+        self.0.extend_from_slice(value);
+        self
+    }
+}
+
+#[test]
+fn queries() {
+    let info = Packet::new(A2S_INFO).string("Source Engine Query");
+    assert_eq!(ConnectionlessMessage::parse(&info.0).unwrap(), ConnectionlessMessage::InfoQuery(A2SInfo {
+        payload: cstring("Source Engine Query"),
+        challenge: None,
+    }));
+
+    let info = Packet::new(A2S_INFO).string("Source Engine Query").long(0x1234);
+    let ConnectionlessMessage::InfoQuery(query) = ConnectionlessMessage::parse(&info.0).unwrap() else {
+        panic!("not an info query");
+    };
+    assert_eq!(query.challenge, Some(0x1234));
+
+    let players = Packet::new(A2S_PLAYER).long(u32::MAX);
+    assert_eq!(ConnectionlessMessage::parse(&players.0).unwrap(), ConnectionlessMessage::PlayerQuery { challenge: u32::MAX });
+
+    let rules = Packet::new(A2S_RULES).long(7);
+    assert_eq!(ConnectionlessMessage::parse(&rules.0).unwrap(), ConnectionlessMessage::RulesQuery { challenge: 7 });
+
+    let challenge = Packet::new(S2C_CHALLENGE).long(0xdeadbeef);
+    assert_eq!(ConnectionlessMessage::parse(&challenge.0).unwrap(), ConnectionlessMessage::QueryChallenge { challenge: 0xdeadbeef });
+}
+
+#[test]
+fn info_reply() {
+    let packet = Packet::new(S2A_INFO_SRC)
+        .byte(17)
+        .string("My server")
+        .string("c2m1_highway")
+        .string("left4dead2")
+        .string("Left 4 Dead 2")
+        .short(550)
+        .byte(3)
+        .byte(8)
+        .byte(0)
+        .byte(b'd')
+        .byte(b'l')
+        .byte(0)
+        .byte(1)
+        .string("2.2.2.0")
+        .byte(0x80 | 0x10 | 0x20 | 0x01)
+        .short(27015)
+        .long_long(90071992547409920)
+        .string("coop,versus")
+        .long_long(550);
+
+    let ConnectionlessMessage::InfoReply(info) = ConnectionlessMessage::parse(&packet.0).unwrap() else {
+        panic!("not an info reply");
+    };
+    assert_eq!(info.protocol, 17);
+    assert_eq!(info.map, cstring("c2m1_highway"));
+    assert_eq!(info.folder, cstring("left4dead2"));
+    assert_eq!(info.app_id, 550);
+    assert_eq!((info.players, info.max_players, info.bots), (3, 8, 0));
+    assert_eq!((info.server_type, info.environment), (b'd', b'l'));
+    assert!(!info.password && info.vac);
+    assert_eq!(info.port, Some(27015));
+    assert_eq!(info.steam_id, Some(90071992547409920));
+    assert_eq!(info.source_tv_port, None);
+    assert_eq!(info.keywords, Some(cstring("coop,versus")));
+    assert_eq!(info.game_id, Some(550));
+}
+
+#[test]
+fn player_and_rules_replies() {
+    let packet = Packet::new(S2A_PLAYER)
+        .byte(2)
+        .byte(0).string("Coach").long(-3i32 as u32).long(12.5f32.to_bits())
+        .byte(1).string("Ellis").long(40).long(300f32.to_bits());
+
+    let ConnectionlessMessage::PlayerReply(reply) = ConnectionlessMessage::parse(&packet.0).unwrap() else {
+        panic!("not a player reply");
+    };
+    assert_eq!(reply.players, vec![
+        PlayerInfo { index: 0, name: cstring("Coach"), score: -3, duration: 12.5 },
+        PlayerInfo { index: 1, name: cstring("Ellis"), score: 40, duration: 300. },
+    ]);
+
+    let packet = Packet::new(S2A_RULES).short(2).string("sv_cheats").string("0").string("mp_gamemode").string("coop");
+    let ConnectionlessMessage::RulesReply(reply) = ConnectionlessMessage::parse(&packet.0).unwrap() else {
+        panic!("not a rules reply");
+    };
+    assert_eq!(reply.rules, vec![
+        (cstring("sv_cheats"), cstring("0")),
+        (cstring("mp_gamemode"), cstring("coop")),
+    ]);
+}
+
+#[test]
+fn handshake() {
+    let get_challenge = Packet::new(A2S_GETCHALLENGE).long(0x11223344).string("0000000000");
+    assert_eq!(ConnectionlessMessage::parse(&get_challenge.0).unwrap(),
+        ConnectionlessMessage::GetChallenge(A2SGetChallenge { client_challenge: 0x11223344 }));
+
+    let challenge = Packet::new(S2C_CHALLENGE)
+        .long(S2C_MAGICVERSION)
+        .long(0x55667788)
+        .long(0x11223344)
+        .long(PROTOCOL_STEAM)
+        .short(0)
+        .long_long(90071992547409920)
+        .byte(1);
+    let ConnectionlessMessage::Challenge(challenge) = ConnectionlessMessage::parse(&challenge.0).unwrap() else {
+        panic!("not a challenge");
+    };
+    assert_eq!((challenge.challenge, challenge.client_challenge, challenge.auth_protocol), (0x55667788, 0x11223344, PROTOCOL_STEAM));
+    assert_eq!(challenge.data.len(), 11);
+
+    let ticket = [0xab; 20];
+    let connect = Packet::new(C2S_CONNECT)
+        .long(24)
+        .long(PROTOCOL_STEAM)
+        .long(0x55667788)
+        .long(0x11223344)
+        .string("Nick")
+        .string("hunter2")
+        .string("1.0.1.7")
+        .short(ticket.len() as u16)
+        .bytes(&ticket);
+    let ConnectionlessMessage::Connect(connect) = ConnectionlessMessage::parse(&connect.0).unwrap() else {
+        panic!("not a connect");
+    };
+    assert_eq!(connect.protocol, 24);
+    assert_eq!(connect.name, cstring("Nick"));
+    assert_eq!(connect.password, cstring("hunter2"));
+    assert_eq!(connect.product_version, Some(cstring("1.0.1.7")));
+    assert_eq!(connect.auth_ticket, ticket);
+    assert!(connect.extra.is_empty());
+
+    // Older branches send no product version
+    let connect = Packet::new(C2S_CONNECT)
+        .long(14).long(PROTOCOL_STEAM).long(1).long(2)
+        .string("Nick").string("")
+        .short(ticket.len() as u16).bytes(&ticket);
+    let ConnectionlessMessage::Connect(connect) = ConnectionlessMessage::parse(&connect.0).unwrap() else {
+        panic!("not a connect");
+    };
+    assert_eq!(connect.product_version, None);
+    assert_eq!(connect.auth_ticket, ticket);
+
+    let accepted = Packet::new(S2C_CONNECTION).long(0x11223344).string("0000000000");
+    assert_eq!(ConnectionlessMessage::parse(&accepted.0).unwrap(), ConnectionlessMessage::Connection { client_challenge: 0x11223344 });
+}
+
+#[test]
+fn invalid() {
+    // Handshake challenge without the magic version
+    let packet = Packet::new(S2C_CHALLENGE).long(1).long(2).long(3).long(4);
+    assert_eq!(ConnectionlessMessage::parse(&packet.0).unwrap_err().kind, ReadErrorKind::InvalidValue);
+
+    // Truncated reply
+    let packet = Packet::new(S2A_PLAYER).byte(1).byte(0).string("Coach");
+    assert_eq!(ConnectionlessMessage::parse(&packet.0).unwrap_err().kind, ReadErrorKind::EndOfBuffer);
+
+    assert_eq!(ConnectionlessMessage::parse(&CONNECTIONLESS_HEADER).unwrap_err().kind, ReadErrorKind::EndOfBuffer);
+
+    let packet = Packet::new(b'z').bytes(b"ping");
+    assert_eq!(ConnectionlessMessage::parse(&packet.0).unwrap(), ConnectionlessMessage::Unknown { kind: b'z', data: b"ping".to_vec() });
+}
+
+#[test]
+fn connection() {
+    let mut connection = Connection::new(SECOND, &L4D2);
+
+    let connect = Packet::new(C2S_CONNECT)
+        .long(2042).long(PROTOCOL_STEAM).long(1).long(2)
+        .string("Nick").string("").string("2.2.2.0")
+        .short(2).bytes(&[1, 2]);
+    let reject = Packet::new(S2C_CONNREJECT).long(2).string("Server is full.");
+
+    assert!(connection.process_packet(&connect.0, Direction::ClientToServer, SECOND).unwrap().is_empty());
+    assert!(connection.process_packet(&reject.0, Direction::ServerToClient, SECOND).unwrap().is_empty());
+
+    assert_eq!(connection.protocol_version, Some(2042));
+    assert_eq!(connection.reject_reason, Some(cstring("Server is full.")));
+    assert_eq!(connection.client_to_server.connectionless, 1);
+    assert_eq!(connection.server_to_client.connectionless, 1);
+
+    let messages = connection.take_connectionless();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[1], ConnectionlessMessage::Reject(S2CConnReject {
+        client_challenge: 2,
+        reason: cstring("Server is full."),
+    }));
+    assert!(connection.take_connectionless().is_empty());
+}
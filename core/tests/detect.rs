@@ -0,0 +1,165 @@
+// Picking the game profile of a connection from its handshake and first payloads
+
+mod common;
+
+use src_sniffer_core::builder::{PacketBuilder, StreamData};
+use src_sniffer_core::connection::{Connection, ConnectionTable, ProfileSource};
+use src_sniffer_core::connectionless::*;
+use src_sniffer_core::message::NetMessage;
+use src_sniffer_core::netchannel::Direction;
+use src_sniffer_core::profile::{GameProfile, L4D, L4D2, SOURCE_2013_SP, TF2};
+
+use common::{encode, string_cmd, SECOND};
+
+fn connect(protocol: u32) -> Vec<u8> {
+    let mut packet = CONNECTIONLESS_HEADER.to_vec();
+    packet.push(C2S_CONNECT);
+    for value in [protocol, PROTOCOL_STEAM, 1, 2] {
+        packet.extend_from_slice(&value.to_le_bytes());
+    }
+    packet.extend_from_slice(b"Nick\0\0");
+    packet
+}
+
+fn info_reply(app_id: u16, folder: &str) -> Vec<u8> {
+    let mut packet = CONNECTIONLESS_HEADER.to_vec();
+    packet.push(S2A_INFO_SRC);
+    packet.push(17);
+    packet.extend_from_slice(b"server\0map\0");
+    packet.extend_from_slice(folder.as_bytes());
+    packet.extend_from_slice(b"\0game\0");
+    packet.extend_from_slice(&app_id.to_le_bytes());
+    packet.extend_from_slice(&[0, 24, 0, b'd', b'l', 0, 1]);
+    packet.extend_from_slice(b"1.0\0");
+    packet
+}
+
+fn reliable_packet(profile: &'static GameProfile, messages: &[NetMessage]) -> Vec<u8> {
+    PacketBuilder::new(profile)
+        .single_block(0, StreamData::messages(encode(messages, profile))).unwrap()
+        .build()
+}
+
+// The zero bits padding the last byte decode as NET_NOP, those are ignored
+fn assert_messages(decoded: Vec<NetMessage>, expected: &[NetMessage]) {
+    let decoded: Vec<NetMessage> = decoded.into_iter().filter(|message| !matches!(message, NetMessage::Nop)).collect();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", expected));
+}
+
+#[test]
+fn protocol_version() {
+    let mut connection = Connection::new(SECOND, &L4D2);
+    assert_eq!(connection.profile_source(), ProfileSource::Default);
+
+    connection.process_packet(&connect(1041), Direction::ClientToServer, SECOND).unwrap();
+    assert_eq!(connection.profile(), &L4D);
+    assert_eq!(connection.profile_source(), ProfileSource::Protocol);
+    assert_eq!(connection.protocol_version, Some(1041));
+
+    // Already matching, the profile stays
+    let mut connection = Connection::new(SECOND, &L4D2);
+    connection.process_packet(&connect(2042), Direction::ClientToServer, SECOND).unwrap();
+    assert_eq!(connection.profile(), &L4D2);
+    assert_eq!(connection.profile_source(), ProfileSource::Protocol);
+
+    // Unknown versions are no hint
+    let mut connection = Connection::new(SECOND, &L4D2);
+    connection.process_packet(&connect(7), Direction::ClientToServer, SECOND).unwrap();
+    assert_eq!(connection.profile_source(), ProfileSource::Default);
+}
+
+#[test]
+fn server_query() {
+    let mut connection = Connection::new(SECOND, &L4D2);
+    connection.process_packet(&info_reply(440, "tf"), Direction::ServerToClient, SECOND).unwrap();
+    assert_eq!(connection.profile(), &TF2);
+    assert_eq!(connection.profile_source(), ProfileSource::ServerQuery);
+
+    // Weaker hints don't override it
+    connection.process_packet(&connect(1041), Direction::ClientToServer, SECOND).unwrap();
+    assert_eq!(connection.profile(), &TF2);
+
+    // Falls back to the game directory
+    let mut connection = Connection::new(SECOND, &L4D2);
+    connection.process_packet(&info_reply(0, "left4dead"), Direction::ServerToClient, SECOND).unwrap();
+    assert_eq!(connection.profile(), &L4D);
+}
+
+#[test]
+fn first_reliable_payload() {
+    let messages = [string_cmd("spec_mode"), string_cmd("jointeam 2")];
+
+    // The single block size is 17 bits wide on L4D and 18 on L4D2
+    let mut connection = Connection::new(SECOND, &L4D2);
+    let result = connection.process_packet(&reliable_packet(&L4D, &messages), Direction::ClientToServer, SECOND);
+    assert_messages(result.unwrap(), &messages);
+    assert_eq!(connection.profile(), &L4D);
+    assert_eq!(connection.profile_source(), ProfileSource::Heuristic);
+
+    // The current profile wins when it parses cleanly
+    let mut connection = Connection::new(SECOND, &L4D2);
+    connection.process_packet(&reliable_packet(&L4D2, &messages), Direction::ClientToServer, SECOND).unwrap();
+    assert_eq!(connection.profile(), &L4D2);
+    assert_eq!(connection.profile_source(), ProfileSource::Heuristic);
+
+    // The profile picked from the protocol version is tried first
+    let mut connection = Connection::new(SECOND, &L4D2);
+    connection.process_packet(&connect(24), Direction::ClientToServer, SECOND).unwrap();
+    assert_eq!(connection.profile(), &SOURCE_2013_SP);
+    let result = connection.process_packet(&reliable_packet(&TF2, &messages), Direction::ClientToServer, SECOND);
+    assert_messages(result.unwrap(), &messages);
+    assert_eq!(connection.profile(), &SOURCE_2013_SP);
+}
+
+#[test]
+fn fragmented_stream() {
+    let messages: Vec<NetMessage> = (0..100).map(|i| string_cmd(&format!("command number {}", i))).collect();
+    let stream = StreamData::messages(encode(&messages, &L4D2));
+    let mut connection = Connection::new(SECOND, &L4D2);
+
+    // The fragments before the last one are no hint, the stream is only judged once complete
+    let mut decoded = Vec::new();
+    let mut start = 0;
+    while start < stream.num_fragments() {
+        assert_eq!(connection.profile_source(), ProfileSource::Default);
+        let count = (stream.num_fragments() - start).min(7);
+        let packet = PacketBuilder::new(&L4D2).fragments(0, &stream, start, count).unwrap().build();
+        decoded.extend(connection.process_packet(&packet, Direction::ClientToServer, SECOND).unwrap());
+        start += count;
+    }
+
+    assert_messages(decoded, &messages);
+    assert_eq!(connection.profile_source(), ProfileSource::Heuristic);
+}
+
+#[test]
+fn unreliable_packets_are_no_hint() {
+    let mut connection = Connection::new(SECOND, &L4D2);
+    let packet = PacketBuilder::new(&L4D).message(&string_cmd("kill")).unwrap().build();
+    connection.process_packet(&packet, Direction::ClientToServer, SECOND).unwrap();
+    assert_eq!(connection.profile_source(), ProfileSource::Default);
+}
+
+#[test]
+fn forced() {
+    let mut connections = ConnectionTable::with_forced_profile(SECOND, &L4D2);
+    let connection = connections.get_or_insert(1, SECOND);
+    assert_eq!(connection.profile_source(), ProfileSource::Forced);
+
+    connection.process_packet(&info_reply(440, "tf"), Direction::ServerToClient, SECOND).unwrap();
+    connection.process_packet(&reliable_packet(&L4D, &[string_cmd("kill")]), Direction::ClientToServer, SECOND).unwrap_or_default();
+    assert_eq!(connection.profile(), &L4D2);
+
+    let mut connections = ConnectionTable::new(SECOND, &L4D2);
+    assert_eq!(connections.get_or_insert(1, SECOND).profile_source(), ProfileSource::Default);
+}
+
+#[test]
+fn out_of_order_timestamps() {
+    let mut connection = Connection::new(2 * SECOND, &L4D2);
+    let packet = PacketBuilder::new(&L4D2).message(&string_cmd("kill")).unwrap().build();
+    connection.process_packet(&packet, Direction::ClientToServer, 5 * SECOND).unwrap();
+    // From another interface of a merged capture, captured earlier
+    connection.process_packet(&packet, Direction::ClientToServer, SECOND).unwrap();
+    assert_eq!((connection.first_seen, connection.last_seen), (2 * SECOND, 5 * SECOND));
+}
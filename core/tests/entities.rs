@@ -0,0 +1,399 @@
+// Entity state rebuilt from send tables, class info and packet entities
+
+mod common;
+
+use std::ffi::CString;
+
+use src_sniffer_core::bitwriter::BitWriter;
+use src_sniffer_core::clc::{CLCBaselineAck, NETTick};
+use src_sniffer_core::entities::{EntityError, EntityTable};
+use src_sniffer_core::message::NetMessage;
+use src_sniffer_core::netchannel::Direction;
+use src_sniffer_core::profile::{GameProfile, PropIndexEncoding, L4D2, TF2};
+use src_sniffer_core::sendtable::*;
+use src_sniffer_core::svc::{ClassInfo, SVCClassInfo, SVCPacketEntities, SVCSendTable};
+
+use common::cstring;
+
+fn prop(prop_type: SendPropType, name: &str, flags: u32) -> SendProp {
+    SendProp {
+        prop_type,
+        name: cstring(name),
+        flags,
+        priority: DEFAULT_PRIORITY,
+        dt_name: CString::default(),
+        num_elements: 0,
+        low_value: 0.,
+        high_value: 0.,
+        bits: 0,
+    }
+}
+
+fn int(name: &str, bits: u8) -> SendProp {
+    SendProp { bits, ..prop(SendPropType::Int, name, SPROP_UNSIGNED) }
+}
+
+fn table_prop(name: &str, table: &str, flags: u32) -> SendProp {
+    SendProp { dt_name: cstring(table), ..prop(SendPropType::DataTable, name, flags) }
+}
+
+fn tables() -> Vec<SendTable> {
+    vec![
+        SendTable {
+            name: cstring("DT_Base"),
+            props: vec![
+                int("m_iHealth", 8),
+                prop(SendPropType::Float, "m_flSpeed", SPROP_NOSCALE),
+                SendProp { bits: 4, ..prop(SendPropType::Int, "m_iAmmo", SPROP_UNSIGNED | SPROP_INSIDEARRAY) },
+                SendProp { num_elements: 3, ..prop(SendPropType::Array, "m_iAmmo", 0) },
+            ],
+        },
+        SendTable {
+            name: cstring("DT_Local"),
+            props: vec![int("m_iFOV", 8)],
+        },
+        SendTable {
+            name: cstring("DT_Player"),
+            props: vec![
+                table_prop("baseclass", "DT_Base", SPROP_COLLAPSIBLE),
+                table_prop("m_Local", "DT_Local", 0),
+                SendProp { dt_name: cstring("DT_Base"), ..prop(SendPropType::Int, "m_flSpeed", SPROP_EXCLUDE) },
+                prop(SendPropType::Vector, "m_vecOrigin", SPROP_COORD | SPROP_CHANGES_OFTEN),
+                SendProp { low_value: 0., high_value: 1., bits: 8, ..prop(SendPropType::Float, "m_flCycle", 0) },
+                prop(SendPropType::String, "m_szName", 0),
+            ],
+        },
+    ]
+}
+
+// Flags as the branches with 16 of them number them
+fn ob_flags(flags: u32) -> u32 {
+    let mut res = flags & 0x3ff | (flags >> 10 & 0x1f) << 11;
+    if flags & SPROP_CHANGES_OFTEN != 0 {
+        res |= 1 << 10;
+    }
+    res
+}
+
+fn send_table(table: &SendTable, profile: &GameProfile) -> NetMessage {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_string(&table.name);
+    writer.write_u16(table.props.len() as u16, 10);
+
+    for prop in &table.props {
+        writer.write_u8(prop.prop_type as u8, 5);
+        writer.write_string(&prop.name);
+        if profile.send_prop_flags_bits == 16 {
+            writer.write_u32(ob_flags(prop.flags), 16);
+        } else {
+            writer.write_u32(prop.flags, profile.send_prop_flags_bits);
+        }
+        if profile.send_prop_priority {
+            writer.write_u8(prop.priority, 8);
+        }
+
+        if prop.prop_type == SendPropType::DataTable || prop.flags & SPROP_EXCLUDE != 0 {
+            writer.write_string(&prop.dt_name);
+        } else if prop.prop_type == SendPropType::Array {
+            writer.write_u16(prop.num_elements, 10);
+        } else {
+            writer.write_f32(prop.low_value);
+            writer.write_f32(prop.high_value);
+            writer.write_u8(prop.bits, 7);
+        }
+    }
+
+    NetMessage::SendTable(SVCSendTable { b_needs_decoder: true, n_length: writer.pos as u16, data: writer.content })
+}
+
+fn class_info() -> NetMessage {
+    NetMessage::ClassInfo(SVCClassInfo {
+        n_num_server_classes: 2,
+        b_create_on_client: false,
+        classes: vec![
+            ClassInfo { class_id: 0, class_name: cstring("CBase"), data_table_name: cstring("DT_Base") },
+            ClassInfo { class_id: 1, class_name: cstring("CPlayer"), data_table_name: cstring("DT_Player") },
+        ],
+    })
+}
+
+fn setup(profile: &GameProfile) -> EntityTable {
+    let mut table = EntityTable::new();
+    for send in tables() {
+        table.process_message(&send_table(&send, profile), Direction::ServerToClient, profile).unwrap();
+    }
+    table.process_message(&class_info(), Direction::ServerToClient, profile).unwrap();
+    table
+}
+
+fn tick(table: &mut EntityTable, tick: i32, profile: &GameProfile) {
+    let message = NetMessage::Tick(NETTick { n_tick: tick, fl_host_frame_time: 0., fl_host_frame_time_std_deviation: 0. });
+    table.process_message(&message, Direction::ServerToClient, profile).unwrap();
+}
+
+enum Value {
+    UInt(u32, usize),
+    Float(f32),
+    Coords([f32; 3]),
+    // Element count over 2 bits, then the elements over 4 bits
+    Ammo(Vec<u32>),
+}
+
+// Changed props by flattened index, in increasing order
+fn write_props(writer: &mut BitWriter, profile: &GameProfile, props: &[(usize, Value)]) {
+    let new_way = profile.prop_index_encoding == PropIndexEncoding::FieldIndexNewWay;
+    if new_way {
+        // Use the short forms
+        writer.write_u8(1, 1);
+    }
+
+    let mut last = -1i64;
+    for (index, value) in props {
+        let diff = (*index as i64 - last - 1) as u32;
+        last = *index as i64;
+
+        match profile.prop_index_encoding {
+            PropIndexEncoding::UBitVar => {
+                writer.write_u8(1, 1);
+                writer.write_ubit_var(diff);
+            },
+            _ if diff == 0 => writer.write_u8(1, 1),
+            _ => {
+                writer.write_u8(0, 1);
+                writer.write_u8(1, 1);
+                writer.write_u32(diff, 3);
+            },
+        }
+
+        match value {
+            Value::UInt(value, bits) => writer.write_u32(*value, *bits),
+            Value::Float(value) => writer.write_f32(*value),
+            Value::Coords(coords) => coords.iter().for_each(|coord| writer.write_bit_coord(*coord)),
+            Value::Ammo(ammo) => {
+                writer.write_u8(ammo.len() as u8, 2);
+                ammo.iter().for_each(|ammo| writer.write_u32(*ammo, 4));
+            },
+        }
+    }
+
+    match profile.prop_index_encoding {
+        PropIndexEncoding::UBitVar => writer.write_u8(0, 1),
+        _ => {
+            writer.write_u8(0, 1);
+            writer.write_u8(0, 1);
+            // 0xfff over 7 bits widened by 7 more
+            writer.write_u8(0x7f, 7);
+            writer.write_u8(0x7f, 7);
+        },
+    }
+}
+
+enum Update {
+    Enter(u16, u16, Vec<(usize, Value)>),
+    Delta(u16, Vec<(usize, Value)>),
+    Leave(u16),
+    Delete(u16),
+}
+
+struct Entities {
+    is_delta: bool,
+    delta_from: i32,
+    baseline: u8,
+    update_baseline: bool,
+}
+
+fn packet_entities(profile: &GameProfile, header: Entities, updates: Vec<Update>) -> NetMessage {
+    let mut writer = BitWriter::new(Vec::new());
+    let mut last = -1i32;
+    let mut header_index = |writer: &mut BitWriter, index: u16| {
+        writer.write_ubit_var((index as i32 - last - 1) as u32);
+        last = index as i32;
+    };
+
+    for update in &updates {
+        match update {
+            Update::Enter(index, class_id, props) => {
+                header_index(&mut writer, *index);
+                writer.write_u8(0b10, 2);
+                writer.write_u16(*class_id, 2);
+                writer.write_u16(*index * 10, 10);
+                write_props(&mut writer, profile, props);
+            },
+            Update::Delta(index, props) => {
+                header_index(&mut writer, *index);
+                writer.write_u8(0b00, 2);
+                write_props(&mut writer, profile, props);
+            },
+            Update::Leave(index) => {
+                header_index(&mut writer, *index);
+                writer.write_u8(0b01, 2);
+            },
+            Update::Delete(index) => {
+                header_index(&mut writer, *index);
+                writer.write_u8(0b11, 2);
+            },
+        }
+    }
+    if header.is_delta {
+        // No explicit deletions
+        writer.write_u8(0, 1);
+    }
+
+    NetMessage::PacketEntities(SVCPacketEntities {
+        n_max_entries: 2048,
+        b_is_delta: header.is_delta,
+        n_delta_from: if header.is_delta { header.delta_from } else { -1 },
+        n_baseline: header.baseline,
+        n_updated_entries: updates.len() as u16,
+        n_length: writer.pos as u32,
+        b_update_baseline: header.update_baseline,
+        data: writer.content,
+    })
+}
+
+const FULL: Entities = Entities { is_delta: false, delta_from: -1, baseline: 0, update_baseline: false };
+
+fn delta(from: i32) -> Entities {
+    Entities { is_delta: true, delta_from: from, ..FULL }
+}
+
+#[test]
+fn send_table_round_trip() {
+    for profile in [&L4D2, &TF2] {
+        for table in tables() {
+            let NetMessage::SendTable(message) = send_table(&table, profile) else { unreachable!() };
+            let mut reader = src_sniffer_core::bitreader::BitReader::new(message.data);
+            assert_eq!(SendTable::parse(&mut reader, profile).unwrap(), table);
+        }
+    }
+}
+
+#[test]
+fn flattening() {
+    let table = setup(&L4D2);
+    let names = |class_id| -> Vec<String> {
+        table.class(class_id).unwrap().props.as_ref().unwrap().iter()
+            .map(|prop| format!("{}.{}", prop.table.to_string_lossy(), prop.prop.name.to_string_lossy()))
+            .collect()
+    };
+
+    assert_eq!(names(0), ["DT_Base.m_iHealth", "DT_Base.m_flSpeed", "DT_Base.m_iAmmo"]);
+    // Non collapsible tables come first, the base class is inlined without the excluded prop,
+    // then CHANGES_OFTEN props are swapped to the front
+    assert_eq!(names(1), [
+        "DT_Player.m_vecOrigin",
+        "DT_Base.m_iHealth",
+        "DT_Base.m_iAmmo",
+        "DT_Local.m_iFOV",
+        "DT_Player.m_flCycle",
+        "DT_Player.m_szName",
+    ]);
+    let ammo = &table.class(1).unwrap().props.as_ref().unwrap()[2];
+    assert_eq!(ammo.element.as_ref().unwrap().flags, SPROP_UNSIGNED | SPROP_INSIDEARRAY);
+
+    // Missing tables leave the class undecodable
+    let mut table = EntityTable::new();
+    table.process_message(&send_table(&tables()[0], &L4D2), Direction::ServerToClient, &L4D2).unwrap();
+    table.process_message(&class_info(), Direction::ServerToClient, &L4D2).unwrap();
+    assert!(table.class(0).unwrap().props.is_some());
+    assert!(table.class(1).unwrap().props.is_none());
+}
+
+#[test]
+fn frames() {
+    for profile in [&L4D2, &TF2] {
+        let mut table = setup(profile);
+
+        tick(&mut table, 100, profile);
+        let full = packet_entities(profile, FULL, vec![
+            Update::Enter(1, 1, vec![
+                (0, Value::Coords([1.5, -2., 300.])),
+                (1, Value::UInt(100, 8)),
+                (2, Value::Ammo(vec![3, 15])),
+                (4, Value::UInt(128, 8)),
+            ]),
+            Update::Enter(5, 0, vec![(1, Value::Float(2.5))]),
+        ]);
+        table.process_message(&full, Direction::ServerToClient, profile).unwrap();
+
+        assert_eq!(table.tick(), Some(100));
+        assert_eq!(table.entities().count(), 2);
+        let player = table.get(1).unwrap();
+        assert_eq!((player.class_id, player.serial), (1, 10));
+        assert_eq!(table.prop(1, "m_vecOrigin"), Some(&PropValue::Vector([1.5, -2., 300.])));
+        assert_eq!(table.prop(1, "DT_Base.m_iHealth"), Some(&PropValue::Int(100)));
+        assert_eq!(table.prop(1, "m_iAmmo"), Some(&PropValue::Array(vec![PropValue::Int(3), PropValue::Int(15)])));
+        assert_eq!(table.prop(1, "m_flCycle"), Some(&PropValue::Float(128. / 255.)));
+        // Never sent
+        assert_eq!(table.prop(1, "m_iFOV"), Some(&PropValue::Int(0)));
+        assert_eq!(table.prop(1, "m_szName"), Some(&PropValue::String(CString::default())));
+        assert_eq!(table.prop(5, "m_flSpeed"), Some(&PropValue::Float(2.5)));
+
+        tick(&mut table, 101, profile);
+        let update = packet_entities(profile, delta(100), vec![
+            Update::Delta(1, vec![(1, Value::UInt(75, 8)), (3, Value::UInt(90, 8))]),
+            Update::Leave(5),
+            Update::Enter(7, 0, Vec::new()),
+        ]);
+        table.process_message(&update, Direction::ServerToClient, profile).unwrap();
+
+        assert_eq!(table.tick(), Some(101));
+        assert_eq!(table.prop(1, "m_iHealth"), Some(&PropValue::Int(75)));
+        assert_eq!(table.prop(1, "m_iFOV"), Some(&PropValue::Int(90)));
+        assert_eq!(table.prop(1, "m_vecOrigin"), Some(&PropValue::Vector([1.5, -2., 300.])));
+        assert!(table.get(5).is_none());
+        assert_eq!(table.get(7).unwrap().class_id, 0);
+
+        // Delta encoded against an older frame, what happened since is undone
+        tick(&mut table, 102, profile);
+        let update = packet_entities(profile, delta(100), vec![Update::Delete(1)]);
+        table.process_message(&update, Direction::ServerToClient, profile).unwrap();
+        let indices: Vec<u16> = table.entities().map(|entity| entity.index).collect();
+        assert_eq!(indices, [5]);
+
+        let update = packet_entities(profile, delta(99), Vec::new());
+        assert_eq!(table.process_message(&update, Direction::ServerToClient, profile), Err(EntityError::MissingFrame(99)));
+        let update = packet_entities(profile, delta(102), vec![Update::Delta(3, Vec::new())]);
+        assert_eq!(table.process_message(&update, Direction::ServerToClient, profile), Err(EntityError::MissingEntity(3)));
+    }
+}
+
+#[test]
+fn baselines() {
+    let profile = &L4D2;
+    let mut table = setup(profile);
+
+    // Instance baseline of the class, encoded like an update against zeroed props
+    let mut writer = BitWriter::new(Vec::new());
+    write_props(&mut writer, profile, &[(0, Value::UInt(50, 8)), (1, Value::Float(1.))]);
+    table.set_instance_baseline(0, writer.content);
+
+    tick(&mut table, 10, profile);
+    let full = packet_entities(profile, Entities { update_baseline: true, ..FULL }, vec![
+        Update::Enter(3, 0, vec![(1, Value::Float(4.))]),
+    ]);
+    table.process_message(&full, Direction::ServerToClient, profile).unwrap();
+    assert_eq!(table.prop(3, "m_iHealth"), Some(&PropValue::Int(50)));
+    assert_eq!(table.prop(3, "m_flSpeed"), Some(&PropValue::Float(4.)));
+
+    let ack = NetMessage::BaselineAck(CLCBaselineAck { n_baseline_tick: 10, n_baseline_nr: 0 });
+    table.process_message(&ack, Direction::ClientToServer, profile).unwrap();
+    assert_eq!(table.baseline_ack, Some((10, 0)));
+
+    // Entering again, delta encoded against the state saved to baseline 1
+    tick(&mut table, 11, profile);
+    let update = packet_entities(profile, Entities { baseline: 1, ..delta(10) }, vec![Update::Leave(3)]);
+    table.process_message(&update, Direction::ServerToClient, profile).unwrap();
+    tick(&mut table, 12, profile);
+    let update = packet_entities(profile, Entities { baseline: 1, ..delta(11) }, vec![Update::Enter(3, 0, Vec::new())]);
+    table.process_message(&update, Direction::ServerToClient, profile).unwrap();
+    assert_eq!(table.prop(3, "m_flSpeed"), Some(&PropValue::Float(4.)));
+
+    // Baseline 0 was never updated, the instance baseline is used
+    tick(&mut table, 13, profile);
+    let update = packet_entities(profile, delta(12), vec![Update::Leave(3)]);
+    table.process_message(&update, Direction::ServerToClient, profile).unwrap();
+    let update = packet_entities(profile, delta(13), vec![Update::Enter(3, 0, Vec::new())]);
+    table.process_message(&update, Direction::ServerToClient, profile).unwrap();
+    assert_eq!(table.prop(3, "m_flSpeed"), Some(&PropValue::Float(1.)));
+}
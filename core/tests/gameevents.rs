@@ -0,0 +1,175 @@
+// Game event descriptors from SVC_GameEventList and the events decoded against them
+
+mod common;
+
+use src_sniffer_core::bitwriter::BitWriter;
+use src_sniffer_core::builder::PacketBuilder;
+use src_sniffer_core::clc::CLCListenEvents;
+use src_sniffer_core::connection::Connection;
+use src_sniffer_core::gameevents::{GameEventKeyType, GameEventRegistry, GameEventValue};
+use src_sniffer_core::message::{MessageType, NetMessage};
+use src_sniffer_core::netchannel::Direction;
+use src_sniffer_core::profile::L4D2;
+use src_sniffer_core::svc::{SVCGameEvent, SVCGameEventList};
+
+use common::{cstring, raw_packet, SECOND};
+
+// ID, name and keys
+type Descriptor = (u16, &'static str, &'static [(&'static str, GameEventKeyType)]);
+
+const EVENTS: [Descriptor; 3] = [
+    (23, "player_death", &[
+        ("userid", GameEventKeyType::Short),
+        ("attacker", GameEventKeyType::Short),
+        ("weapon", GameEventKeyType::String),
+        ("headshot", GameEventKeyType::Bool),
+    ]),
+    (40, "round_start", &[
+        ("timelimit", GameEventKeyType::Long),
+        ("duration", GameEventKeyType::Float),
+        ("objective", GameEventKeyType::String),
+    ]),
+    (301, "infected_hurt", &[
+        ("attacker", GameEventKeyType::Short),
+        ("entityid", GameEventKeyType::Long),
+        ("hitgroup", GameEventKeyType::Byte),
+        ("amount", GameEventKeyType::Short),
+        ("xuid", GameEventKeyType::UInt64),
+    ]),
+];
+
+fn event_list() -> SVCGameEventList {
+    let mut writer = BitWriter::new(Vec::new());
+    for (id, name, keys) in EVENTS {
+        writer.write_u16(id, 9);
+        writer.write_string(&cstring(name));
+        for (key, key_type) in keys {
+            writer.write_u8(*key_type as u8, 3);
+            writer.write_string(&cstring(key));
+        }
+        writer.write_u8(0, 3);
+    }
+    SVCGameEventList { n_num_events: EVENTS.len() as u16, n_length: writer.pos as u32, data: writer.content }
+}
+
+fn player_death() -> SVCGameEvent {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(23, 9);
+    writer.write_i16(3, 16);
+    writer.write_i16(-1, 16);
+    writer.write_string(&cstring("hunting_rifle"));
+    writer.write_u8(1, 1);
+    SVCGameEvent { n_length: writer.pos as u16, data: writer.content }
+}
+
+#[test]
+fn descriptors() {
+    let mut registry = GameEventRegistry::new();
+    registry.load(&event_list()).unwrap();
+    assert_eq!(registry.len(), 3);
+
+    let descriptor = registry.by_name("infected_hurt").unwrap();
+    assert_eq!(descriptor.id, 301);
+    assert_eq!(descriptor.keys[2], (cstring("hitgroup"), GameEventKeyType::Byte));
+    assert_eq!(registry.get(40).unwrap().name, cstring("round_start"));
+    assert!(registry.get(41).is_none());
+
+    // A list cut short in the keys of its second event
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(1, 9);
+    writer.write_string(&cstring("broken"));
+    writer.write_u8(0, 3);
+    writer.write_u16(2, 9);
+    writer.write_string(&cstring("truncated"));
+    writer.write_u8(GameEventKeyType::Long as u8, 3);
+    let list = SVCGameEventList { n_num_events: 2, n_length: writer.pos as u32, data: writer.content };
+    assert!(registry.load(&list).is_err());
+    // Left as it was
+    assert_eq!(registry.len(), 3);
+}
+
+#[test]
+fn events() {
+    let mut registry = GameEventRegistry::new();
+    registry.load(&event_list()).unwrap();
+
+    let event = registry.decode(&player_death()).unwrap();
+    assert_eq!(event.name, cstring("player_death"));
+    assert_eq!(event.get("userid"), Some(&GameEventValue::Short(3)));
+    assert_eq!(event.get("attacker").and_then(|value| value.as_i64()), Some(-1));
+    assert_eq!(event.get("weapon"), Some(&GameEventValue::String(cstring("hunting_rifle"))));
+    assert_eq!(event.get("headshot"), Some(&GameEventValue::Bool(true)));
+    assert_eq!(event.get("victim"), None);
+    assert_eq!(event.to_string(), r#"player_death userid=3 attacker=-1 weapon="hunting_rifle" headshot=true"#);
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(301, 9);
+    writer.write_i16(4, 16);
+    writer.write_i32(120, 32);
+    writer.write_u8(2, 8);
+    writer.write_i16(50, 16);
+    writer.write_u64(76561197960287930, 64);
+    let event = registry.decode(&SVCGameEvent { n_length: writer.pos as u16, data: writer.content }).unwrap();
+    let values: Vec<String> = event.keys.iter().map(|(_, value)| value.to_string()).collect();
+    assert_eq!(values, ["4", "120", "2", "50", "76561197960287930"]);
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(40, 9);
+    writer.write_i32(300, 32);
+    writer.write_f32(1.5);
+    let event = SVCGameEvent { n_length: writer.pos as u16, data: writer.content };
+    // Missing its objective
+    assert!(registry.decode(&event).is_err());
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(41, 9);
+    assert!(registry.decode(&SVCGameEvent { n_length: 9, data: writer.content }).is_err());
+}
+
+#[test]
+fn listened() {
+    let mut registry = GameEventRegistry::new();
+    registry.load(&event_list()).unwrap();
+
+    let mut events = vec![0u32; 16];
+    events[0] = 1 << 23;
+    events[9] = 1 << (301 - 288);
+    // Never announced
+    events[1] = 1;
+    let names: Vec<&str> = registry.listened(&CLCListenEvents { events }).iter().map(|name| name.to_str().unwrap()).collect();
+    assert_eq!(names, ["player_death", "infected_hurt"]);
+}
+
+#[test]
+fn connection() {
+    let mut connection = Connection::new(SECOND, &L4D2);
+    connection.set_profile(&L4D2);
+
+    // Raw server messages, they can't be written
+    let list = event_list();
+    let event = player_death();
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(L4D2.message_id(MessageType::GameEventList).unwrap(), 6);
+    writer.write_u16(list.n_num_events, 9);
+    writer.write_u32(list.n_length, 20);
+    writer.write_bits(&list.data, list.n_length as usize);
+    writer.write_u8(L4D2.message_id(MessageType::GameEvent).unwrap(), 6);
+    writer.write_u16(event.n_length, 11);
+    writer.write_bits(&event.data, event.n_length as usize);
+    let packet = raw_packet(&L4D2, &writer);
+    connection.process_packet(&packet, Direction::ServerToClient, SECOND).unwrap();
+
+    assert_eq!(connection.game_event_registry().len(), 3);
+    let events = connection.take_game_events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].get("weapon"), Some(&GameEventValue::String(cstring("hunting_rifle"))));
+    assert!(connection.take_game_events().is_empty());
+
+    let mut events = vec![0u32; 16];
+    events[1] = 1 << (40 - 32);
+    let listen = NetMessage::ListenEvents(CLCListenEvents { events });
+    let packet = PacketBuilder::new(&L4D2).sequence(1).message(&listen).unwrap().build();
+    connection.process_packet(&packet, Direction::ClientToServer, SECOND).unwrap();
+    assert_eq!(connection.listened_events(), [cstring("round_start").as_c_str()]);
+    assert_eq!(connection.stats(Direction::ServerToClient).game_event_errors, 0);
+}
@@ -0,0 +1,117 @@
+// KeyValues trees in their binary and text forms
+
+mod common;
+
+use src_sniffer_core::bitreader::{BitReader, ReadErrorKind};
+use src_sniffer_core::bitwriter::BitWriter;
+use src_sniffer_core::keyvalues::*;
+
+use common::cstring;
+
+fn binary(keys: &[KeyValues], end: u8) -> Vec<u8> {
+    let mut writer = BitWriter::new(Vec::new());
+    write_binary(&mut writer, keys, end);
+    writer.content
+}
+
+fn tree() -> Vec<KeyValues> {
+    vec![KeyValues::section("settings", vec![
+        KeyValues::new("name", KeyValue::String(cstring("say \"hi\"\\n"))),
+        KeyValues::new("rate", KeyValue::Int(30000)),
+        KeyValues::new("volume", KeyValue::Float(0.5)),
+        KeyValues::new("steamid", KeyValue::UInt64(76561198000000000)),
+        KeyValues::section("nested", vec![
+            KeyValues::section("deeper", vec![KeyValues::new("x", KeyValue::Int(-1))]),
+        ]),
+    ])]
+}
+
+#[test]
+fn binary_layout() {
+    let keys = [KeyValues::section("a", vec![KeyValues::new("b", KeyValue::Int(1))])];
+    assert_eq!(binary(&keys, TYPE_END), [0, b'a', 0, 2, b'b', 0, 1, 0, 0, 0, 8, 8]);
+    assert_eq!(binary(&keys, TYPE_END_COMPILED), [0, b'a', 0, 2, b'b', 0, 1, 0, 0, 0, 11, 11]);
+}
+
+#[test]
+fn binary_round_trip() {
+    for end in [TYPE_END, TYPE_END_COMPILED] {
+        let data = binary(&tree(), end);
+        let mut reader = BitReader::new(data.clone());
+        assert_eq!(read_binary(&mut reader).unwrap(), tree());
+        assert_eq!(reader.pos, data.len() * 8);
+    }
+}
+
+#[test]
+fn wide_strings() {
+    // Sent as UTF-8 strings, as they're converted by the engine
+    let keys = [KeyValues::new("name", KeyValue::WString("ünïcode".to_string()))];
+    let mut reader = BitReader::new(binary(&keys, TYPE_END));
+    assert_eq!(read_binary(&mut reader).unwrap(), [KeyValues::new("name", KeyValue::String(cstring("ünïcode")))]);
+}
+
+#[test]
+fn binary_invalid() {
+    // Unknown type
+    let mut reader = BitReader::new(vec![9, b'a', 0, 8]);
+    assert_eq!(read_binary(&mut reader).unwrap_err().kind, ReadErrorKind::InvalidValue);
+
+    // Wide string, sent without its value
+    let mut reader = BitReader::new(vec![5, b'a', 0, 8]);
+    assert_eq!(read_binary(&mut reader).unwrap_err().kind, ReadErrorKind::InvalidValue);
+
+    // Missing end
+    let mut reader = BitReader::new(vec![2, b'a', 0, 1, 0, 0, 0]);
+    assert_eq!(read_binary(&mut reader).unwrap_err().kind, ReadErrorKind::EndOfBuffer);
+
+    // Sections nested without end
+    let mut data = Vec::new();
+    for _ in 0..1000 {
+        data.extend_from_slice(&[0, b'a', 0]);
+    }
+    let mut reader = BitReader::new(data);
+    assert_eq!(read_binary(&mut reader).unwrap_err().kind, ReadErrorKind::InvalidValue);
+}
+
+#[test]
+fn text_round_trip() {
+    let text = tree()[0].to_string();
+    assert!(text.starts_with("\"settings\"\n{\n\t\"name\"\t\t\"say \\\"hi\\\"\\\\n\"\n"));
+    assert_eq!(parse_text(&text).unwrap(), tree());
+}
+
+#[test]
+fn text_format() {
+    let text = r#"
+        // Comment
+        "root"
+        {
+            key value [$WIN32]
+            "color"    "255 0 0 255"
+            sub { "a" "1.5" }
+        }
+        second "x"
+    "#;
+
+    let keys = parse_text(text).unwrap();
+    assert_eq!(keys.len(), 2);
+    let root = &keys[0];
+    assert_eq!(root.get("KEY").unwrap().value, KeyValue::String(cstring("value")));
+    assert_eq!(root.get("color").unwrap().value, KeyValue::String(cstring("255 0 0 255")));
+    assert_eq!(root.get("sub").unwrap().get("a").unwrap().value, KeyValue::Float(1.5));
+    assert_eq!(keys[1], KeyValues::new("second", KeyValue::String(cstring("x"))));
+    assert!(KeyValues::try_new("a\0b", KeyValue::Int(1)).is_err());
+}
+
+#[test]
+fn text_invalid() {
+    assert_eq!(parse_text("\"root\" {\n\"a\" \"b\"\n").unwrap_err(), TextError { line: 3, message: "missing '}'" });
+    assert_eq!(parse_text("\"a\" \"unterminated").unwrap_err().message, "unterminated string");
+    assert_eq!(parse_text("}").unwrap_err().message, "expected a key");
+    assert_eq!(parse_text("\"key\"").unwrap_err().message, "expected a value or '{'");
+    // NULs, literal or escaped, in names and values
+    assert_eq!(parse_text("\"a\0b\" \"c\"").unwrap_err().message, "NUL in key name");
+    assert_eq!(parse_text("\"a\" \"b\0c\"").unwrap_err().message, "NUL in value");
+    assert_eq!(parse_text("\"a\" \"b\\\0c\"").unwrap_err().message, "NUL in value");
+}
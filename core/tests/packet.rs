@@ -0,0 +1,246 @@
+// Datagrams made by PacketBuilder must decode to what was put in them
+
+mod common;
+
+use src_sniffer_core::bitreader::ReadErrorKind;
+use src_sniffer_core::bitwriter::BitWriter;
+use src_sniffer_core::checksum;
+use src_sniffer_core::builder::{PacketBuilder, StreamData};
+use src_sniffer_core::clc::*;
+use src_sniffer_core::message::NetMessage;
+use src_sniffer_core::netchannel::{Direction, NetChannel, NetPacketHeader, PacketFlag};
+use src_sniffer_core::netchannel::{FRAGMENT_BITS, FRAGMENT_SIZE, MAX_FILE_SIZE_BITS, PACKET_FLAG_RELIABLE};
+use src_sniffer_core::profile::{L4D, L4D2, TF2};
+
+use common::{cstring, encode, string_cmd};
+
+fn tick(n_tick: i32) -> NetMessage {
+    NetMessage::Tick(NETTick { n_tick, ..Default::default() })
+}
+
+// Messages as carried by a reliable stream
+// The zero bits padding the last byte decode as NET_NOP, those are ignored
+fn assert_messages(mut decoded: &[NetMessage], expected: &[NetMessage]) {
+    while decoded.len() > expected.len() && matches!(decoded.last(), Some(NetMessage::Nop)) {
+        decoded = &decoded[..decoded.len() - 1];
+    }
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", expected));
+}
+
+#[test]
+fn unreliable_messages() {
+    for profile in [&L4D2, &TF2] {
+        let expected = [tick(1234), string_cmd("say hi"), NetMessage::Nop];
+        let packet = PacketBuilder::new(profile)
+            .sequence(10)
+            .sequence_ack(9)
+            .choke(3)
+            .message(&expected[0]).unwrap()
+            .message(&expected[1]).unwrap()
+            .message(&expected[2]).unwrap()
+            .build();
+
+        let mut channel = NetChannel::with_profile(profile);
+        let decoded = channel.process_packet(&packet, Direction::ClientToServer).unwrap();
+        assert_messages(&decoded, &expected);
+    }
+}
+
+#[test]
+fn header() {
+    let packet = PacketBuilder::new(&L4D2)
+        .sequence(0x01020304)
+        .sequence_ack(0x05060708)
+        .rel_state(1)
+        .choke(2)
+        .build();
+
+    assert_eq!(&packet[..4], &[4, 3, 2, 1]);
+    assert_eq!(&packet[4..8], &[8, 7, 6, 5]);
+    // Choked flag set automatically, followed by the choke count
+    assert_eq!(packet[8] & 1 << 4, 1 << 4);
+    assert_eq!(packet[11], 1);
+    assert_eq!(packet[12], 2);
+}
+
+#[test]
+fn move_commands_are_delta_encoded() {
+    let first = CUserCmd { command_number: 10, tick_count: 100, buttons: 1, ..Default::default() };
+    let second = CUserCmd { command_number: 11, tick_count: 101, buttons: 2, ..first.clone() };
+    let third = CUserCmd { command_number: 12, tick_count: 102, ..second.clone() };
+    let expected = [
+        NetMessage::Move(CLCMove { n_new_commands: 2, n_backup_commands: 0, n_length: 0, user_cmds: vec![first.clone(), second.clone()] }),
+        // The next packet repeats the last command as a backup, its chain starts over
+        NetMessage::Move(CLCMove { n_new_commands: 1, n_backup_commands: 1, n_length: 0, user_cmds: vec![second, third] }),
+    ];
+
+    let packet = PacketBuilder::new(&L4D2)
+        .message(&expected[0]).unwrap()
+        .message(&expected[1]).unwrap()
+        .build();
+
+    let mut channel = NetChannel::new();
+    let decoded = channel.process_packet(&packet, Direction::ClientToServer).unwrap();
+
+    assert_eq!(decoded.len(), 2);
+    for (decoded, expected) in decoded.iter().zip(&expected) {
+        let (NetMessage::Move(decoded), NetMessage::Move(expected)) = (decoded, expected) else {
+            panic!("expected CLC_Move, got {:?}", decoded);
+        };
+        assert_eq!(decoded.user_cmds, expected.user_cmds);
+        assert_eq!(decoded.backup_commands().len(), expected.n_backup_commands as usize);
+    }
+}
+
+#[test]
+fn single_block_stream() {
+    for profile in [&L4D, &L4D2, &TF2] {
+        let reliable = [string_cmd("spec_mode"), string_cmd("jointeam 2")];
+        let unreliable = [tick(7)];
+
+        let packet = PacketBuilder::new(profile)
+            .subchannel(5).unwrap()
+            .single_block(0, StreamData::messages(encode(&reliable, profile))).unwrap()
+            .message(&unreliable[0]).unwrap()
+            .build();
+
+        let mut channel = NetChannel::with_profile(profile);
+        let decoded = channel.process_packet(&packet, Direction::ClientToServer).unwrap();
+
+        let expected: Vec<NetMessage> = reliable.into_iter().chain(unreliable).collect();
+        assert_messages(&decoded, &expected);
+    }
+}
+
+#[test]
+fn fragmented_file() {
+    let data: Vec<u8> = (0..FRAGMENT_SIZE * 10 + 17).map(|i| i as u8).collect();
+    let stream = StreamData::file(77, cstring("user_custom/ab/abcd.dat"), data.clone());
+    assert_eq!(stream.num_fragments(), 11);
+
+    let mut channel = NetChannel::new();
+    let mut start = 0;
+    for (sequence, count) in [4, 7].into_iter().enumerate() {
+        let packet = PacketBuilder::new(&L4D2)
+            .sequence(sequence as u32)
+            .fragments(1, &stream, start, count).unwrap()
+            .build();
+        start += count;
+
+        channel.process_packet(&packet, Direction::ServerToClient).unwrap();
+        if start < stream.num_fragments() {
+            assert!(channel.take_files().is_empty());
+        }
+    }
+
+    let files = channel.take_files();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].transfer_id, 77);
+    assert_eq!(files[0].filename, "user_custom/ab/abcd.dat");
+    assert_eq!(files[0].direction, Direction::ServerToClient);
+    assert_eq!(files[0].data, data);
+}
+
+#[test]
+fn fragmented_messages() {
+    let reliable: Vec<NetMessage> = (0..100).map(|i| string_cmd(&format!("command number {}", i))).collect();
+    let stream = StreamData::messages(encode(&reliable, &L4D2));
+    assert!(stream.num_fragments() > 7);
+
+    let mut channel = NetChannel::new();
+    let mut decoded = Vec::new();
+    let mut start = 0;
+    while start < stream.num_fragments() {
+        let count = (stream.num_fragments() - start).min(7);
+        let packet = PacketBuilder::new(&L4D2)
+            .fragments(0, &stream, start, count).unwrap()
+            .build();
+        start += count;
+
+        decoded.extend(channel.process_packet(&packet, Direction::ClientToServer).unwrap());
+    }
+
+    assert_messages(&decoded, &reliable);
+}
+
+#[test]
+fn invalid_builder_input() {
+    let stream = StreamData::messages(vec![0; FRAGMENT_SIZE * 3]);
+    assert!(PacketBuilder::new(&L4D2).subchannel(8).is_none());
+    assert!(PacketBuilder::new(&L4D2).fragments(0, &stream, 2, 2).is_none());
+    assert!(PacketBuilder::new(&L4D2).fragments(0, &stream, 0, 0).is_none());
+    assert!(PacketBuilder::new(&L4D2).fragments(2, &stream, 0, 1).is_none());
+    assert!(PacketBuilder::new(&L4D2).single_block(0, StreamData::messages(vec![0; 1 << 18])).is_none());
+    assert!(PacketBuilder::new(&L4D).single_block(0, StreamData::messages(vec![0; 1 << 17])).is_none());
+    assert!(PacketBuilder::new(&L4D2).raw(&[0], 9).is_none());
+
+    // The reliable flag only comes from the streams
+    let packet = PacketBuilder::new(&L4D2).flags(PACKET_FLAG_RELIABLE).build();
+    assert_eq!(packet[8] & PACKET_FLAG_RELIABLE, 0);
+    assert!(NetChannel::new().process_packet(&packet, Direction::ClientToServer).is_ok());
+}
+
+// First fragment of stream 0 announcing `bytes`, followed by one fragment of data
+fn forged_fragment(filename: Option<&str>, bytes: u32) -> Vec<u8> {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(0, 3);
+    writer.write_u8(1, 1);
+    writer.write_u8(1, 1);
+    writer.write_u32(0, MAX_FILE_SIZE_BITS - FRAGMENT_BITS);
+    writer.write_u8(1, 3);
+    match filename {
+        Some(filename) => {
+            writer.write_u8(1, 1);
+            writer.write_u32(1, 32);
+            writer.write_string(&cstring(filename));
+        },
+        None => writer.write_u8(0, 1),
+    }
+    writer.write_u8(0, 1);
+    writer.write_u32(bytes, MAX_FILE_SIZE_BITS);
+    writer.write_bytes(&[0; FRAGMENT_SIZE]);
+
+    let header = NetPacketHeader { sequence: 1, sequence_ack: 0, flags: PacketFlag(PACKET_FLAG_RELIABLE), checksum: 0, rel_state: 0 };
+    let mut packet = header.to_bytes().to_vec();
+    packet.extend(writer.content);
+    checksum::update_checksum(&mut packet);
+    packet
+}
+
+#[test]
+fn forged_stream_size() {
+    // The largest size the header can announce, for a file and for messages
+    for filename in [Some("downloads/big.dat"), None] {
+        let packet = forged_fragment(filename, (1 << MAX_FILE_SIZE_BITS) - 1);
+        let err = NetChannel::new().process_packet(&packet, Direction::ServerToClient).unwrap_err();
+        assert_eq!(err.kind, ReadErrorKind::InvalidValue);
+    }
+
+    // Message streams fit in a payload, files may be larger
+    let packet = forged_fragment(None, 1 << 20);
+    assert!(NetChannel::new().process_packet(&packet, Direction::ServerToClient).is_err());
+    let packet = forged_fragment(Some("downloads/big.dat"), 1 << 20);
+    assert!(NetChannel::new().process_packet(&packet, Direction::ServerToClient).is_ok());
+}
+
+#[test]
+fn checksum() {
+    assert_eq!(checksum::crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(checksum::short_checksum(b"123456789"), 0xcbf4 ^ 0x3926);
+
+    let mut packet = PacketBuilder::new(&L4D2)
+        .sequence(3)
+        .message(&string_cmd("say hi")).unwrap()
+        .build();
+    assert_eq!(checksum::header_checksum(&packet), checksum::packet_checksum(&packet));
+
+    // A flipped bit in the payload is caught
+    let last = packet.len() - 1;
+    packet[last] ^= 0x40;
+    let err = NetChannel::new().process_packet(&packet, Direction::ClientToServer).unwrap_err();
+    assert_eq!(err.kind, ReadErrorKind::ChecksumMismatch);
+
+    // Rewritten packets are valid again once the checksum is updated
+    checksum::update_checksum(&mut packet);
+    assert!(NetChannel::new().process_packet(&packet, Direction::ClientToServer).is_ok());
+}
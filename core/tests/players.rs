@@ -0,0 +1,197 @@
+// Player roster from the userinfo table and CLC_ClientInfo
+
+mod common;
+
+use std::ffi::CString;
+use std::time::Duration;
+
+use src_sniffer_core::bitwriter::BitWriter;
+use src_sniffer_core::builder::PacketBuilder;
+use src_sniffer_core::clc::CLCClientInfo;
+use src_sniffer_core::connection::Connection;
+use src_sniffer_core::message::{MessageType, NetMessage};
+use src_sniffer_core::netchannel::Direction;
+use src_sniffer_core::players::{PlayerInfo, Roster, SteamId};
+use src_sniffer_core::profile::TF2;
+
+use common::{cstring, raw_packet, SECOND};
+
+fn info(name: &str, user_id: i32, friends_id: u32) -> PlayerInfo {
+    PlayerInfo {
+        xuid: 0,
+        name: cstring(name),
+        user_id,
+        guid: if friends_id == 0 { cstring("BOT") } else { cstring(&SteamId::from_account_id(friends_id).steam2()) },
+        friends_id,
+        friends_name: CString::default(),
+        fake_player: friends_id == 0,
+        is_hltv: false,
+        custom_files: [0, 0xdeadbeef, 0, 0],
+        files_downloaded: 2,
+    }
+}
+
+fn put_string(data: &mut [u8], string: &CString) {
+    data[..string.as_bytes().len()].copy_from_slice(string.as_bytes());
+}
+
+// player_info_t as the OB branches send it
+fn encode_player_info(info: &PlayerInfo) -> Vec<u8> {
+    let mut data = vec![0; 132];
+    put_string(&mut data[0..32], &info.name);
+    data[32..36].copy_from_slice(&info.user_id.to_le_bytes());
+    put_string(&mut data[36..69], &info.guid);
+    data[72..76].copy_from_slice(&info.friends_id.to_le_bytes());
+    put_string(&mut data[76..108], &info.friends_name);
+    data[108] = info.fake_player as u8;
+    data[109] = info.is_hltv as u8;
+    for (i, crc) in info.custom_files.iter().enumerate() {
+        data[112 + i * 4..116 + i * 4].copy_from_slice(&crc.to_le_bytes());
+    }
+    data[128] = info.files_downloaded;
+    data
+}
+
+#[test]
+fn steam_ids() {
+    let steam_id = SteamId::from_account_id(22202);
+    assert_eq!(steam_id.steam64(), 76561197960287930);
+    assert_eq!(steam_id.steam2(), "STEAM_0:0:11101");
+    assert_eq!(steam_id.steam3(), "[U:1:22202]");
+    assert_eq!(steam_id.to_string(), "STEAM_0:0:11101");
+
+    assert_eq!(SteamId::parse_steam2("STEAM_1:1:123"), Some(SteamId::from_account_id(247)));
+    assert_eq!(SteamId::parse_steam2("STEAM_0:0:11101"), Some(steam_id));
+    for invalid in ["BOT", "STEAM_0:2:1", "STEAM_0:1", "STEAM_0:1:2:3", "[U:1:22202]"] {
+        assert_eq!(SteamId::parse_steam2(invalid), None);
+    }
+}
+
+#[test]
+fn player_info() {
+    let player = info("Zoey", 3, 22202);
+    assert_eq!(PlayerInfo::parse(&encode_player_info(&player), false), Some(player.clone()));
+    assert_eq!(player.steam_id(), Some(SteamId::from_account_id(22202)));
+    assert!(PlayerInfo::parse(&[0; 128], false).is_none());
+
+    // Byte swapped with the XUID and longer names
+    let mut data = vec![0; 344];
+    data[8..16].copy_from_slice(&76561197960287930u64.to_be_bytes());
+    data[16..20].copy_from_slice(b"Bill");
+    data[144..148].copy_from_slice(&7i32.to_be_bytes());
+    data[148..151].copy_from_slice(b"BOT");
+    data[316] = 1;
+    data[324..328].copy_from_slice(&0x12345678u32.to_be_bytes());
+    data[336] = 1;
+
+    let bill = PlayerInfo::parse(&data, true).unwrap();
+    assert_eq!((bill.name.to_str().unwrap(), bill.user_id, bill.guid.to_str().unwrap()), ("Bill", 7, "BOT"));
+    assert_eq!(bill.custom_files, [0, 0x12345678, 0, 0]);
+    assert!(bill.fake_player);
+    assert_eq!(bill.files_downloaded, 1);
+    // Without an account ID the XUID is used
+    assert_eq!(bill.steam_id(), Some(SteamId(76561197960287930)));
+
+    // Servers that leave the account ID out still fill the guid
+    let mut player = info("Louis", 4, 1001);
+    player.friends_id = 0;
+    assert_eq!(player.steam_id(), Some(SteamId::from_account_id(1001)));
+    assert_eq!(info("Bot", 5, 0).steam_id(), None);
+}
+
+#[test]
+fn roster() {
+    let mut roster = Roster::new();
+    roster.update(0, &encode_player_info(&info("Zoey", 2, 22202)), false, SECOND);
+    roster.update(1, &encode_player_info(&info("Bill", 3, 0)), false, SECOND);
+    assert_eq!(roster.present().count(), 2);
+    assert!(roster.by_slot(1).unwrap().is_bot());
+
+    // Same user ID, a new name
+    roster.update(0, &encode_player_info(&info("Francis", 2, 22202)), false, 2 * SECOND);
+    let zoey = roster.by_user_id(2).unwrap();
+    assert_eq!(zoey.name().to_str().unwrap(), "Francis");
+    assert_eq!(zoey.name_history, [(cstring("Zoey"), 2 * SECOND)]);
+    assert_eq!(zoey.joined, SECOND);
+
+    // Freed slot, then taken by someone else
+    roster.update(1, &[], false, 3 * SECOND);
+    assert!(roster.by_slot(1).is_none());
+    roster.update(0, &encode_player_info(&info("Louis", 5, 1001)), false, 4 * SECOND);
+    assert_eq!(roster.by_slot(0).unwrap().user_id(), 5);
+
+    let players: Vec<(i32, Option<Duration>)> = roster.players().map(|player| (player.user_id(), player.left)).collect();
+    assert_eq!(players, [(2, Some(4 * SECOND)), (3, Some(3 * SECOND)), (5, None)]);
+
+    // Undecodable entries change nothing
+    roster.update(0, &[1, 2, 3], false, 5 * SECOND);
+    assert_eq!(roster.by_slot(0).unwrap().user_id(), 5);
+}
+
+// Raw SVC_CreateStringTable, servers are the only ones sending it. An entry per slot, without
+// user data for free slots.
+fn create_user_info(slots: &[Option<&PlayerInfo>]) -> Vec<u8> {
+    let mut entries = BitWriter::new(Vec::new());
+    for (slot, info) in slots.iter().enumerate() {
+        entries.write_u8(1, 1);
+        entries.write_u8(1, 1);
+        entries.write_u8(0, 1);
+        entries.write_string(&cstring(&slot.to_string()));
+        match info {
+            Some(info) => {
+                entries.write_u8(1, 1);
+                let data = encode_player_info(info);
+                entries.write_u16(data.len() as u16, 14);
+                entries.write_bytes(&data);
+            },
+            None => entries.write_u8(0, 1),
+        }
+    }
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(TF2.message_id(MessageType::CreateStringTable).unwrap(), 6);
+    writer.write_string(&cstring("userinfo"));
+    writer.write_u16(64, 16);
+    writer.write_u16(slots.len() as u16, 7);
+    writer.write_u32(entries.pos as u32, TF2.max_payload_bits + 3);
+    writer.write_u8(0, 1);
+    writer.write_u8(0, TF2.string_table_flags_bits);
+    writer.write_bits(&entries.content, entries.pos);
+
+    raw_packet(&TF2, &writer)
+}
+
+#[test]
+fn connection() {
+    let mut connection = Connection::new(SECOND, &TF2);
+    connection.set_profile(&TF2);
+
+    let client_info = NetMessage::ClientInfo(CLCClientInfo {
+        n_server_count: 1,
+        n_send_table_crc: 0,
+        b_is_hltv: false,
+        b_is_replay: false,
+        n_friends_id: 22202,
+        friends_name: cstring("Zoey"),
+        n_custom_files: [0; 4],
+    });
+    let packet = PacketBuilder::new(&TF2).sequence(1).message(&client_info).unwrap().build();
+    connection.process_packet(&packet, Direction::ClientToServer, SECOND).unwrap();
+    assert_eq!(connection.roster().local_steam_id, Some(SteamId::from_account_id(22202)));
+
+    let zoey = info("Zoey", 2, 22202);
+    let bot = info("Bill", 3, 0);
+    let packet = create_user_info(&[Some(&zoey), Some(&bot), Some(&info("Louis", 4, 1001))]);
+    connection.process_packet(&packet, Direction::ServerToClient, 2 * SECOND).unwrap();
+    assert_eq!(connection.string_tables().user_info().unwrap().len(), 3);
+    assert_eq!(connection.roster().present().count(), 3);
+    assert_eq!(connection.roster().by_user_id(2).unwrap().steam_id(), Some(SteamId::from_account_id(22202)));
+
+    // The table of the next map no longer has Louis, the others stay
+    let packet = create_user_info(&[Some(&zoey), Some(&bot), None]);
+    connection.process_packet(&packet, Direction::ServerToClient, 3 * SECOND).unwrap();
+    let players: Vec<(i32, Duration, Option<Duration>)> = connection.roster().players()
+        .map(|player| (player.user_id(), player.joined, player.left))
+        .collect();
+    assert_eq!(players, [(2, 2 * SECOND, None), (3, 2 * SECOND, None), (4, 2 * SECOND, Some(3 * SECOND))]);
+}
@@ -0,0 +1,358 @@
+// Every message written with BitWriter must parse back to itself, and writing the parsed message
+// again must give the exact same bits
+
+mod common;
+
+use std::fmt::Debug;
+
+use src_sniffer_core::bitreader::{BitReader, ReadResult};
+use src_sniffer_core::bitwriter::BitWriter;
+use src_sniffer_core::clc::*;
+use src_sniffer_core::keyvalues::{KeyValue, KeyValues};
+use src_sniffer_core::profile::{GameProfile, UserCmdLayout, L4D2, PROFILES, SOURCE_2007, TF2};
+
+use common::cstring;
+
+// Written first so the message doesn't start on a byte boundary
+const PREFIX: u8 = 0b101;
+const PREFIX_BITS: usize = 3;
+
+fn encode_prefixed<T>(message: &T, write: &impl Fn(&T, &mut BitWriter)) -> BitWriter {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(PREFIX, PREFIX_BITS);
+    write(message, &mut writer);
+    writer
+}
+
+fn round_trip<T: PartialEq + Debug>(
+    message: &T,
+    write: impl Fn(&T, &mut BitWriter),
+    parse: impl Fn(&mut BitReader) -> ReadResult<T>,
+) {
+    let writer = encode_prefixed(message, &write);
+
+    let mut reader = BitReader::new(writer.content.clone());
+    assert_eq!(reader.read_u8(PREFIX_BITS).unwrap(), PREFIX);
+    let parsed = parse(&mut reader).unwrap();
+
+    assert_eq!(&parsed, message);
+    assert_eq!(reader.pos, writer.pos, "message not fully consumed");
+
+    let rewritten = encode_prefixed(&parsed, &write);
+    assert_eq!(rewritten.pos, writer.pos);
+    assert_eq!(rewritten.content, writer.content);
+}
+
+#[test]
+fn primitives() {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(1, 1);
+    writer.write_u8(0xff, 3);
+    writer.write_u16(0x1abc, 13);
+    writer.write_u32(0xdead_beef, 32);
+    writer.write_u64(0x0123_4567_89ab_cdef, 64);
+    writer.write_var_u32(300);
+    writer.write_var_u32(u32::MAX);
+    writer.write_bits(&[0xaa, 0x05], 11);
+    writer.write_string(&cstring("hello"));
+    writer.write_u8(0, 0);
+
+    let mut reader = BitReader::new(writer.content.clone());
+    assert_eq!(reader.read_u8(1).unwrap(), 1);
+    // Bits above the requested width are dropped
+    assert_eq!(reader.read_u8(3).unwrap(), 0b111);
+    assert_eq!(reader.read_u16(13).unwrap(), 0x1abc);
+    assert_eq!(reader.read_u32(32).unwrap(), 0xdead_beef);
+    assert_eq!(reader.read_u64(64).unwrap(), 0x0123_4567_89ab_cdef);
+    assert_eq!(reader.read_var_u32().unwrap(), 300);
+    assert_eq!(reader.read_var_u32().unwrap(), u32::MAX);
+    assert_eq!(reader.read_bits(11).unwrap(), vec![0xaa, 0x05]);
+    assert_eq!(reader.read_string().unwrap(), cstring("hello"));
+    assert_eq!(reader.pos, writer.pos);
+    assert_eq!(writer.content.len(), writer.pos.div_ceil(8));
+}
+
+#[test]
+fn net_disconnect() {
+    let message = NETDisconnect { reason: cstring("Kicked by Console") };
+    round_trip(&message, NETDisconnect::write, NETDisconnect::parse);
+}
+
+#[test]
+fn net_file() {
+    let message = NETFile { transfer_id: 42, filename: cstring("user_custom/ab/abcd.dat"), requested: true };
+    round_trip(&message, NETFile::write, NETFile::parse);
+}
+
+#[test]
+fn net_tick() {
+    let message = NETTick {
+        n_tick: 123456,
+        fl_host_frame_time: 1500.0 / 100000.0,
+        fl_host_frame_time_std_deviation: 20.0 / 100000.0,
+    };
+    round_trip(&message, NETTick::write, NETTick::parse);
+}
+
+#[test]
+fn net_string_cmd() {
+    let message = NETStringCmd { command: cstring("say hello") };
+    round_trip(&message, NETStringCmd::write, NETStringCmd::parse);
+}
+
+#[test]
+fn net_set_convar() {
+    let message = NETSetConVar {
+        convars: vec![
+            ConVar { name: cstring("name"), value: cstring("player") },
+            ConVar { name: cstring("cl_interp"), value: cstring("0") },
+            ConVar { name: cstring("empty"), value: cstring("") },
+        ],
+    };
+    round_trip(&message, |m, w| assert!(m.write(w)), NETSetConVar::parse);
+
+    let empty = NETSetConVar { convars: Vec::new() };
+    round_trip(&empty, |m, w| assert!(m.write(w)), NETSetConVar::parse);
+
+    // The count is a byte, more can't be sent in one message
+    let convar = ConVar { name: cstring("a"), value: cstring("b") };
+    let full = NETSetConVar { convars: vec![convar; 255] };
+    round_trip(&full, |m, w| assert!(m.write(w)), NETSetConVar::parse);
+    let too_many = NETSetConVar { convars: vec![full.convars[0].clone(); 256] };
+    let mut writer = BitWriter::new(Vec::new());
+    assert!(!too_many.write(&mut writer));
+    assert_eq!(writer.pos, 0);
+}
+
+#[test]
+fn net_signon_state() {
+    let extended = NETSignonState {
+        n_signon_state: 6,
+        n_spawn_count: 3,
+        idk1: 2,
+        idk2_len: 5,
+        idk2_buf: vec![1, 2, 3, 4, 5],
+        idk3_len: 8,
+        idk3_buf: b"c1m1_hot".to_vec(),
+    };
+    round_trip(&extended, |m, w| m.write(w, &L4D2), |r| NETSignonState::parse(r, &L4D2));
+
+    let short = NETSignonState {
+        n_signon_state: 6,
+        n_spawn_count: 3,
+        idk1: 0,
+        idk2_len: 0,
+        idk2_buf: Vec::new(),
+        idk3_len: 0,
+        idk3_buf: Vec::new(),
+    };
+    round_trip(&short, |m, w| m.write(w, &TF2), |r| NETSignonState::parse(r, &TF2));
+}
+
+#[test]
+fn clc_client_info() {
+    for replay in [false, true] {
+        let profile: &GameProfile = if replay { &TF2 } else { &L4D2 };
+        let message = CLCClientInfo {
+            n_server_count: 7,
+            n_send_table_crc: 0x1234_5678,
+            b_is_hltv: false,
+            b_is_replay: replay,
+            n_friends_id: 76561198,
+            friends_name: cstring("player"),
+            n_custom_files: [0, 0xcafe_babe, 0, 1],
+        };
+        round_trip(&message, |m, w| m.write(w, profile), |r| CLCClientInfo::parse(r, profile));
+    }
+}
+
+fn user_cmds_bits(user_cmds: &[CUserCmd], layout: &UserCmdLayout) -> u16 {
+    let mut writer = BitWriter::new(Vec::new());
+    let mut from = CUserCmd::default();
+    for user_cmd in user_cmds {
+        user_cmd.write_delta(&mut writer, &from, layout);
+        from = user_cmd.clone();
+    }
+    writer.pos as u16
+}
+
+#[test]
+fn clc_move() {
+    let first = CUserCmd {
+        command_number: 100,
+        tick_count: 2000,
+        viewangles: QAngle { x: 10.0, y: 90.0, z: 0.0 },
+        forwardmove: 450.0,
+        buttons: 1,
+        weaponselect: 12,
+        ..Default::default()
+    };
+
+    // Consecutive command where only some fields changed
+    let next = CUserCmd {
+        command_number: 101,
+        tick_count: 2001,
+        viewangles: QAngle { x: 10.0, y: 91.0, z: 0.0 },
+        sidemove: 200.0,
+        buttons: 0,
+        impulse: 201,
+        weaponselect: 13,
+        weaponsubtype: 2,
+        mousedx: -5,
+        mousedy: 12,
+        ..first.clone()
+    };
+
+    // Nothing changed at all
+    let unchanged = CUserCmd {
+        command_number: 102,
+        tick_count: 2002,
+        ..next.clone()
+    };
+
+    // Everything sent in full
+    let full = CUserCmd {
+        command_number: 5000,
+        tick_count: 1,
+        viewangles: QAngle { x: 1.0, y: 2.0, z: 3.0 },
+        forwardmove: 4.0,
+        sidemove: 5.0,
+        upmove: 6.0,
+        buttons: -1,
+        impulse: 100,
+        weaponselect: 2047,
+        weaponsubtype: 63,
+        mousedx: i16::MIN,
+        mousedy: i16::MAX,
+        hasbeenpredicted: false,
+    };
+
+    let chains = [
+        (1, 0, vec![first.clone()]),
+        (1, 2, vec![first.clone(), next.clone(), unchanged.clone()]),
+        (2, 1, vec![next.clone(), unchanged.clone(), full.clone()]),
+        (0, 0, Vec::new()),
+    ];
+
+    for profile in PROFILES {
+        let layout = &profile.user_cmd;
+        for (n_new_commands, n_backup_commands, user_cmds) in &chains {
+            let message = CLCMove {
+                n_new_commands: *n_new_commands,
+                n_backup_commands: *n_backup_commands,
+                n_length: user_cmds_bits(user_cmds, layout),
+                user_cmds: user_cmds.clone(),
+            };
+            round_trip(&message, |m, w| m.write(w, layout), |r| CLCMove::parse(r, layout));
+        }
+    }
+}
+
+#[test]
+fn clc_move_commands() {
+    let user_cmds: Vec<CUserCmd> = (1..=5)
+        .map(|i| CUserCmd { command_number: i, tick_count: 100 + i, buttons: i, ..Default::default() })
+        .collect();
+    let message = CLCMove { n_new_commands: 2, n_backup_commands: 3, n_length: 0, user_cmds };
+
+    let layout = &L4D2.user_cmd;
+    let mut writer = BitWriter::new(Vec::new());
+    message.write(&mut writer, layout);
+    let parsed = CLCMove::parse(&mut BitReader::new(writer.content), layout).unwrap();
+
+    assert_eq!(parsed.user_cmds, message.user_cmds);
+    let numbers = |user_cmds: &[CUserCmd]| user_cmds.iter().map(|user_cmd| user_cmd.command_number).collect::<Vec<_>>();
+    assert_eq!(numbers(parsed.backup_commands()), [1, 2, 3]);
+    assert_eq!(numbers(parsed.new_commands()), [4, 5]);
+
+    // 13 change flags each, the first command also sends its tick count against the zeroed
+    // usercmd, the following ones only their buttons
+    assert_eq!(parsed.n_length as usize, 5 * (13 + 32) + 32);
+
+    // Fewer commands than announced
+    let mut writer = BitWriter::new(Vec::new());
+    CLCMove { n_new_commands: 3, ..message.clone() }.write(&mut writer, layout);
+    assert!(CLCMove::parse(&mut BitReader::new(writer.content), layout).is_err());
+}
+
+#[test]
+fn user_cmd_floats() {
+    // Angles and moves are raw float bits, fractions included
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(0, 1);
+    writer.write_u8(0, 1);
+    for value in [-12.375f32, 179.9, 0.5] {
+        writer.write_u8(1, 1);
+        writer.write_u32(value.to_bits(), 32);
+    }
+    writer.write_u8(1, 1);
+    writer.write_u32((-450f32).to_bits(), 32);
+    writer.write_u8(0, 2);
+    writer.write_u8(1, 1);
+    writer.write_i32(-1, 32);
+    writer.write_u8(0, 4);
+
+    let layout = &SOURCE_2007.user_cmd;
+    let user_cmd = CUserCmd::parse_delta(&mut BitReader::new(writer.content), &CUserCmd::default(), layout).unwrap();
+    assert_eq!(user_cmd.command_number, 1);
+    assert_eq!(user_cmd.viewangles, QAngle { x: -12.375, y: 179.9, z: 0.5 });
+    assert_eq!(user_cmd.forwardmove, -450.);
+    assert_eq!(user_cmd.buttons, -1);
+}
+
+#[test]
+fn clc_baseline_ack() {
+    let message = CLCBaselineAck { n_baseline_tick: 98765, n_baseline_nr: 1 };
+    round_trip(&message, CLCBaselineAck::write, CLCBaselineAck::parse);
+}
+
+#[test]
+fn clc_listen_events() {
+    let message = CLCListenEvents { events: (0..16).map(|i| 0x0101_0101 * i).collect() };
+    round_trip(&message, CLCListenEvents::write, CLCListenEvents::parse);
+}
+
+#[test]
+fn clc_loading_progress() {
+    let message = CLCLoadingProgress { idk: 77 };
+    round_trip(&message, CLCLoadingProgress::write, CLCLoadingProgress::parse);
+}
+
+#[test]
+fn clc_cmd_key_values() {
+    let message = CmdKeyValues {
+        values: vec![KeyValues::section("ClanTagChanged", vec![
+            KeyValues::new("string", KeyValue::String(cstring("value"))),
+            KeyValues::new("int", KeyValue::Int(-42)),
+            KeyValues::new("float", KeyValue::Float(3.25)),
+            KeyValues::new("ptr", KeyValue::Ptr(0xdead_beef)),
+            KeyValues::new("color", KeyValue::Color([1, 2, 3, 4])),
+            KeyValues::new("uint64", KeyValue::UInt64(76561198000000000)),
+            KeyValues::section("empty", Vec::new()),
+        ])],
+    };
+    for profile in [&L4D2, &TF2] {
+        round_trip(&message, |m, w| m.write(w, profile), CmdKeyValues::parse);
+    }
+
+    let empty = CmdKeyValues { values: Vec::new() };
+    round_trip(&empty, |m, w| m.write(w, &L4D2), CmdKeyValues::parse);
+}
+
+#[test]
+fn profiles_disagree_on_layout() {
+    // The same bits don't mean the same thing on every branch
+    let message = NETSignonState {
+        n_signon_state: 2,
+        n_spawn_count: 1,
+        idk1: 0,
+        idk2_len: 0,
+        idk2_buf: Vec::new(),
+        idk3_len: 0,
+        idk3_buf: Vec::new(),
+    };
+    let writer = encode_prefixed(&message, &|m: &NETSignonState, w: &mut BitWriter| m.write(w, &SOURCE_2007));
+    let mut reader = BitReader::new(writer.content);
+    reader.read_u8(PREFIX_BITS).unwrap();
+    assert!(NETSignonState::parse(&mut reader, &L4D2).is_err());
+}
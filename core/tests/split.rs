@@ -0,0 +1,143 @@
+// Split packet reassembly, on its own and through a connection
+
+mod common;
+
+use src_sniffer_core::bitreader::ReadErrorKind;
+use src_sniffer_core::builder::PacketBuilder;
+use src_sniffer_core::connection::Connection;
+use src_sniffer_core::message::NetMessage;
+use src_sniffer_core::netchannel::Direction;
+use src_sniffer_core::profile::L4D2;
+use src_sniffer_core::split::{split_packet, SplitHeader, SplitReassembler, SPLIT_HEADER_SIZE};
+
+use common::{string_cmd, SECOND};
+
+fn datagram(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7) as u8).collect()
+}
+
+#[test]
+fn header() {
+    let pieces = split_packet(&datagram(2500), -5, 1000);
+    assert_eq!(pieces.len(), 3);
+    assert_eq!(&pieces[0][..4], &[0xfe, 0xff, 0xff, 0xff]);
+
+    let (header, piece) = SplitHeader::parse(&pieces[2]).unwrap();
+    assert_eq!(header, SplitHeader { sequence: -5, packet_number: 2, packet_count: 3, split_size: 1000 });
+    assert_eq!(piece.len(), 500);
+    assert_eq!(&pieces[2][..SPLIT_HEADER_SIZE], &header.to_bytes());
+}
+
+#[test]
+fn in_order() {
+    let data = datagram(3000);
+    let pieces = split_packet(&data, 1, 1200);
+    let mut splits = SplitReassembler::default();
+
+    assert_eq!(splits.push(&pieces[0], SECOND).unwrap(), None);
+    assert_eq!(splits.push(&pieces[1], SECOND).unwrap(), None);
+    assert_eq!(splits.push(&pieces[2], SECOND).unwrap(), Some(data));
+    assert_eq!(splits.pending(), 0);
+}
+
+#[test]
+fn out_of_order_with_duplicates() {
+    let data = datagram(5000);
+    let pieces = split_packet(&data, 2, 1200);
+    let mut splits = SplitReassembler::default();
+
+    for number in [4, 1, 1, 3, 4, 0] {
+        assert_eq!(splits.push(&pieces[number], SECOND).unwrap(), None);
+    }
+    assert_eq!(splits.push(&pieces[2], SECOND).unwrap(), Some(data));
+
+    // A late duplicate starts a new datagram that never completes
+    assert_eq!(splits.push(&pieces[3], SECOND).unwrap(), None);
+    assert_eq!(splits.pending(), 1);
+}
+
+#[test]
+fn interleaved() {
+    let first = datagram(2000);
+    let second = datagram(2100);
+    let first_pieces = split_packet(&first, 10, 1100);
+    let second_pieces = split_packet(&second, 11, 1100);
+    let mut splits = SplitReassembler::default();
+
+    assert_eq!(splits.push(&first_pieces[0], SECOND).unwrap(), None);
+    assert_eq!(splits.push(&second_pieces[1], SECOND).unwrap(), None);
+    assert_eq!(splits.push(&second_pieces[0], SECOND).unwrap(), Some(second));
+    assert_eq!(splits.push(&first_pieces[1], SECOND).unwrap(), Some(first));
+}
+
+#[test]
+fn timeout() {
+    let data = datagram(2000);
+    let pieces = split_packet(&data, 3, 1000);
+    let mut splits = SplitReassembler::new(2 * SECOND);
+
+    assert_eq!(splits.push(&pieces[0], SECOND).unwrap(), None);
+    // The first piece expired, the datagram starts over
+    assert_eq!(splits.push(&pieces[1], 4 * SECOND).unwrap(), None);
+    assert_eq!(splits.pending(), 1);
+    assert_eq!(splits.push(&pieces[0], 5 * SECOND).unwrap(), Some(data));
+
+    assert_eq!(splits.push(&pieces[0], 6 * SECOND).unwrap(), None);
+    splits.evict_expired(10 * SECOND);
+    assert_eq!(splits.pending(), 0);
+}
+
+#[test]
+fn invalid() {
+    let mut splits = SplitReassembler::default();
+    let pieces = split_packet(&datagram(2000), 4, 1000);
+
+    // Truncated header
+    let err = splits.push(&pieces[0][..8], SECOND).unwrap_err();
+    assert_eq!(err.kind, ReadErrorKind::EndOfBuffer);
+
+    // Packet number past the count
+    let mut bad = pieces[0].clone();
+    bad[9] = 2;
+    assert_eq!(splits.push(&bad, SECOND).unwrap_err().kind, ReadErrorKind::InvalidValue);
+
+    // Piece larger than the split size
+    let mut bad = pieces[0].clone();
+    bad.push(0);
+    assert_eq!(splits.push(&bad, SECOND).unwrap_err().kind, ReadErrorKind::InvalidValue);
+
+    // Pieces disagreeing on the count
+    splits.push(&pieces[0], SECOND).unwrap();
+    let mut bad = pieces[1].clone();
+    bad[8] = 3;
+    assert_eq!(splits.push(&bad, SECOND).unwrap_err().kind, ReadErrorKind::InvalidValue);
+    assert_eq!(splits.pending(), 0);
+}
+
+#[test]
+fn connection() {
+    let commands: Vec<NetMessage> = (0..200)
+        .map(|i| string_cmd(&format!("echo {}", i)))
+        .collect();
+
+    let mut builder = PacketBuilder::new(&L4D2).sequence(1);
+    for command in &commands {
+        builder = builder.message(command).unwrap();
+    }
+    let packet = builder.build();
+    let pieces = split_packet(&packet, 1, 1000);
+    assert!(pieces.len() > 1);
+
+    let mut connection = Connection::new(SECOND, &L4D2);
+    let mut decoded = Vec::new();
+    for piece in pieces.iter().rev() {
+        decoded.extend(connection.process_packet(piece, Direction::ServerToClient, SECOND).unwrap());
+    }
+
+    assert_eq!(connection.server_to_client.packets, pieces.len() as u64);
+    assert_eq!(connection.server_to_client.messages, decoded.len() as u64);
+
+    // The zero bits padding the last byte decode as NET_NOP
+    let decoded: Vec<&NetMessage> = decoded.iter().filter(|message| !matches!(message, NetMessage::Nop)).collect();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", commands.iter().collect::<Vec<_>>()));
+}
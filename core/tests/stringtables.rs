@@ -0,0 +1,235 @@
+// String tables mirrored from SVC_CreateStringTable and SVC_UpdateStringTable
+
+mod common;
+
+use src_sniffer_core::bitwriter::BitWriter;
+use src_sniffer_core::message::NetMessage;
+use src_sniffer_core::profile::{GameProfile, L4D2, TF2};
+use src_sniffer_core::stringtables::{StringTableEntry, StringTableUpdate, StringTables};
+use src_sniffer_core::svc::{SVCCreateStringTable, SVCUpdateStringTable};
+
+use common::cstring;
+
+fn entry(string: &str, user_data: &[u8]) -> StringTableEntry {
+    StringTableEntry { string: cstring(string), user_data: user_data.to_vec() }
+}
+
+// Starts the entries of an update
+fn entries(profile: &GameProfile) -> BitWriter {
+    let mut writer = BitWriter::new(Vec::new());
+    if profile.string_table_dictionaries {
+        writer.write_u8(0, 1);
+    }
+    writer
+}
+
+// Index, `None` for the one after the previous entry
+fn write_index(writer: &mut BitWriter, index: Option<u32>, bits: usize) {
+    match index {
+        Some(index) => {
+            writer.write_u8(0, 1);
+            writer.write_u32(index, bits);
+        },
+        None => writer.write_u8(1, 1),
+    }
+}
+
+fn write_string(writer: &mut BitWriter, string: &str) {
+    writer.write_u8(1, 1);
+    writer.write_u8(0, 1);
+    writer.write_string(&cstring(string));
+}
+
+fn write_user_data(writer: &mut BitWriter, user_data: &[u8]) {
+    writer.write_u8(1, 1);
+    writer.write_u16(user_data.len() as u16, 14);
+    writer.write_bytes(user_data);
+}
+
+fn create(name: &str, max_entries: u16, entries: u32, flags: u8, writer: BitWriter) -> NetMessage {
+    NetMessage::CreateStringTable(SVCCreateStringTable {
+        table_name: cstring(name),
+        n_max_entries: max_entries,
+        n_num_entries: entries,
+        n_length: writer.pos as u32,
+        b_user_data_fixed_size: false,
+        n_user_data_size: 0,
+        n_user_data_size_bits: 0,
+        n_flags: flags,
+        data: writer.content,
+    })
+}
+
+fn update(table_id: u8, entries: u16, writer: BitWriter) -> NetMessage {
+    NetMessage::UpdateStringTable(SVCUpdateStringTable {
+        n_table_id: table_id,
+        n_changed_entries: entries,
+        n_length: writer.pos as u32,
+        data: writer.content,
+    })
+}
+
+// LZSS buffer made of literals only
+fn lzss_literals(data: &[u8]) -> Vec<u8> {
+    let mut output = b"LZSS".to_vec();
+    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    for chunk in data.chunks(8) {
+        // The terminator follows the last literal
+        output.push(if chunk.len() < 8 { 1 << chunk.len() } else { 0 });
+        output.extend_from_slice(chunk);
+    }
+    if data.len().is_multiple_of(8) {
+        output.push(1);
+    }
+    output.extend_from_slice(&[0, 0]);
+    output
+}
+
+#[test]
+fn create_and_update() {
+    let profile = &TF2;
+    let mut tables = StringTables::new();
+
+    let mut writer = entries(profile);
+    write_index(&mut writer, None, 10);
+    write_string(&mut writer, "maps/ctf_2fort.bsp");
+    writer.write_u8(0, 1);
+    write_index(&mut writer, None, 10);
+    write_string(&mut writer, "models/player/scout.mdl");
+    write_user_data(&mut writer, &[1, 2, 3]);
+    // "models/player/" from the previous string
+    write_index(&mut writer, None, 10);
+    writer.write_u8(1, 1);
+    writer.write_u8(1, 1);
+    writer.write_u8(1, 5);
+    writer.write_u8(14, 5);
+    writer.write_string(&cstring("heavy.mdl"));
+    writer.write_u8(0, 1);
+
+    let message = create("modelprecache", 1024, 3, 0, writer);
+    let result = tables.process_message(&message, profile).unwrap();
+    assert_eq!(result, Some(StringTableUpdate { table_id: 0, entries: vec![0, 1, 2] }));
+
+    let models = tables.model_precache().unwrap();
+    assert_eq!(models.len(), 3);
+    assert_eq!(models.get(1), Some(&entry("models/player/scout.mdl", &[1, 2, 3])));
+    assert_eq!(models.string(2), Some(cstring("models/player/heavy.mdl").as_c_str()));
+    assert_eq!(models.find("maps/ctf_2fort.bsp"), Some(0));
+
+    // New user data for an entry, its string is kept, then a new entry
+    let mut writer = entries(profile);
+    write_index(&mut writer, Some(1), 10);
+    writer.write_u8(0, 1);
+    write_user_data(&mut writer, &[9]);
+    write_index(&mut writer, Some(3), 10);
+    write_string(&mut writer, "models/player/spy.mdl");
+    writer.write_u8(0, 1);
+
+    let result = tables.process_message(&update(0, 2, writer), profile).unwrap();
+    assert_eq!(result, Some(StringTableUpdate { table_id: 0, entries: vec![1, 3] }));
+
+    let models = tables.get(0).unwrap();
+    assert_eq!(models.get(1), Some(&entry("models/player/scout.mdl", &[9])));
+    assert_eq!(models.string(3), Some(cstring("models/player/spy.mdl").as_c_str()));
+
+    // Entries out of order are appended with their string
+    let mut writer = entries(profile);
+    write_index(&mut writer, Some(10), 10);
+    write_string(&mut writer, "models/skipped.mdl");
+    writer.write_u8(0, 1);
+    let result = tables.process_message(&update(0, 1, writer), profile).unwrap();
+    assert_eq!(result.unwrap().entries, [4]);
+    let models = tables.get(0).unwrap();
+    assert_eq!(models.string(4), Some(cstring("models/skipped.mdl").as_c_str()));
+    assert_eq!(models.find("models/skipped.mdl"), Some(4));
+
+    assert!(tables.sound_precache().is_none());
+    assert!(tables.process_message(&update(1, 1, entries(profile)), profile).is_err());
+}
+
+#[test]
+fn fixed_size_user_data() {
+    let profile = &L4D2;
+    let mut tables = StringTables::new();
+
+    let mut writer = entries(profile);
+    for (string, user_data) in [("1", 0x123u16), ("2", 0xfff)] {
+        write_index(&mut writer, None, 6);
+        write_string(&mut writer, string);
+        writer.write_u8(1, 1);
+        writer.write_u16(user_data, 12);
+    }
+
+    let NetMessage::CreateStringTable(mut message) = create("instancebaseline", 64, 2, 0, writer) else { unreachable!() };
+    message.b_user_data_fixed_size = true;
+    message.n_user_data_size = 2;
+    message.n_user_data_size_bits = 12;
+    tables.process_message(&NetMessage::CreateStringTable(message), profile).unwrap();
+
+    let baselines = tables.instance_baseline().unwrap();
+    assert_eq!(baselines.get(0), Some(&entry("1", &[0x23, 0x01])));
+    assert_eq!(baselines.get(1), Some(&entry("2", &[0xff, 0x0f])));
+}
+
+#[test]
+fn compressed() {
+    let profile = &L4D2;
+    let mut tables = StringTables::new();
+
+    let mut writer = entries(profile);
+    for sound in ["player/footsteps/concrete1.wav", "player/footsteps/concrete2.wav"] {
+        write_index(&mut writer, None, 13);
+        write_string(&mut writer, sound);
+        writer.write_u8(0, 1);
+    }
+    let data = lzss_literals(&writer.content);
+    let size = writer.content.len();
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u32(size as u32, 32);
+    writer.write_u32(data.len() as u32, 32);
+    writer.write_bytes(&data);
+
+    tables.process_message(&create("soundprecache", 8192, 2, 1, writer), profile).unwrap();
+    let sounds = tables.sound_precache().unwrap();
+    assert_eq!(sounds.string(1), Some(cstring("player/footsteps/concrete2.wav").as_c_str()));
+
+    // The inner header announcing more than the message
+    let mut forged = data.clone();
+    forged[4..8].copy_from_slice(&0xfffffff0u32.to_le_bytes());
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u32(size as u32, 32);
+    writer.write_u32(forged.len() as u32, 32);
+    writer.write_bytes(&forged);
+    assert!(tables.process_message(&create("modelprecache", 1024, 2, 1, writer), profile).is_err());
+
+    // Corrupted buffers still create the table, updates refer to it by index
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u32(16, 32);
+    writer.write_u32(4, 32);
+    writer.write_bytes(b"LZSX");
+    assert!(tables.process_message(&create("downloadables", 8192, 1, 1, writer), profile).is_err());
+    assert!(tables.downloadables().unwrap().is_empty());
+    assert_eq!(tables.len(), 3);
+}
+
+#[test]
+fn dictionaries() {
+    let mut tables = StringTables::new();
+
+    // Dictionary encoded strings can't be decoded
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(1, 1);
+    assert!(tables.process_message(&create("modelprecache", 1024, 1, 0, writer), &L4D2).is_err());
+
+    // Branches without dictionaries have no such bit
+    let mut writer = BitWriter::new(Vec::new());
+    write_index(&mut writer, None, 10);
+    write_string(&mut writer, "models/error.mdl");
+    writer.write_u8(0, 1);
+    let message = create("modelprecache", 1024, 1, 0, writer);
+    assert!(tables.process_message(&message, &L4D2).is_err());
+    let mut tables = StringTables::new();
+    tables.process_message(&message, &TF2).unwrap();
+    assert_eq!(tables.model_precache().unwrap().string(0), Some(cstring("models/error.mdl").as_c_str()));
+}
@@ -0,0 +1,262 @@
+// Server messages parsed from hand built payloads, with the field widths of each branch
+
+mod common;
+
+use std::ffi::CString;
+
+use src_sniffer_core::bitreader::BitReader;
+use src_sniffer_core::bitwriter::BitWriter;
+use src_sniffer_core::message::{MessageType, NetMessage};
+use src_sniffer_core::netchannel::{Direction, NetChannel};
+use src_sniffer_core::profile::{GameProfile, L4D2, SOURCE_2007, TF2};
+use src_sniffer_core::svc::*;
+
+use common::{cstring, raw_packet};
+
+fn reader(writer: BitWriter) -> BitReader {
+    BitReader::new(writer.content)
+}
+
+// Fields shared by every branch up to the map, then the strings
+fn write_server_info(writer: &mut BitWriter, profile: &GameProfile) {
+    writer.write_u16(2042, 16);
+    writer.write_u32(3, 32);
+    writer.write_u8(0, 1);
+    writer.write_u8(1, 1);
+    writer.write_u32(0xdeadbeef, 32);
+    if profile.server_info_string_table_crc {
+        writer.write_u32(0x1234, 32);
+    }
+    writer.write_u16(280, 16);
+    if profile.server_info_map_md5 {
+        writer.write_bytes(&[0xaa; 16]);
+    } else {
+        writer.write_u32(0xcafe, 32);
+    }
+    writer.write_u8(1, 8);
+    writer.write_u8(8, 8);
+    writer.write_f32(1. / 30.);
+    writer.write_u8(b'l', 8);
+    for string in ["left4dead2", "c1m1_hotel", "sky_l4d_c1_1_hdr", "Dead Center"] {
+        writer.write_string(&cstring(string));
+    }
+}
+
+#[test]
+fn server_info() {
+    // String table CRC, map CRC and the mission
+    let mut writer = BitWriter::new(Vec::new());
+    write_server_info(&mut writer, &L4D2);
+    writer.write_string(&cstring("campaign1"));
+    writer.write_string(&cstring("coop"));
+    let info = SVCServerInfo::parse(&mut reader(writer), &L4D2).unwrap();
+    assert_eq!((info.n_protocol, info.n_server_count, info.b_is_hltv, info.b_is_dedicated), (2042, 3, false, true));
+    assert_eq!((info.n_client_crc, info.n_string_table_crc, info.n_max_classes), (0xdeadbeef, 0x1234, 280));
+    assert_eq!((info.n_map_crc, info.map_md5), (0xcafe, [0; 16]));
+    assert_eq!((info.n_player_slot, info.n_max_clients, info.c_os), (1, 8, b'l'));
+    assert_eq!(info.f_tick_interval, 1. / 30.);
+    assert_eq!(info.host_name, cstring("Dead Center"));
+    assert_eq!((info.mission_name, info.mutation_name), (cstring("campaign1"), cstring("coop")));
+    assert!(!info.b_is_replay);
+
+    // Map MD5 and the replay bit, nothing between the client CRC and the class count
+    let mut writer = BitWriter::new(Vec::new());
+    write_server_info(&mut writer, &TF2);
+    writer.write_u8(1, 1);
+    let info = SVCServerInfo::parse(&mut reader(writer), &TF2).unwrap();
+    assert_eq!((info.n_string_table_crc, info.n_max_classes), (0, 280));
+    assert_eq!((info.n_map_crc, info.map_md5), (0, [0xaa; 16]));
+    assert_eq!(info.sky_name, cstring("sky_l4d_c1_1_hdr"));
+    assert_eq!(info.mission_name, CString::default());
+    assert!(info.b_is_replay);
+
+    let mut writer = BitWriter::new(Vec::new());
+    write_server_info(&mut writer, &SOURCE_2007);
+    let info = SVCServerInfo::parse(&mut reader(writer), &SOURCE_2007).unwrap();
+    assert_eq!((info.n_map_crc, info.game_dir.as_c_str()), (0xcafe, c"left4dead2"));
+    assert!(!info.b_is_replay);
+
+    // Too short for the map MD5 and the replay bit of the 2013 layout
+    let mut writer = BitWriter::new(Vec::new());
+    write_server_info(&mut writer, &SOURCE_2007);
+    assert!(SVCServerInfo::parse(&mut reader(writer), &TF2).is_err());
+}
+
+#[test]
+fn sounds() {
+    // Reliable sounds are alone with an 8 bit length
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(1, 1);
+    writer.write_u16(16, 8);
+    writer.write_bytes(&[0x12, 0x34]);
+    let sounds = SVCSounds::parse(&mut reader(writer)).unwrap();
+    assert_eq!(sounds, SVCSounds { b_reliable_sound: true, n_num_sounds: 1, n_length: 16, data: vec![0x12, 0x34] });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(0, 1);
+    writer.write_u8(3, 8);
+    writer.write_u16(300, 16);
+    writer.write_bytes(&[0x55; 38]);
+    let sounds = SVCSounds::parse(&mut reader(writer)).unwrap();
+    assert_eq!((sounds.b_reliable_sound, sounds.n_num_sounds, sounds.n_length), (false, 3, 300));
+    assert_eq!(sounds.data.len(), 38);
+
+    // Fewer bits than announced
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(0, 1);
+    writer.write_u8(1, 8);
+    writer.write_u16(64, 16);
+    writer.write_bytes(&[0; 2]);
+    assert!(SVCSounds::parse(&mut reader(writer)).is_err());
+}
+
+#[test]
+fn bsp_decal() {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_bit_vec3_coord([128., -64.5, 0.]);
+    writer.write_u16(300, 9);
+    writer.write_u8(1, 1);
+    writer.write_u16(1500, 11);
+    writer.write_u16(42, 11);
+    writer.write_u8(1, 1);
+    let decal = SVCBSPDecal::parse(&mut reader(writer)).unwrap();
+    assert_eq!(decal, SVCBSPDecal {
+        pos: Vector { x: 128., y: -64.5, z: 0. },
+        n_decal_texture_index: 300,
+        n_entity_index: 1500,
+        n_model_index: 42,
+        b_low_priority: true,
+    });
+
+    // On the world, no entity or model
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_bit_vec3_coord([1., 2., 3.]);
+    writer.write_u16(7, 9);
+    writer.write_u8(0, 1);
+    writer.write_u8(0, 1);
+    let decal = SVCBSPDecal::parse(&mut reader(writer)).unwrap();
+    assert_eq!((decal.n_decal_texture_index, decal.n_entity_index, decal.n_model_index), (7, 0, 0));
+    assert!(!decal.b_low_priority);
+}
+
+#[test]
+fn payload_headers() {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(5, 8);
+    writer.write_u16(24, 11);
+    writer.write_bytes(b"abc");
+    let message = SVCUserMessage::parse(&mut reader(writer)).unwrap();
+    assert_eq!(message, SVCUserMessage { n_msg_type: 5, n_length: 24, data: b"abc".to_vec() });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(16, 11);
+    writer.write_bytes(&[0xff, 0x01]);
+    let event = SVCGameEvent::parse(&mut reader(writer)).unwrap();
+    assert_eq!(event, SVCGameEvent { n_length: 16, data: vec![0xff, 0x01] });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(1500, 11);
+    writer.write_u16(200, 9);
+    writer.write_u16(8, 11);
+    writer.write_u8(0x7f, 8);
+    let message = SVCEntityMessage::parse(&mut reader(writer)).unwrap();
+    assert_eq!(message, SVCEntityMessage { n_entity_index: 1500, n_class_id: 200, n_length: 8, data: vec![0x7f] });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(7, 8);
+    writer.write_u8(1, 8);
+    writer.write_u16(16, 16);
+    writer.write_bytes(&[1, 2]);
+    let voice = SVCVoiceData::parse(&mut reader(writer)).unwrap();
+    assert_eq!(voice, SVCVoiceData { n_from_client: 7, b_proximity: 1, n_length: 16, data: vec![1, 2] });
+
+    // Length in bytes rather than bits
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_i16(-1, 16);
+    writer.write_u16(3, 16);
+    writer.write_bytes(&[4, 5, 6]);
+    let menu = SVCMenu::parse(&mut reader(writer)).unwrap();
+    assert_eq!(menu, SVCMenu { n_type: -1, n_length: 3, data: vec![4, 5, 6] });
+}
+
+#[test]
+fn packet_entities() {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(2048 - 1, 11);
+    writer.write_u8(1, 1);
+    writer.write_i32(1200, 32);
+    writer.write_u8(1, 1);
+    writer.write_u16(3, 11);
+    writer.write_u32(16, 20);
+    writer.write_u8(1, 1);
+    writer.write_bytes(&[0xab, 0xcd]);
+    let entities = SVCPacketEntities::parse(&mut reader(writer)).unwrap();
+    assert_eq!(entities, SVCPacketEntities {
+        n_max_entries: 2047,
+        b_is_delta: true,
+        n_delta_from: 1200,
+        n_baseline: 1,
+        n_updated_entries: 3,
+        n_length: 16,
+        b_update_baseline: true,
+        data: vec![0xab, 0xcd],
+    });
+
+    // Full updates have no tick to delta from
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(20, 11);
+    writer.write_u8(0, 1);
+    writer.write_u8(0, 1);
+    writer.write_u16(1, 11);
+    writer.write_u32(8, 20);
+    writer.write_u8(0, 1);
+    writer.write_u8(0x42, 8);
+    let entities = SVCPacketEntities::parse(&mut reader(writer)).unwrap();
+    assert_eq!((entities.b_is_delta, entities.n_delta_from, entities.n_updated_entries), (false, -1, 1));
+    assert_eq!(entities.data, [0x42]);
+}
+
+#[test]
+fn temp_entities() {
+    // The length is as wide as the payloads of the branch
+    for profile in [&L4D2, &TF2] {
+        let mut writer = BitWriter::new(Vec::new());
+        writer.write_u8(2, 8);
+        writer.write_u32(24, profile.max_payload_bits);
+        writer.write_bytes(b"xyz");
+        let temp = SVCTempEntities::parse(&mut reader(writer), profile).unwrap();
+        assert_eq!(temp, SVCTempEntities { n_num_entries: 2, n_length: 24, data: b"xyz".to_vec() });
+    }
+}
+
+#[test]
+fn small_messages() {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(1500, 11);
+    assert_eq!(SVCSetView::parse(&mut reader(writer)).unwrap(), SVCSetView { n_entity_index: 1500 });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_i32(-7, 32);
+    writer.write_string(&cstring("sv_cheats"));
+    let query = SVCGetCvarValue::parse(&mut reader(writer)).unwrap();
+    assert_eq!(query, SVCGetCvarValue { i_cookie: -7, cvar_name: cstring("sv_cheats") });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(4000, 13);
+    assert_eq!(SVCPrefetch::parse(&mut reader(writer)).unwrap(), SVCPrefetch { n_sound_index: 4000 });
+}
+
+// svc_Print through the channel, the IDs differ between branches
+#[test]
+fn print() {
+    for profile in [&L4D2, &TF2, &SOURCE_2007] {
+        let mut writer = BitWriter::new(Vec::new());
+        writer.write_u8(profile.message_id(MessageType::Print).unwrap(), 6);
+        writer.write_string(&cstring("Welcome\n"));
+        let packet = raw_packet(profile, &writer);
+
+        let mut channel = NetChannel::with_profile(profile);
+        let messages = channel.process_packet(&packet, Direction::ServerToClient).unwrap();
+        assert_eq!(messages.first(), Some(&NetMessage::Print(SVCPrint { text: cstring("Welcome\n") })));
+    }
+}
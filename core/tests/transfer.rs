@@ -0,0 +1,52 @@
+// Files received over the reliable streams are saved under the output directory only
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use src_sniffer_core::netchannel::Direction;
+use src_sniffer_core::transfer::FileTransfer;
+
+fn transfer(filename: &str) -> FileTransfer {
+    FileTransfer {
+        transfer_id: 7,
+        filename: filename.to_string(),
+        direction: Direction::ServerToClient,
+        data: b"payload".to_vec(),
+    }
+}
+
+#[test]
+fn sanitized_path() {
+    let cases = [
+        ("maps/c1m1_hotel.bsp", "maps/c1m1_hotel.bsp"),
+        ("../../x", "x"),
+        ("..\\..\\x", "x"),
+        ("/abs/x", "abs/x"),
+        ("C:\\x", "x"),
+        ("a:stream", "transfer_7"),
+        ("materials/./a:stream/../x.vmt", "materials/x.vmt"),
+        ("", "transfer_7"),
+        ("../..", "transfer_7"),
+    ];
+    for (filename, expected) in cases {
+        assert_eq!(transfer(filename).sanitized_path(), PathBuf::from(expected), "{:?}", filename);
+    }
+}
+
+#[test]
+fn save() {
+    let directory = std::env::temp_dir().join(format!("src-sniffer-transfer-{}", std::process::id()));
+    let root = directory.join("files");
+
+    for filename in ["../../escaped", "..\\outside", "/etc/passwd", "C:\\boot.ini", "sound/../../../x.wav", ""] {
+        let path = transfer(filename).save(&root).unwrap();
+        assert!(path.starts_with(&root), "{:?} saved as {:?}", filename, path);
+        assert_eq!(fs::read(&path).unwrap(), b"payload");
+    }
+    // Nothing written next to the output directory
+    let siblings: Vec<_> = fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(siblings, ["files"]);
+    assert!(Path::new(&root).join("transfer_7").is_file());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
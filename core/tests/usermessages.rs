@@ -0,0 +1,218 @@
+// User messages decoded by name and the transcript of what they showed
+
+mod common;
+
+use std::ffi::CString;
+
+use src_sniffer_core::bitreader::BitReader;
+use src_sniffer_core::bitwriter::BitWriter;
+use src_sniffer_core::connection::Connection;
+use src_sniffer_core::message::MessageType;
+use src_sniffer_core::netchannel::Direction;
+use src_sniffer_core::players::{Roster, SteamId};
+use src_sniffer_core::profile::{GameProfile, CSS, L4D2, TF2};
+use src_sniffer_core::svc::SVCUserMessage;
+use src_sniffer_core::usermessages::{TranscriptKind, UserMessage, UserMessageRegistry};
+
+use common::{cstring, raw_packet, SECOND};
+
+fn user_message(profile: &GameProfile, name: &str, writer: BitWriter) -> SVCUserMessage {
+    let id = profile.user_messages.iter().position(|message| *message == name).unwrap();
+    SVCUserMessage { n_msg_type: id as u8, n_length: writer.pos as u16, data: writer.content }
+}
+
+fn say_text2(client: u8, format: &str, params: &[&str]) -> BitWriter {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(client, 8);
+    writer.write_u8(1, 8);
+    writer.write_string(&cstring(format));
+    for param in params {
+        writer.write_string(&cstring(param));
+    }
+    writer
+}
+
+fn decode(profile: &GameProfile, name: &str, writer: BitWriter) -> UserMessage {
+    UserMessageRegistry::new().decode(&user_message(profile, name, writer), profile).unwrap().unwrap()
+}
+
+// Roster with Zoey in slot 2, as entity 3
+fn roster() -> Roster {
+    let mut data = vec![0; 132];
+    data[..4].copy_from_slice(b"Zoey");
+    data[32..36].copy_from_slice(&7i32.to_le_bytes());
+    data[72..76].copy_from_slice(&22202u32.to_le_bytes());
+    let mut roster = Roster::new();
+    roster.update(2, &data, false, SECOND);
+    roster
+}
+
+#[test]
+fn chat() {
+    let message = decode(&TF2, "SayText2", say_text2(3, "#TF_Chat_All", &["Zoey", "\x01hello\n", "", ""]));
+    assert_eq!(message, UserMessage::SayText2 {
+        client: 3,
+        chat: true,
+        format: cstring("#TF_Chat_All"),
+        params: vec![cstring("Zoey"), cstring("\x01hello\n"), CString::default(), CString::default()],
+    });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(0, 8);
+    writer.write_string(&cstring("Console: restarting"));
+    writer.write_u8(0, 8);
+    let message = decode(&CSS, "SayText", writer);
+    assert_eq!(message, UserMessage::SayText { client: 0, text: cstring("Console: restarting"), chat: false });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(4, 8);
+    writer.write_string(&cstring("#Cstrike_TitlesTXT_Game_will_restart_in"));
+    writer.write_string(&cstring("3"));
+    writer.write_string(&cstring("SECONDS"));
+    writer.write_string(&CString::default());
+    writer.write_string(&CString::default());
+    let UserMessage::TextMsg { destination, params, .. } = decode(&CSS, "TextMsg", writer) else { panic!() };
+    assert_eq!(destination, 4);
+    assert_eq!(params.len(), 4);
+}
+
+#[test]
+fn hud() {
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_string(&cstring("#L4D_Instructor_Explain_Reload"));
+    assert_eq!(decode(&L4D2, "HintText", writer), UserMessage::HintText(cstring("#L4D_Instructor_Explain_Reload")));
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(2, 8);
+    writer.write_string(&cstring("Press E"));
+    writer.write_string(&cstring("to use"));
+    assert_eq!(decode(&CSS, "KeyHintText", writer), UserMessage::KeyHintText(vec![cstring("Press E"), cstring("to use")]));
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_string(&cstring("info"));
+    writer.write_u8(1, 8);
+    writer.write_u8(2, 8);
+    for string in ["title", "MOTD", "msg", "motd"] {
+        writer.write_string(&cstring(string));
+    }
+    assert_eq!(decode(&TF2, "VGUIMenu", writer), UserMessage::VGUIMenu {
+        name: cstring("info"),
+        show: true,
+        keys: vec![(cstring("title"), cstring("MOTD")), (cstring("msg"), cstring("motd"))],
+    });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(0, 8);
+    writer.write_f32(15.);
+    writer.write_f32(150.);
+    writer.write_f32(1.5);
+    assert_eq!(decode(&L4D2, "Shake", writer), UserMessage::Shake { command: 0, amplitude: 15., frequency: 150., duration: 1.5 });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u16(512, 16);
+    writer.write_u16(256, 16);
+    writer.write_u16(0x11, 16);
+    writer.write_bytes(&[255, 0, 0, 128]);
+    assert_eq!(decode(&L4D2, "Fade", writer), UserMessage::Fade { duration: 512, hold_time: 256, flags: 0x11, color: [255, 0, 0, 128] });
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(3, 8);
+    writer.write_i16(2, 16);
+    writer.write_i16(-40, 16);
+    assert_eq!(decode(&L4D2, "PZDmgMsg", writer), UserMessage::PZDmgMsg { kind: 3, values: vec![2, -40] });
+}
+
+#[test]
+fn registry() {
+    let mut registry = UserMessageRegistry::new();
+
+    // No decoder for it, or an ID the game doesn't register
+    let message = user_message(&TF2, "Geiger", BitWriter::new(vec![0]));
+    assert_eq!(registry.decode(&message, &TF2).unwrap(), None);
+    let message = SVCUserMessage { n_msg_type: 200, n_length: 0, data: Vec::new() };
+    assert_eq!(registry.decode(&message, &TF2).unwrap(), None);
+
+    // Cut short
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(0, 8);
+    assert!(registry.decode(&user_message(&TF2, "Fade", writer), &TF2).is_err());
+
+    // A game specific message carrying the same fields as a built-in one
+    fn radio_text(reader: &mut BitReader) -> src_sniffer_core::bitreader::ReadResult<UserMessage> {
+        let destination = reader.read_u8(8)?;
+        let client = reader.read_u8(8)?;
+        let format = reader.read_string()?;
+        let mut params = Vec::new();
+        while reader.bits_left() >= 8 {
+            params.push(reader.read_string()?);
+        }
+        Ok(UserMessage::SayText2 { client, chat: destination == 3, format, params })
+    }
+    registry.register("RadioText", radio_text);
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(3, 8);
+    writer.write_u8(3, 8);
+    writer.write_string(&cstring("#Game_radio"));
+    writer.write_string(&cstring("Zoey"));
+    writer.write_string(&cstring("#Cstrike_TitlesTXT_Go_go_go"));
+    let message = registry.decode(&user_message(&CSS, "RadioText", writer), &CSS).unwrap().unwrap();
+    let line = message.transcript(&Roster::new(), SECOND).unwrap();
+    assert_eq!(line.to_string(), "[chat] Zoey: #Cstrike_TitlesTXT_Go_go_go");
+}
+
+#[test]
+fn transcript() {
+    let roster = roster();
+
+    // The speaker comes from the roster, the name the server sent is only a fallback
+    let message = decode(&L4D2, "SayText2", say_text2(3, "#L4D_Chat_All", &["Zoey (dead)", "\x03hi all\n"]));
+    let line = message.transcript(&roster, 2 * SECOND).unwrap();
+    assert_eq!(line.time, 2 * SECOND);
+    assert_eq!(line.kind, TranscriptKind::Chat);
+    assert_eq!(line.speaker.as_deref(), Some("Zoey"));
+    assert_eq!(line.steam_id, Some(SteamId::from_account_id(22202)));
+    assert_eq!(line.to_string(), "[chat] Zoey: hi all");
+
+    let message = decode(&L4D2, "SayText2", say_text2(9, "#L4D_Chat_Team", &["Bill", "behind you"]));
+    let line = message.transcript(&roster, SECOND).unwrap();
+    assert_eq!((line.speaker.as_deref(), line.steam_id), (Some("Bill"), None));
+
+    // Plugins send the text as the format
+    let message = decode(&TF2, "SayText2", say_text2(0, "\x04[SM] \x01Map changes in 5 minutes", &[]));
+    assert_eq!(message.transcript(&roster, SECOND).unwrap().to_string(), "[chat] [SM] Map changes in 5 minutes");
+
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(4, 8);
+    writer.write_string(&cstring("Round starts in %s1 seconds"));
+    writer.write_string(&cstring("10"));
+    let line = decode(&CSS, "TextMsg", writer).transcript(&roster, SECOND).unwrap();
+    assert_eq!((line.kind, line.text.as_str()), (TranscriptKind::Center, "Round starts in 10 seconds"));
+
+    let line = UserMessage::KeyHintText(vec![cstring("Press E"), cstring("to use")]).transcript(&roster, SECOND).unwrap();
+    assert_eq!(line.to_string(), "[hint] Press E to use");
+
+    let shake = UserMessage::Shake { command: 0, amplitude: 1., frequency: 1., duration: 1. };
+    assert_eq!(shake.transcript(&roster, SECOND), None);
+}
+
+#[test]
+fn connection() {
+    let mut connection = Connection::new(SECOND, &L4D2);
+    connection.set_profile(&L4D2);
+
+    // Raw SVC_UserMessage, servers are the only ones sending it
+    let message = user_message(&L4D2, "SayText2", say_text2(1, "#L4D_Chat_All", &["Louis", "pills here"]));
+    let mut writer = BitWriter::new(Vec::new());
+    writer.write_u8(L4D2.message_id(MessageType::UserMessage).unwrap(), 6);
+    writer.write_u8(message.n_msg_type, 8);
+    writer.write_u16(message.n_length, 11);
+    writer.write_bits(&message.data, message.n_length as usize);
+    let packet = raw_packet(&L4D2, &writer);
+    connection.process_packet(&packet, Direction::ServerToClient, 3 * SECOND).unwrap();
+
+    let transcript = connection.take_transcript();
+    assert_eq!(transcript.len(), 1);
+    assert_eq!((transcript[0].time, transcript[0].to_string().as_str()), (3 * SECOND, "[chat] Louis: pills here"));
+    assert!(connection.take_transcript().is_empty());
+}
@@ -0,0 +1,411 @@
+use std::io::{Error, ErrorKind, Result};
+use std::time::Duration;
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b23c4d;
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d0d0a;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 0x00000001;
+const PCAPNG_PACKET: u32 = 0x00000002;
+const PCAPNG_SIMPLE_PACKET: u32 = 0x00000003;
+const PCAPNG_ENHANCED_PACKET: u32 = 0x00000006;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+
+// Option code holding the timestamp resolution of an interface
+const PCAPNG_IF_TSRESOL: u16 = 9;
+
+// A captured link-layer frame
+#[derive(Debug)]
+pub struct Frame {
+    // Time since the UNIX epoch
+    pub timestamp: Duration,
+    pub link_type: u32,
+    pub data: Vec<u8>,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+// Bounds-checked little or big endian reads over the capture file
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            big_endian: false
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.data.len() - self.pos {
+            return Err(invalid("truncated capture file"));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?.try_into().unwrap();
+        Ok(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?.try_into().unwrap();
+        Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+}
+
+// Reads every frame of a pcap or pcapng capture
+pub fn read_capture(data: &[u8]) -> Result<Vec<Frame>> {
+    if data.len() < 4 {
+        return Err(invalid("capture file is too short"));
+    }
+
+    let magic = u32::from_le_bytes(data[..4].try_into().unwrap());
+    if magic == PCAPNG_SECTION_HEADER {
+        read_pcapng(data)
+    } else {
+        read_pcap(data)
+    }
+}
+
+fn read_pcap(data: &[u8]) -> Result<Vec<Frame>> {
+    let mut cursor = Cursor::new(data);
+
+    let magic = cursor.u32()?;
+    let nanos = if magic == PCAP_MAGIC_MICROS || magic.swap_bytes() == PCAP_MAGIC_MICROS {
+        false
+    } else if magic == PCAP_MAGIC_NANOS || magic.swap_bytes() == PCAP_MAGIC_NANOS {
+        true
+    } else {
+        return Err(invalid("not a pcap or pcapng file"));
+    };
+    cursor.big_endian = magic != PCAP_MAGIC_MICROS && magic != PCAP_MAGIC_NANOS;
+
+    // Version, timezone, timestamp accuracy and snapshot length
+    let _ = cursor.bytes(16)?;
+    let link_type = cursor.u32()?;
+
+    let mut frames = Vec::new();
+    while !cursor.is_empty() {
+        let ts_sec = cursor.u32()? as u64;
+        let ts_frac = cursor.u32()?;
+        let captured_len = cursor.u32()? as usize;
+        let _original_len = cursor.u32()?;
+        let data = cursor.bytes(captured_len)?;
+
+        let frac = if nanos {
+            Duration::from_nanos(ts_frac as u64)
+        } else {
+            Duration::from_micros(ts_frac as u64)
+        };
+
+        frames.push(Frame {
+            timestamp: Duration::from_secs(ts_sec) + frac,
+            link_type,
+            data: data.to_vec(),
+        });
+    }
+
+    Ok(frames)
+}
+
+#[derive(Debug)]
+struct Interface {
+    link_type: u32,
+    // Number of timestamp units per second
+    units_per_sec: u64,
+}
+
+impl Interface {
+    fn timestamp(&self, high: u32, low: u32) -> Duration {
+        let units = ((high as u64) << 32) | low as u64;
+        let secs = units / self.units_per_sec;
+        let frac = units % self.units_per_sec;
+        Duration::from_secs(secs) + Duration::from_nanos((frac as u128 * 1_000_000_000 / self.units_per_sec as u128) as u64)
+    }
+}
+
+fn read_pcapng(data: &[u8]) -> Result<Vec<Frame>> {
+    let mut cursor = Cursor::new(data);
+    let mut interfaces: Vec<Interface> = Vec::new();
+    let mut frames = Vec::new();
+
+    while !cursor.is_empty() {
+        let block_start = cursor.pos;
+        let block_type = cursor.u32()?;
+
+        // The section header decides the byte order of everything up to the next one
+        if block_type == PCAPNG_SECTION_HEADER {
+            let _ = cursor.bytes(4)?;
+            let byte_order = cursor.bytes(4)?;
+            cursor.big_endian = match u32::from_le_bytes(byte_order.try_into().unwrap()) {
+                PCAPNG_BYTE_ORDER_MAGIC => false,
+                magic if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+                _ => return Err(invalid("invalid pcapng byte-order magic")),
+            };
+            cursor.pos = block_start + 4;
+            interfaces.clear();
+        }
+
+        let block_len = cursor.u32()? as usize;
+        if block_len < 12 || !block_len.is_multiple_of(4) {
+            return Err(invalid("invalid pcapng block length"));
+        }
+        let body = cursor.bytes(block_len - 12)?;
+        let _ = cursor.u32()?;
+
+        let mut body = Cursor {
+            data: body,
+            pos: 0,
+            big_endian: cursor.big_endian,
+        };
+
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION => {
+                let link_type = body.u16()? as u32;
+                let _reserved = body.u16()?;
+                let _snap_len = body.u32()?;
+
+                let mut units_per_sec = 1_000_000;
+                while body.data.len() - body.pos >= 4 {
+                    let code = body.u16()?;
+                    let len = body.u16()? as usize;
+                    let value = body.bytes(len)?;
+                    let _ = body.bytes((4 - len % 4) % 4)?;
+
+                    if code == 0 {
+                        break;
+                    }
+                    if code == PCAPNG_IF_TSRESOL && len == 1 {
+                        let resolution = value[0];
+                        units_per_sec = if resolution & 0x80 != 0 {
+                            1u64.checked_shl((resolution & 0x7f) as u32)
+                        } else {
+                            10u64.checked_pow(resolution as u32)
+                        }.ok_or_else(|| invalid("invalid pcapng timestamp resolution"))?;
+                    }
+                }
+
+                interfaces.push(Interface {
+                    link_type,
+                    units_per_sec,
+                });
+            },
+            PCAPNG_ENHANCED_PACKET | PCAPNG_PACKET => {
+                let interface_id = if block_type == PCAPNG_PACKET {
+                    let id = body.u16()? as usize;
+                    let _drops = body.u16()?;
+                    id
+                } else {
+                    body.u32()? as usize
+                };
+                let ts_high = body.u32()?;
+                let ts_low = body.u32()?;
+                let captured_len = body.u32()? as usize;
+                let _original_len = body.u32()?;
+                let data = body.bytes(captured_len)?;
+
+                let interface = interfaces.get(interface_id)
+                    .ok_or_else(|| invalid("packet references an unknown interface"))?;
+
+                frames.push(Frame {
+                    timestamp: interface.timestamp(ts_high, ts_low),
+                    link_type: interface.link_type,
+                    data: data.to_vec(),
+                });
+            },
+            PCAPNG_SIMPLE_PACKET => {
+                let original_len = body.u32()? as usize;
+                let data = &body.data[body.pos..];
+                let data = &data[..original_len.min(data.len())];
+
+                let interface = interfaces.first()
+                    .ok_or_else(|| invalid("packet references an unknown interface"))?;
+
+                // Simple packets carry no timestamp
+                frames.push(Frame {
+                    timestamp: Duration::ZERO,
+                    link_type: interface.link_type,
+                    data: data.to_vec(),
+                });
+            },
+            _ => {}
+        }
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_bytes(value: u16, big_endian: bool) -> [u8; 2] {
+        if big_endian { value.to_be_bytes() } else { value.to_le_bytes() }
+    }
+
+    fn u32_bytes(value: u32, big_endian: bool) -> [u8; 4] {
+        if big_endian { value.to_be_bytes() } else { value.to_le_bytes() }
+    }
+
+    fn pcap(magic: u32, big_endian: bool, records: &[(u32, u32, &[u8])]) -> Vec<u8> {
+        let mut data = u32_bytes(magic, big_endian).to_vec();
+        data.extend_from_slice(&u16_bytes(2, big_endian));
+        data.extend_from_slice(&u16_bytes(4, big_endian));
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&u32_bytes(65535, big_endian));
+        data.extend_from_slice(&u32_bytes(1, big_endian));
+        for (secs, frac, frame) in records {
+            data.extend_from_slice(&u32_bytes(*secs, big_endian));
+            data.extend_from_slice(&u32_bytes(*frac, big_endian));
+            data.extend_from_slice(&u32_bytes(frame.len() as u32, big_endian));
+            data.extend_from_slice(&u32_bytes(frame.len() as u32, big_endian));
+            data.extend_from_slice(frame);
+        }
+        data
+    }
+
+    // Block with its body padded to 4 bytes and its length on both ends
+    fn block(block_type: u32, body: &[u8], big_endian: bool) -> Vec<u8> {
+        let mut body = body.to_vec();
+        body.resize(body.len().next_multiple_of(4), 0);
+        let len = u32_bytes(12 + body.len() as u32, big_endian);
+
+        let mut data = u32_bytes(block_type, big_endian).to_vec();
+        data.extend_from_slice(&len);
+        data.extend_from_slice(&body);
+        data.extend_from_slice(&len);
+        data
+    }
+
+    fn section_header(big_endian: bool) -> Vec<u8> {
+        let mut body = u32_bytes(PCAPNG_BYTE_ORDER_MAGIC, big_endian).to_vec();
+        body.extend_from_slice(&u16_bytes(1, big_endian));
+        body.extend_from_slice(&u16_bytes(0, big_endian));
+        body.extend_from_slice(&[0xff; 8]);
+        block(PCAPNG_SECTION_HEADER, &body, big_endian)
+    }
+
+    fn interface(link_type: u16, tsresol: Option<u8>, big_endian: bool) -> Vec<u8> {
+        let mut body = u16_bytes(link_type, big_endian).to_vec();
+        body.extend_from_slice(&[0; 2]);
+        body.extend_from_slice(&u32_bytes(65535, big_endian));
+        if let Some(tsresol) = tsresol {
+            body.extend_from_slice(&u16_bytes(PCAPNG_IF_TSRESOL, big_endian));
+            body.extend_from_slice(&u16_bytes(1, big_endian));
+            body.extend_from_slice(&[tsresol, 0, 0, 0]);
+            body.extend_from_slice(&[0; 4]);
+        }
+        block(PCAPNG_INTERFACE_DESCRIPTION, &body, big_endian)
+    }
+
+    fn enhanced_packet(interface: u32, units: u64, frame: &[u8], big_endian: bool) -> Vec<u8> {
+        let mut body = u32_bytes(interface, big_endian).to_vec();
+        body.extend_from_slice(&u32_bytes((units >> 32) as u32, big_endian));
+        body.extend_from_slice(&u32_bytes(units as u32, big_endian));
+        body.extend_from_slice(&u32_bytes(frame.len() as u32, big_endian));
+        body.extend_from_slice(&u32_bytes(frame.len() as u32, big_endian));
+        body.extend_from_slice(frame);
+        block(PCAPNG_ENHANCED_PACKET, &body, big_endian)
+    }
+
+    fn timestamps(frames: &[Frame]) -> Vec<Duration> {
+        frames.iter().map(|frame| frame.timestamp).collect()
+    }
+
+    #[test]
+    fn pcap_byte_orders_and_resolutions() {
+        let records: [(u32, u32, &[u8]); 2] = [(10, 500_000, b"abc"), (11, 1, b"")];
+        for big_endian in [false, true] {
+            let frames = read_capture(&pcap(PCAP_MAGIC_MICROS, big_endian, &records)).unwrap();
+            assert_eq!(timestamps(&frames), [Duration::from_millis(10_500), Duration::from_micros(11_000_001)]);
+            assert_eq!((frames[0].link_type, frames[0].data.as_slice()), (1, b"abc".as_slice()));
+
+            let records: [(u32, u32, &[u8]); 1] = [(10, 250_000_000, b"abc")];
+            let frames = read_capture(&pcap(PCAP_MAGIC_NANOS, big_endian, &records)).unwrap();
+            assert_eq!(timestamps(&frames), [Duration::from_millis(10_250)]);
+        }
+    }
+
+    #[test]
+    fn pcap_invalid() {
+        assert!(read_capture(b"abc").is_err());
+        assert!(read_capture(&[0; 24]).is_err());
+
+        // Every cut inside the header or a record is an error, never a panic
+        let data = pcap(PCAP_MAGIC_MICROS, false, &[(1, 2, b"frame")]);
+        assert!(read_capture(&data[..24]).unwrap().is_empty());
+        for len in (0..data.len()).filter(|len| *len != 24) {
+            assert!(read_capture(&data[..len]).is_err(), "{}", len);
+        }
+    }
+
+    #[test]
+    fn pcapng_blocks() {
+        for big_endian in [false, true] {
+            let mut data = section_header(big_endian);
+            // Nanoseconds, the default microseconds and 1/8 seconds
+            data.extend(interface(1, Some(9), big_endian));
+            data.extend(interface(113, None, big_endian));
+            data.extend(interface(101, Some(0x83), big_endian));
+            data.extend(enhanced_packet(0, 1_500_000_000, b"first", big_endian));
+            data.extend(enhanced_packet(1, 2_000_001, b"second", big_endian));
+            data.extend(enhanced_packet(2, 20, b"third", big_endian));
+            // Unknown blocks are skipped
+            data.extend(block(0x0bad, &[1, 2, 3], big_endian));
+            let mut body = u32_bytes(6, big_endian).to_vec();
+            body.extend_from_slice(b"simple");
+            data.extend(block(PCAPNG_SIMPLE_PACKET, &body, big_endian));
+
+            let frames = read_capture(&data).unwrap();
+            assert_eq!(timestamps(&frames), [
+                Duration::from_millis(1500),
+                Duration::from_micros(2_000_001),
+                Duration::from_millis(2500),
+                Duration::ZERO,
+            ]);
+            let frames: Vec<(u32, &[u8])> = frames.iter().map(|frame| (frame.link_type, frame.data.as_slice())).collect();
+            assert_eq!(frames, [
+                (1, b"first".as_slice()),
+                (113, b"second".as_slice()),
+                (101, b"third".as_slice()),
+                (1, b"simple".as_slice()),
+            ]);
+        }
+    }
+
+    #[test]
+    fn pcapng_invalid() {
+        // Packet before any interface
+        let mut data = section_header(false);
+        data.extend(enhanced_packet(0, 0, b"frame", false));
+        assert!(read_capture(&data).is_err());
+
+        // Resolution past what 64 bits hold
+        let mut data = section_header(false);
+        data.extend(interface(1, Some(0x80 | 64), false));
+        assert!(read_capture(&data).is_err());
+
+        // Every cut inside a block is an error, never a panic
+        let header = section_header(false);
+        let interface = interface(1, Some(9), false);
+        let boundaries = [header.len(), header.len() + interface.len()];
+        let data = [header, interface, enhanced_packet(0, 0, b"frame", false)].concat();
+        for len in (4..data.len()).filter(|len| !boundaries.contains(len)) {
+            assert!(read_capture(&data[..len]).is_err(), "{}", len);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Replays pcap/pcapng captures through the netchannel decoder.
+//!
+//! Usage: `src-sniffer-replay <capture file> <server address:port> [--files <directory>] [--game <profile>]`
+//!
+//! Files transferred over the netchannel are written to `--files` when given. `--game` forces the
+//! engine branch the capture was taken on, otherwise each connection detects its own from the
+//! handshake and starts out as L4D2.
+
+mod capture;
+mod udp;
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, process};
+
+use src_sniffer_core::connection::{Connection, ConnectionTable};
+use src_sniffer_core::netchannel::Direction;
+use src_sniffer_core::profile::{GameProfile, DEFAULT_PROFILE, PROFILES};
+use src_sniffer_core::stringtables::StringTable;
+
+// Connections silent for that long are considered closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+const USAGE: &str = "<capture file> <server address:port> [--files <directory>] [--game <profile>]";
+
+// UDP 5-tuple of a conversation, as (client, server)
+type ConnectionKey = (SocketAddr, SocketAddr);
+
+fn format_timestamp(timestamp: Duration) -> String {
+    format!("{}.{:06}", timestamp.as_secs(), timestamp.subsec_micros())
+}
+
+fn print_summary(key: &ConnectionKey, connection: &Connection) {
+    println!("[{}] {} Connection closed, signon state {}, active {:.3}s, decoded as {} ({})",
+        format_timestamp(connection.last_seen), key.0, connection.signon_state,
+        connection.last_seen.saturating_sub(connection.first_seen).as_secs_f64(), connection.profile().title,
+        connection.profile_source());
+
+    if let Some(protocol) = connection.protocol_version {
+        println!("    Protocol version {}", protocol);
+    }
+    if let Some(reason) = &connection.reject_reason {
+        println!("    Rejected: {}", reason.to_string_lossy());
+    }
+
+    let tables = connection.string_tables();
+    if !tables.is_empty() {
+        let count = |table: Option<&StringTable>| table.map_or(0, |table| table.len());
+        println!("    {} string tables, {} models, {} sounds, {} downloadables", tables.len(),
+            count(tables.model_precache()), count(tables.sound_precache()), count(tables.downloadables()));
+    }
+
+    let roster = connection.roster();
+    if let Some(steam_id) = roster.local_steam_id {
+        println!("    Client {} {}", steam_id, roster.local_friends_name.as_deref().unwrap_or_default().to_string_lossy());
+    }
+    for player in roster.players() {
+        let steam_id = player.steam_id().map_or("BOT".to_string(), |steam_id| format!("{} {}", steam_id.steam3(), steam_id.steam64()));
+        print!("    Player #{} {} ({}) joined at {}", player.user_id(), player.name().to_string_lossy(), steam_id,
+            format_timestamp(player.joined));
+        if let Some(left) = player.left {
+            print!(", left at {}", format_timestamp(left));
+        }
+        for (name, until) in &player.name_history {
+            print!(", was {} until {}", name.to_string_lossy(), format_timestamp(*until));
+        }
+        println!();
+    }
+
+    let listened = connection.listened_events();
+    if !listened.is_empty() {
+        let names: Vec<String> = listened.iter().map(|name| name.to_string_lossy().into_owned()).collect();
+        println!("    Listening to {} of {} game events: {}", names.len(), connection.game_event_registry().len(), names.join(", "));
+    }
+
+    let entities = connection.entities();
+    if entities.create_on_client {
+        println!("    Entities not decoded, the server didn't send its tables");
+    } else if let Some(tick) = entities.tick() {
+        println!("    {} entities of {} classes at tick {}", entities.entities().count(), entities.classes().count(), tick);
+    }
+
+    for direction in [Direction::ClientToServer, Direction::ServerToClient] {
+        let stats = connection.stats(direction);
+        println!("    {} {} packets, {} bytes, {} messages, {} connectionless, {} errors ({} bad checksums), {} files",
+            direction, stats.packets, stats.bytes, stats.messages, stats.connectionless, stats.errors,
+            stats.checksum_errors, stats.files);
+        if stats.string_table_errors > 0 {
+            println!("    {} {} string table messages couldn't be read", direction, stats.string_table_errors);
+        }
+        if stats.user_message_errors > 0 {
+            println!("    {} {} user messages couldn't be decoded", direction, stats.user_message_errors);
+        }
+        if stats.game_event_errors > 0 {
+            println!("    {} {} game event messages couldn't be decoded", direction, stats.game_event_errors);
+        }
+        if stats.entity_errors > 0 {
+            println!("    {} {} entity messages couldn't be applied", direction, stats.entity_errors);
+        }
+        if stats.compressed_packets > 0 {
+            println!("    {} {} compressed packets, {} -> {} bytes",
+                direction, stats.compressed_packets, stats.compressed_bytes, stats.uncompressed_bytes);
+        }
+    }
+}
+
+// Removes `--name <value>` from the arguments and returns the value
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let pos = args.iter().position(|arg| arg == name)?;
+    if pos + 1 >= args.len() {
+        return None;
+    }
+    let value = args.remove(pos + 1);
+    args.remove(pos);
+    Some(value)
+}
+
+fn run(path: &str, server: SocketAddr, files_dir: Option<&Path>, profile: Option<&'static GameProfile>) -> Result<(), Box<dyn Error>> {
+    let data = fs::read(path)?;
+    let frames = capture::read_capture(&data)?;
+    let mut connections: ConnectionTable<ConnectionKey> = match profile {
+        Some(profile) => ConnectionTable::with_forced_profile(IDLE_TIMEOUT, profile),
+        None => ConnectionTable::new(IDLE_TIMEOUT, DEFAULT_PROFILE),
+    };
+
+    for frame in frames {
+        let Some(datagram) = udp::parse_frame(frame.link_type, &frame.data) else {
+            continue;
+        };
+
+        let (direction, client) = if datagram.destination == server {
+            (Direction::ClientToServer, datagram.source)
+        } else if datagram.source == server {
+            (Direction::ServerToClient, datagram.destination)
+        } else {
+            continue;
+        };
+
+        for (key, connection) in connections.evict_idle(frame.timestamp) {
+            print_summary(&key, &connection);
+        }
+
+        let connection = connections.get_or_insert((client, server), frame.timestamp);
+
+        let timestamp = format_timestamp(frame.timestamp);
+        let profile = connection.profile();
+        let result = connection.process_packet(datagram.payload, direction, frame.timestamp);
+        if !std::ptr::eq(profile, connection.profile()) {
+            println!("[{}] {} Decoding as {} ({})", timestamp, client, connection.profile().title, connection.profile_source());
+        }
+        if let Some(info) = connection.last_packet().and_then(|metadata| metadata.compression) {
+            println!("[{}] {} {} {} compressed packet, {} -> {} bytes ({:.1}%)",
+                timestamp, client, direction, info.method, info.compressed_size, info.uncompressed_size,
+                info.ratio() * 100.);
+        }
+        match result {
+            Ok(messages) => {
+                for message in messages {
+                    println!("[{}] {} {} {:?}", timestamp, client, direction, message);
+                }
+            },
+            Err(err) => println!("[{}] {} {} Failed to decode packet: {}", timestamp, client, direction, err),
+        }
+
+        for event in connection.take_game_events() {
+            println!("[{}] {} {} Game event {}", timestamp, client, direction, event);
+        }
+
+        for line in connection.take_transcript() {
+            let steam_id = line.steam_id.map_or(String::new(), |steam_id| format!(" ({})", steam_id));
+            println!("[{}] {} {} {}{}", timestamp, client, direction, line, steam_id);
+        }
+
+        for message in connection.take_connectionless() {
+            println!("[{}] {} {} {:?}", timestamp, client, direction, message);
+        }
+
+        for file in connection.take_files() {
+            println!("[{}] {} {} File transfer {} {:?} ({} bytes)",
+                timestamp, client, file.direction, file.transfer_id, file.filename, file.data.len());
+
+            if let Some(files_dir) = files_dir {
+                match file.save(files_dir) {
+                    Ok(saved) => println!("[{}] {} {} Saved to {}", timestamp, client, file.direction, saved.display()),
+                    Err(err) => println!("[{}] {} {} Failed to save file: {}", timestamp, client, file.direction, err),
+                }
+            }
+        }
+    }
+
+    for (key, connection) in connections.drain() {
+        print_summary(&key, &connection);
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().collect();
+
+    let files_dir = take_option(&mut args, "--files").map(PathBuf::from);
+
+    let profile = match take_option(&mut args, "--game") {
+        Some(name) => match GameProfile::by_name(&name) {
+            Some(profile) => Some(profile),
+            None => {
+                let names: Vec<&str> = PROFILES.iter().map(|profile| profile.name).collect();
+                eprintln!("Unknown game '{}', expected one of {}", name, names.join(", "));
+                process::exit(2);
+            }
+        },
+        None => None,
+    };
+
+    if args.len() != 3 {
+        eprintln!("Usage: {} {}", args[0], USAGE);
+        process::exit(2);
+    }
+
+    let server: SocketAddr = match args[2].parse() {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("Invalid server address '{}': {}", args[2], err);
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(&args[1], server, files_dir.as_deref(), profile) {
+        eprintln!("{}: {}", args[1], err);
+        process::exit(1);
+    }
+}
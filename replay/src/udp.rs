@@ -0,0 +1,296 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LOOP: u32 = 108;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+const IP_PROTOCOL_UDP: u8 = 17;
+
+// IPv6 extension headers we know how to skip
+const IPV6_HOP_BY_HOP: u8 = 0;
+const IPV6_ROUTING: u8 = 43;
+const IPV6_FRAGMENT: u8 = 44;
+const IPV6_DESTINATION: u8 = 60;
+
+#[derive(Debug)]
+pub struct Datagram<'a> {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub payload: &'a [u8],
+}
+
+fn be_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(pos..pos + 2)?.try_into().unwrap()))
+}
+
+// Extracts the UDP datagram of a link-layer frame. Returns `None` for anything else, including
+// fragmented IP packets which aren't reassembled.
+pub fn parse_frame(link_type: u32, frame: &[u8]) -> Option<Datagram<'_>> {
+    match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut ether_type = be_u16(frame, 12)?;
+            let mut pos = 14;
+            while ether_type == ETHERTYPE_VLAN || ether_type == ETHERTYPE_QINQ {
+                ether_type = be_u16(frame, pos + 2)?;
+                pos += 4;
+            }
+            parse_ether_type(ether_type, frame.get(pos..)?)
+        },
+        LINKTYPE_LINUX_SLL => parse_ether_type(be_u16(frame, 14)?, frame.get(16..)?),
+        LINKTYPE_LINUX_SLL2 => parse_ether_type(be_u16(frame, 0)?, frame.get(20..)?),
+        LINKTYPE_NULL | LINKTYPE_LOOP => {
+            // The address family is in the byte order of the capturing host
+            let family = u32::from_le_bytes(frame.get(..4)?.try_into().unwrap());
+            let family = if family > 0xffff { family.swap_bytes() } else { family };
+            match family {
+                2 => parse_ipv4(frame.get(4..)?),
+                // BSD variants disagree on the IPv6 value
+                24 | 28 | 30 => parse_ipv6(frame.get(4..)?),
+                _ => None,
+            }
+        },
+        LINKTYPE_RAW => match frame.first()? >> 4 {
+            4 => parse_ipv4(frame),
+            6 => parse_ipv6(frame),
+            _ => None,
+        },
+        LINKTYPE_IPV4 => parse_ipv4(frame),
+        LINKTYPE_IPV6 => parse_ipv6(frame),
+        _ => None,
+    }
+}
+
+fn parse_ether_type(ether_type: u16, packet: &[u8]) -> Option<Datagram<'_>> {
+    match ether_type {
+        ETHERTYPE_IPV4 => parse_ipv4(packet),
+        ETHERTYPE_IPV6 => parse_ipv6(packet),
+        _ => None,
+    }
+}
+
+fn parse_ipv4(packet: &[u8]) -> Option<Datagram<'_>> {
+    let header_len = ((packet.first()? & 0x0f) as usize) * 4;
+    let total_len = be_u16(packet, 2)? as usize;
+    let fragment = be_u16(packet, 6)?;
+    let protocol = *packet.get(9)?;
+
+    // More fragments flag or a fragment offset
+    if fragment & 0x3fff != 0 || protocol != IP_PROTOCOL_UDP {
+        return None;
+    }
+
+    let source = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(12..16)?).unwrap());
+    let destination = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(16..20)?).unwrap());
+
+    // Ethernet may pad the frame past the end of the IP packet
+    let packet = packet.get(..total_len.min(packet.len()))?;
+    parse_udp(IpAddr::V4(source), IpAddr::V4(destination), packet.get(header_len..)?)
+}
+
+fn parse_ipv6(packet: &[u8]) -> Option<Datagram<'_>> {
+    let payload_len = be_u16(packet, 4)? as usize;
+    let mut next_header = *packet.get(6)?;
+
+    let source = Ipv6Addr::from(<[u8; 16]>::try_from(packet.get(8..24)?).unwrap());
+    let destination = Ipv6Addr::from(<[u8; 16]>::try_from(packet.get(24..40)?).unwrap());
+
+    let packet = packet.get(40..)?;
+    let mut payload = packet.get(..payload_len.min(packet.len()))?;
+    loop {
+        match next_header {
+            IP_PROTOCOL_UDP => break,
+            IPV6_HOP_BY_HOP | IPV6_ROUTING | IPV6_DESTINATION => {
+                next_header = *payload.first()?;
+                let len = (*payload.get(1)? as usize + 1) * 8;
+                payload = payload.get(len..)?;
+            },
+            IPV6_FRAGMENT => return None,
+            _ => return None,
+        }
+    }
+
+    parse_udp(IpAddr::V6(source), IpAddr::V6(destination), payload)
+}
+
+fn parse_udp(source: IpAddr, destination: IpAddr, segment: &[u8]) -> Option<Datagram<'_>> {
+    let source_port = be_u16(segment, 0)?;
+    let destination_port = be_u16(segment, 2)?;
+    let len = be_u16(segment, 4)? as usize;
+
+    // The length includes the 8 bytes UDP header
+    let payload = segment.get(8..len.max(8).min(segment.len()))?;
+
+    Some(Datagram {
+        source: SocketAddr::new(source, source_port),
+        destination: SocketAddr::new(destination, destination_port),
+        payload,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"\xff\xff\xff\xffTSource Engine Query\0";
+
+    fn udp(payload: &[u8]) -> Vec<u8> {
+        let mut segment = 27005u16.to_be_bytes().to_vec();
+        segment.extend_from_slice(&27015u16.to_be_bytes());
+        segment.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        segment.extend_from_slice(&[0, 0]);
+        segment.extend_from_slice(payload);
+        segment
+    }
+
+    fn ipv4(protocol: u8, fragment: u16) -> Vec<u8> {
+        let segment = udp(PAYLOAD);
+        let mut packet = vec![0x45, 0];
+        packet.extend_from_slice(&(20 + segment.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        packet.extend_from_slice(&fragment.to_be_bytes());
+        packet.extend_from_slice(&[64, protocol, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        packet.extend_from_slice(&segment);
+        packet
+    }
+
+    // With a hop-by-hop options header before the UDP one
+    fn ipv6(hop_by_hop: bool) -> Vec<u8> {
+        let mut payload = Vec::new();
+        if hop_by_hop {
+            payload.extend_from_slice(&[IP_PROTOCOL_UDP, 0, 0, 0, 0, 0, 0, 0]);
+        }
+        payload.extend_from_slice(&udp(PAYLOAD));
+
+        let mut packet = vec![0x60, 0, 0, 0];
+        packet.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        packet.push(if hop_by_hop { IPV6_HOP_BY_HOP } else { IP_PROTOCOL_UDP });
+        packet.push(64);
+        packet.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        packet.extend_from_slice(&Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2).octets());
+        packet.extend_from_slice(&payload);
+        packet
+    }
+
+    fn ethernet(ether_types: &[u16], packet: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0xaa; 12];
+        for (i, ether_type) in ether_types.iter().enumerate() {
+            frame.extend_from_slice(&ether_type.to_be_bytes());
+            // Tag control information of the VLAN tags
+            if i + 1 < ether_types.len() {
+                frame.extend_from_slice(&[0, 42]);
+            }
+        }
+        frame.extend_from_slice(packet);
+        frame
+    }
+
+    fn assert_v4(datagram: Option<Datagram>) {
+        let datagram = datagram.unwrap();
+        assert_eq!(datagram.source, "10.0.0.1:27005".parse().unwrap());
+        assert_eq!(datagram.destination, "10.0.0.2:27015".parse().unwrap());
+        assert_eq!(datagram.payload, PAYLOAD);
+    }
+
+    fn assert_v6(datagram: Option<Datagram>) {
+        let datagram = datagram.unwrap();
+        assert_eq!(datagram.source, "[::1]:27005".parse().unwrap());
+        assert_eq!(datagram.destination, "[fe80::2]:27015".parse().unwrap());
+        assert_eq!(datagram.payload, PAYLOAD);
+    }
+
+    #[test]
+    fn ethernet_frames() {
+        assert_v4(parse_frame(LINKTYPE_ETHERNET, &ethernet(&[ETHERTYPE_IPV4], &ipv4(IP_PROTOCOL_UDP, 0))));
+        assert_v6(parse_frame(LINKTYPE_ETHERNET, &ethernet(&[ETHERTYPE_IPV6], &ipv6(false))));
+
+        // VLAN and QinQ tags
+        let frame = ethernet(&[ETHERTYPE_VLAN, ETHERTYPE_IPV4], &ipv4(IP_PROTOCOL_UDP, 0));
+        assert_v4(parse_frame(LINKTYPE_ETHERNET, &frame));
+        let frame = ethernet(&[ETHERTYPE_QINQ, ETHERTYPE_VLAN, ETHERTYPE_IPV6], &ipv6(true));
+        assert_v6(parse_frame(LINKTYPE_ETHERNET, &frame));
+
+        // Padding after the IP packet isn't part of the payload
+        let mut frame = ethernet(&[ETHERTYPE_IPV4], &ipv4(IP_PROTOCOL_UDP, 0));
+        frame.extend_from_slice(&[0; 16]);
+        assert_v4(parse_frame(LINKTYPE_ETHERNET, &frame));
+
+        assert!(parse_frame(LINKTYPE_ETHERNET, &ethernet(&[0x0806], &ipv4(IP_PROTOCOL_UDP, 0))).is_none());
+    }
+
+    #[test]
+    fn linux_sll() {
+        let mut frame = vec![0; 14];
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        frame.extend_from_slice(&ipv4(IP_PROTOCOL_UDP, 0));
+        assert_v4(parse_frame(LINKTYPE_LINUX_SLL, &frame));
+
+        let mut frame = ETHERTYPE_IPV6.to_be_bytes().to_vec();
+        frame.extend_from_slice(&[0; 18]);
+        frame.extend_from_slice(&ipv6(false));
+        assert_v6(parse_frame(LINKTYPE_LINUX_SLL2, &frame));
+    }
+
+    #[test]
+    fn loopback() {
+        for link_type in [LINKTYPE_NULL, LINKTYPE_LOOP] {
+            // Either byte order of the capturing host
+            let frame = [&2u32.to_le_bytes()[..], &ipv4(IP_PROTOCOL_UDP, 0)].concat();
+            assert_v4(parse_frame(link_type, &frame));
+            let frame = [&2u32.to_be_bytes()[..], &ipv4(IP_PROTOCOL_UDP, 0)].concat();
+            assert_v4(parse_frame(link_type, &frame));
+            for family in [24u32, 28, 30] {
+                let frame = [&family.to_le_bytes()[..], &ipv6(false)].concat();
+                assert_v6(parse_frame(link_type, &frame));
+            }
+            let frame = [&7u32.to_le_bytes()[..], &ipv4(IP_PROTOCOL_UDP, 0)].concat();
+            assert!(parse_frame(link_type, &frame).is_none());
+        }
+    }
+
+    #[test]
+    fn raw_ip() {
+        assert_v4(parse_frame(LINKTYPE_RAW, &ipv4(IP_PROTOCOL_UDP, 0)));
+        assert_v6(parse_frame(LINKTYPE_RAW, &ipv6(false)));
+        assert_v4(parse_frame(LINKTYPE_IPV4, &ipv4(IP_PROTOCOL_UDP, 0)));
+        assert_v6(parse_frame(LINKTYPE_IPV6, &ipv6(true)));
+
+        // TCP, fragments and unknown link types
+        assert!(parse_frame(LINKTYPE_RAW, &ipv4(6, 0)).is_none());
+        assert!(parse_frame(LINKTYPE_RAW, &ipv4(IP_PROTOCOL_UDP, 0x2000)).is_none());
+        assert!(parse_frame(LINKTYPE_RAW, &ipv4(IP_PROTOCOL_UDP, 0x0010)).is_none());
+        assert!(parse_frame(LINKTYPE_RAW, &[0x50]).is_none());
+        assert!(parse_frame(999, &ipv4(IP_PROTOCOL_UDP, 0)).is_none());
+    }
+
+    #[test]
+    fn truncated() {
+        let frames = [
+            (LINKTYPE_ETHERNET, ethernet(&[ETHERTYPE_VLAN, ETHERTYPE_IPV4], &ipv4(IP_PROTOCOL_UDP, 0)), 4 + 14 + 20 + 8),
+            (LINKTYPE_ETHERNET, ethernet(&[ETHERTYPE_IPV6], &ipv6(true)), 14 + 40 + 8 + 8),
+            (LINKTYPE_LINUX_SLL, [&[0; 14][..], &ETHERTYPE_IPV4.to_be_bytes(), &ipv4(IP_PROTOCOL_UDP, 0)].concat(), 16 + 20 + 8),
+            (LINKTYPE_NULL, [&2u32.to_le_bytes()[..], &ipv4(IP_PROTOCOL_UDP, 0)].concat(), 4 + 20 + 8),
+            (LINKTYPE_RAW, ipv6(false), 40 + 8),
+        ];
+        for (link_type, frame, headers) in frames {
+            for len in 0..frame.len() {
+                let datagram = parse_frame(link_type, &frame[..len]);
+                if len < headers {
+                    assert!(datagram.is_none(), "{} {}", link_type, len);
+                } else {
+                    // Cut in the payload, what is left of it
+                    assert_eq!(datagram.unwrap().payload, &PAYLOAD[..len - headers]);
+                }
+            }
+        }
+    }
+}
@@ -1,73 +1,78 @@
+// The injected frontend only exists on Windows builds with the `hook` feature enabled, the
+// decoding itself lives in `src-sniffer-core`
+#![cfg(feature = "hook")]
 #![allow(non_snake_case, unused_variables)]
 #![allow(dead_code)]
 
-mod bitreader;
-mod bitwriter;
-mod clc;
-
 use windows::Win32::Foundation::{BOOL, HANDLE};
 use windows::Win32::Networking::WinSock::SOCKET;
-use windows::Win32::Networking::WinSock::SOCKADDR;
+use windows::Win32::Networking::WinSock::{AF_INET, SOCKADDR};
 use windows::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress};
 use windows::core::{PCSTR, PCWSTR};
 
 use std::os::raw::{c_void, c_char};
 use std::error::Error;
 use std::{ffi::CString, ffi::c_int, iter, mem};
+use std::path::Path;
 use std::sync::{Mutex, LazyLock};
+use std::time::{Duration, Instant};
 
 use retour::static_detour;
-use bitreader::BitReader;
-use clc::*;
+use src_sniffer_core::connection::ConnectionTable;
+use src_sniffer_core::netchannel::Direction;
+use src_sniffer_core::profile::{GameProfile, DEFAULT_PROFILE};
 
 static_detour! {
     static SendtoHook: unsafe extern "system" fn(SOCKET, *mut c_char, c_int, c_int, *mut SOCKADDR, c_int) -> c_int;
+    static RecvfromHook: unsafe extern "system" fn(SOCKET, *mut c_char, c_int, c_int, *mut SOCKADDR, *mut c_int) -> c_int;
 }
 
 type FnSendto = unsafe extern "system" fn(SOCKET, *mut c_char, c_int, c_int, *mut SOCKADDR, c_int) -> c_int;
+type FnRecvfrom = unsafe extern "system" fn(SOCKET, *mut c_char, c_int, c_int, *mut SOCKADDR, *mut c_int) -> c_int;
 
-const PACKET_FLAG_RELIABLE:   u8 = 1 << 0;
-const PACKET_FLAG_COMPRESSED: u8 = 1 << 1;
-const PACKET_FLAG_ENCRYPTED:  u8 = 1 << 2;
-const PACKET_FLAG_SPLIT:      u8 = 1 << 3;
-const PACKET_FLAG_CHOKED:     u8 = 1 << 4;
+// Files transferred over the netchannel are written there when set
+const FILES_DIR_VAR: &str = "SRC_SNIFFER_FILES";
 
-#[repr(C, packed)]
-#[derive(Copy, Clone)]
-struct PacketFlag(u8);
+// Short name of the game profile to decode with, detected from the handshake when unset
+const GAME_VAR: &str = "SRC_SNIFFER_GAME";
 
-impl std::fmt::Debug for PacketFlag {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut flags = String::new();
+// Connections silent for that long are dropped along with their state
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
 
-        if self.0 & PACKET_FLAG_RELIABLE != 0 {
-            flags.push_str("Reliable ");
-        }
-        if self.0 & PACKET_FLAG_COMPRESSED != 0 {
-            flags.push_str("Compressed ");
-        }
-        if self.0 & PACKET_FLAG_ENCRYPTED != 0 {
-            flags.push_str("Encrypted ");
-        }
-        if self.0 & PACKET_FLAG_SPLIT != 0 {
-            flags.push_str("Split ");
-        }
-        if self.0 & PACKET_FLAG_CHOKED != 0 {
-            flags.push_str("Chocked ");
-        }
+// Socket and peer address of a connection
+type ConnectionKey = (usize, Vec<u8>);
+
+// The game is the client: what it sends goes to the server and what it receives comes from it
+static CONNECTIONS: LazyLock<Mutex<ConnectionTable<ConnectionKey>>> = LazyLock::new(|| {
+    Mutex::new(match game_profile() {
+        Some(profile) => ConnectionTable::with_forced_profile(IDLE_TIMEOUT, profile),
+        None => ConnectionTable::new(IDLE_TIMEOUT, DEFAULT_PROFILE),
+    })
+});
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
 
-        write!(f, "{}({})", flags, self.0)
+// Profile forced by the user, `None` to detect it
+fn game_profile() -> Option<&'static GameProfile> {
+    let name = std::env::var(GAME_VAR).ok()?;
+
+    let profile = GameProfile::by_name(&name);
+    if profile.is_none() {
+        println!("Unknown game '{}', detecting it", name);
     }
+    profile
 }
 
-#[repr(C, packed)]
-#[derive(Debug)]
-struct NetPacketHeader {
-    sequence: u32,
-    sequence_ack: u32,
-    flags: PacketFlag,
-    checksum: u16,
-    rel_state: u8,
+fn connection_key(s: SOCKET, addr: *const SOCKADDR, len: c_int) -> ConnectionKey {
+    if addr.is_null() || len <= 0 {
+        return (s.0, Vec::new());
+    }
+
+    let mut addr = unsafe { std::slice::from_raw_parts(addr as *const u8, len as usize) };
+    // Only keep the family, port and address of IPv4 peers, sin_zero isn't always zeroed
+    if addr.len() >= 8 && u16::from_le_bytes([addr[0], addr[1]]) == AF_INET.0 {
+        addr = &addr[..8];
+    }
+    (s.0, addr.to_vec())
 }
 
 /// Returns a module symbol's absolute address.
@@ -114,299 +119,84 @@ unsafe fn main() -> Result<(), Box<dyn Error>> {
         .initialize(target, sendto_detour)?
         .enable()?;
 
-    println!("Attached");
-
-    Ok(())
-}
-
-#[derive(Debug)]
-struct DataFragment {
-    filename: Vec<u8>,
-    buffer: Vec<u8>,
-    bytes: u32,
-    bits: u32,
-    is_compressed: bool,
-    num_fragments: i32,
-    acked_fragments: i32,
-}
-
-impl Default for DataFragment {
-    fn default() -> Self { 
-        Self {
-            filename: vec![0; 260],
-            buffer: vec![],
-            bytes: 0,
-            bits: 0,
-            is_compressed: false,
-            num_fragments: 0,
-            acked_fragments: 0,
-        }
-    }
-}
-
-static RECEIVE_LIST: LazyLock<Mutex<[DataFragment; 2]>> = LazyLock::new(|| { 
-    Mutex::new([Default::default(), Default::default()]) 
-});
-
-fn check_receiving_list(stream: usize) -> bool {
-    let data = &mut RECEIVE_LIST.lock().unwrap()[stream];
-
-    if data.buffer.is_empty() {
-        // ProcessMesssages without data_buffer
-        return true;
-    }
-    if data.acked_fragments < data.num_fragments {
-        // ProcessMessages without data_buffer 
-        return true;
-    }
-    if data.acked_fragments > data.num_fragments {
-        // ProcessMessages with data_buffer
-        return false;
-    }
-
-    if data.is_compressed {
-        todo!();
-    }
+    let address = get_module_symbol_address("WS2_32.dll", "recvfrom")
+        .expect("could not find 'recvfrom address");
+    let target: FnRecvfrom = mem::transmute(address);
 
-    if data.filename[0] == 0 {
-        // ProcessMessages with data_buffer
-        let mut reader = BitReader::new(data.buffer[..data.bytes as usize].to_vec());
-        if !process_messages(&mut reader) {
-            return false;
-        }
-    } else {
-        todo!();
-    }
+    RecvfromHook
+        .initialize(target, recvfrom_detour)?
+        .enable()?;
 
-    if !data.buffer.is_empty() {
-        data.buffer.clear();
+    match game_profile() {
+        Some(profile) => println!("Attached, decoding as {}", profile.title),
+        None => println!("Attached, detecting the game from the handshake"),
     }
 
-    // ProcessMessages without data_buffer 
-    true
+    Ok(())
 }
 
-fn process_control_message(command: u8, reader: &mut BitReader) -> bool {
-    // net_NOP
-    if command == 0 {
-        return true;
-    }
-
-    // net_Disconnect
-    if command == 1 {
-        let reason = reader.read_string();
-        println!("Disconnected. Reason: {:?}", reason);
-        return false;
+fn process_packet(key: ConnectionKey, buf: *const c_char, len: c_int, direction: Direction) {
+    if buf.is_null() || len <= 0 {
+        return;
     }
 
-    // net_File
-    if command == 2 {
-        let transfer_id = reader.read_u32(32);
-        let string = reader.read_string();
-
-        if reader.read_u8(1) != 0 {
-            println!("File requested {:?} {transfer_id}", string);
-        } else {
-            println!("File denied {:?} {transfer_id}", string);
-        }
+    let packet: &[u8] = unsafe { std::slice::from_raw_parts(buf as *const u8, len as usize) };
+    let now = START.elapsed();
 
-        return true;
+    let mut connections = CONNECTIONS.lock().unwrap();
+    for (key, connection) in connections.evict_idle(now) {
+        println!("Connection on socket {} closed, {} packets sent, {} received",
+            key.0, connection.client_to_server.packets, connection.server_to_client.packets);
     }
 
-    false
-}
-
-fn process_messages(reader: &mut BitReader) -> bool {
-    'parse_loop: loop {
-        if reader.bits_left() < 6 {
-            break;
-        }
+    let connection = connections.get_or_insert(key, now);
 
-        let command = reader.read_u8(6);
-        if command <= 2 {
-            if !process_control_message(command, reader) {
-                return false;
-            }
-            continue;
-        }
+    // The packet is always passed through untouched, even if it couldn't be decoded
+    let profile = connection.profile();
+    let result = connection.process_packet(packet, direction, now);
+    if !std::ptr::eq(profile, connection.profile()) {
+        println!("Decoding as {} ({})", connection.profile().title, connection.profile_source());
+    }
 
-        //println!("Command {command}");
-        //println!("Command content {:02x?}", &reader.content[reader.pos / 8..]); 
-        //println!("Bits left {}", reader.bits_left());
-
-        match command {
-            NET_NOP => {
-            },
-            NET_TICK => {
-                NETTick::parse(reader);
-            },
-            NET_STRINGCMD => {
-                NETStringCmd::parse(reader);
-            },
-            NET_SETCONVAR => {
-                NETSetConVar::parse(reader);
-            },
-            NET_SIGNONSTATE => {
-                NETSignonState::parse(reader);
-            },
-            CLC_CLIENTINFO => {
-                CLCClientInfo::parse(reader);
-            },
-            CLC_MOVE => {
-                CLCMove::parse(reader);
-            },
-            CLC_BASELINEACK => {
-                CLCBaselineAck::parse(reader);
-            },
-            CLC_LISTENEVENTS => {
-                CLCListenEvents::parse(reader);
-            },
-            CLC_LOADINGPROGRESS => {
-                CLCLoadingProgress::parse(reader);
-            },
-            CLC_CMDKEYVALUES => {
-                CmdKeyValues::parse(reader);
-            },
-            _ => {
-                println!("Command {}", command);
-                println!("NOT IMPLEMENTED");
-                break 'parse_loop;
+    match result {
+        Ok(messages) => {
+            for message in messages {
+                println!("{} {:?}", direction, message);
             }
-        }
+        },
+        Err(err) => println!("{} Failed to decode packet: {}", direction, err),
     }
 
-    true
-}
-
-fn read_sub_channel_data(reader: &mut BitReader, stream: usize) -> bool {
-    let data = &mut RECEIVE_LIST.lock().unwrap()[stream];
-
-    let mut start_fragment: i32 = 0;
-    let mut num_fragments: i32 = 0;
-    let mut offset: u32 = 0;
-    let mut length: u32 = 0;
-
-    let single_block: bool = reader.read_u8(1) == 0;
-
-    if !single_block {
-        start_fragment = reader.read_u32(18) as i32;
-        num_fragments = reader.read_u8(3) as i32;
-        offset = (start_fragment * (1 << 8)) as u32;
-        length = (num_fragments * (1 << 8)) as u32;
+    for message in connection.take_connectionless() {
+        println!("{} {:?}", direction, message);
     }
 
-    if offset == 0 {
-        data.filename[0] = 0;
-        data.is_compressed = false;
+    for file in connection.take_files() {
+        println!("{} File transfer {} {:?} ({} bytes)", file.direction, file.transfer_id, file.filename, file.data.len());
 
-        if single_block {
-            // Check if the data is compressed
-            if reader.read_u8(1) == 1 {
-                data.is_compressed = true;
-                let _ = reader.read_u32(26);
-            }
-            // L4D2
-            data.bytes = reader.read_u32(18);
-            // L4D1
-            //data.bytes = reader.read_u32(17);
-        } else {
-            if reader.read_u8(1) == 1 {
-                let _ = reader.read_u32(32);
-                let filename = reader.read_string();
-                let filename = filename.to_bytes();
-                data.filename[..filename.len()].copy_from_slice(filename);
+        if let Some(files_dir) = std::env::var_os(FILES_DIR_VAR) {
+            match file.save(Path::new(&files_dir)) {
+                Ok(saved) => println!("{} Saved to {}", file.direction, saved.display()),
+                Err(err) => println!("{} Failed to save file: {}", file.direction, err),
             }
-
-            if reader.read_u8(1) == 1 {
-                data.is_compressed = true;
-                let _ = reader.read_u32(26);
-            }
-            data.bytes = reader.read_u32(26);
-        }
-
-        if !data.buffer.is_empty() {
-            data.buffer.clear();
-        }
-
-        data.bits = data.bytes * 8;
-        data.buffer = vec![0; ((((data.bytes) + (4-1)) / 4) * 4) as usize];
-        data.num_fragments = ((data.bytes + (1 << 8) - 1) / (1 << 8)) as i32;
-        data.acked_fragments = 0;
-
-        if single_block {
-            num_fragments = data.num_fragments as i32; 
-            length = (num_fragments * (1 << 8)) as u32;
-        }
-    } else {
-        if data.buffer.is_empty() {
-            return false;
         }
     }
-
-    if start_fragment + num_fragments == data.num_fragments as i32 {
-        let rest = (1 << 8) - (data.bytes % (1 << 8));
-        if rest < (1 << 8) {
-            length -= rest;
-        }
-    }
-
-    assert!(offset + length <= data.bytes);
-
-    // buf.ReadBytes
-    for i in 0..length {
-        *data.buffer.get_mut((offset + i) as usize).unwrap() = reader.read_u8(8);
-    }
-
-    data.acked_fragments += num_fragments;
-    true
 }
 
 fn sendto_detour(s: SOCKET, buf: *mut c_char, len: c_int, flags: c_int, to: *mut SOCKADDR, tolen: c_int) -> c_int {
-    let packet: &mut [u8] = unsafe { std::slice::from_raw_parts_mut(buf as *mut u8, len as usize) };
-    let header: NetPacketHeader = unsafe { std::ptr::read(packet.as_ptr() as _) };
-
-    // CONNECTIONLESS_HEADER
-    if packet[..4] == [0xff, 0xff, 0xff, 0xff] {
-        return unsafe { SendtoHook.call(s, buf, len, flags, to, tolen) };
-    }
-
+    process_packet(connection_key(s, to, tolen), buf, len, Direction::ClientToServer);
 
-    let content;
-    if header.flags.0 & PACKET_FLAG_CHOKED != 0 {
-        // Chocked packet
-        content = &packet[std::mem::size_of::<NetPacketHeader>() + 1..];
-    } else {
-        content = &packet[std::mem::size_of::<NetPacketHeader>()..];
-    }
-    
-    let mut reader = BitReader::new(content.to_vec());
-
-    // Read subchannel data
-    if header.flags.0 & PACKET_FLAG_RELIABLE != 0 {
-        let bit = 1 << reader.read_u8(3);
-
-        for i in 0..2 {
-            if reader.read_u8(1) != 0 {
-                if !read_sub_channel_data(&mut reader, i) {
-                    return unsafe { SendtoHook.call(s, buf, len, flags, to, tolen) }
-                }
-            }
-        }
+    unsafe { SendtoHook.call(s, buf, len, flags, to, tolen) }
+}
 
-        for i in 0..2 {
-            if !check_receiving_list(i) {
-                return unsafe { SendtoHook.call(s, buf, len, flags, to, tolen) }
-            }
-        }
-    }
+fn recvfrom_detour(s: SOCKET, buf: *mut c_char, len: c_int, flags: c_int, from: *mut SOCKADDR, fromlen: *mut c_int) -> c_int {
+    let received = unsafe { RecvfromHook.call(s, buf, len, flags, from, fromlen) };
 
-    if reader.bits_left() > 0 {
-        if !process_messages(&mut reader) {
-            return unsafe { SendtoHook.call(s, buf, len, flags, to, tolen) }
-        }
-    } else {
-        println!("No bits left");
+    // Errors and empty reads are returned as is
+    if received > 0 {
+        let fromlen = if fromlen.is_null() { 0 } else { unsafe { *fromlen } };
+        process_packet(connection_key(s, from, fromlen), buf, received, Direction::ServerToClient);
     }
 
-    unsafe { SendtoHook.call(s, buf, len, flags, to, tolen) }
+    received
 }